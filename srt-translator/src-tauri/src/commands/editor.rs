@@ -0,0 +1,218 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tauri::{AppHandle, Emitter, State};
+
+use super::jobs::{translated_output_path, write_job_output};
+use crate::editor::{EditLog, Provenance, SavedEdits};
+use crate::srt::SrtDocument;
+use crate::state::{AppState, FileData, JobStatus, TranslationJob};
+
+/// One cue of a job, source beside translation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CuePair {
+    pub id: usize,
+    pub start: String,
+    pub end: String,
+    pub source: String,
+    pub translation: Option<String>,
+    /// What the model wrote, for "revert".
+    pub machine: Option<String>,
+    pub provenance: Provenance,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CueChange {
+    pub cue: CuePair,
+    pub can_undo: bool,
+    pub can_redo: bool,
+}
+
+fn cue_pair(doc: &SrtDocument, job: &TranslationJob, cue_id: usize) -> Result<CuePair, String> {
+    let cue = doc
+        .cues
+        .iter()
+        .find(|c| c.id == cue_id)
+        .ok_or_else(|| format!("Cue not found: {}", cue_id))?;
+    let translation = job.translated.as_ref().and_then(|t| t.get(&cue_id)).cloned();
+    let (machine, provenance) = match &job.edits {
+        Some(edits) => (edits.machine_text(cue_id).map(str::to_string), edits.provenance(cue_id)),
+        None => (translation.clone(), Provenance::Machine),
+    };
+    Ok(CuePair {
+        id: cue.id,
+        start: cue.start.format(),
+        end: cue.end.format(),
+        source: cue.text_lines.join("\n"),
+        translation,
+        machine,
+        provenance,
+    })
+}
+
+/// The cues `translated` is keyed by: the job's own when it merged/split cues, else the file's.
+fn job_document(files: &HashMap<String, FileData>, job: &TranslationJob) -> Result<SrtDocument, String> {
+    match &job.document {
+        Some(doc) => Ok(doc.clone()),
+        None => files
+            .get(&job.info.file_id)
+            .map(|f| f.document.clone())
+            .ok_or_else(|| format!("File not found: {}", job.info.file_id)),
+    }
+}
+
+/// Apply `op` to a finished job's translation; `op` returns the cue it changed, if any.
+/// The result is saved to the job's history entry, so reopening the job keeps it.
+fn change_cue(
+    app: &AppHandle,
+    state: &AppState,
+    job_id: &str,
+    nothing: &str,
+    op: impl FnOnce(&SrtDocument, &mut EditLog, &mut HashMap<usize, String>) -> Result<Option<usize>, String>,
+) -> Result<CueChange, String> {
+    let (change, translated, saved) = {
+        let files = state.files.lock().unwrap();
+        let mut jobs = state.jobs.lock().unwrap();
+        apply_change(&files, &mut jobs, job_id, nothing, op)?
+    };
+    if let Err(e) = state.history.lock().unwrap().save_edits(job_id, &translated, saved) {
+        let _ = app.emit("translation://warning", format!("The edit couldn't be saved to the history: {}", e));
+    }
+    Ok(change)
+}
+
+fn apply_change(
+    files: &HashMap<String, FileData>,
+    jobs: &mut HashMap<String, TranslationJob>,
+    job_id: &str,
+    nothing: &str,
+    op: impl FnOnce(&SrtDocument, &mut EditLog, &mut HashMap<usize, String>) -> Result<Option<usize>, String>,
+) -> Result<(CueChange, HashMap<usize, String>, SavedEdits), String> {
+    let job = jobs
+        .get_mut(job_id)
+        .ok_or_else(|| format!("Job not found: {}", job_id))?;
+    if matches!(job.info.status, JobStatus::Running | JobStatus::Paused) {
+        return Err("This job is still running; edit it once it has finished.".into());
+    }
+    let doc = job_document(files, job)?;
+    let translated = job
+        .translated
+        .as_mut()
+        .ok_or_else(|| "This job has no translation yet.".to_string())?;
+    let edits = job.edits.get_or_insert_with(|| EditLog::new(translated));
+
+    let cue_id = op(&doc, edits, translated)?.ok_or_else(|| nothing.to_string())?;
+    let (can_undo, can_redo, saved) = (edits.can_undo(), edits.can_redo(), edits.saved());
+    let translated = translated.clone();
+    Ok((CueChange { cue: cue_pair(&doc, job, cue_id)?, can_undo, can_redo }, translated, saved))
+}
+
+fn require_cue(doc: &SrtDocument, cue_id: usize) -> Result<(), String> {
+    if doc.cues.iter().any(|c| c.id == cue_id) {
+        Ok(())
+    } else {
+        Err(format!("Cue not found: {}", cue_id))
+    }
+}
+
+/// Every cue of a job with its source text, translation and who wrote it.
+#[tauri::command]
+pub fn get_job_cues(
+    job_id: String,
+    state: State<AppState>,
+) -> Result<Vec<CuePair>, String> {
+    let files = state.files.lock().unwrap();
+    let jobs = state.jobs.lock().unwrap();
+    let job = jobs
+        .get(&job_id)
+        .ok_or_else(|| format!("Job not found: {}", job_id))?;
+    let doc = job_document(&files, job)?;
+    doc.cues.iter().map(|cue| cue_pair(&doc, job, cue.id)).collect()
+}
+
+#[tauri::command]
+pub fn edit_cue(
+    job_id: String,
+    cue_id: usize,
+    text: String,
+    app: AppHandle,
+    state: State<AppState>,
+) -> Result<CueChange, String> {
+    change_cue(&app, &state, &job_id, "", |doc, edits, translated| {
+        require_cue(doc, cue_id)?;
+        edits.edit(translated, cue_id, text);
+        Ok(Some(cue_id))
+    })
+}
+
+/// Put back the model's translation of a cue.
+#[tauri::command]
+pub fn revert_cue(
+    job_id: String,
+    cue_id: usize,
+    app: AppHandle,
+    state: State<AppState>,
+) -> Result<CueChange, String> {
+    change_cue(&app, &state, &job_id, "", |doc, edits, translated| {
+        require_cue(doc, cue_id)?;
+        edits.revert(translated, cue_id);
+        Ok(Some(cue_id))
+    })
+}
+
+#[tauri::command]
+pub fn undo_edit(
+    job_id: String,
+    app: AppHandle,
+    state: State<AppState>,
+) -> Result<CueChange, String> {
+    change_cue(&app, &state, &job_id, "Nothing to undo.", |_, edits, translated| Ok(edits.undo(translated)))
+}
+
+#[tauri::command]
+pub fn redo_edit(
+    job_id: String,
+    app: AppHandle,
+    state: State<AppState>,
+) -> Result<CueChange, String> {
+    change_cue(&app, &state, &job_id, "Nothing to redo.", |_, edits, translated| Ok(edits.redo(translated)))
+}
+
+/// Write the edited translation out again, in the job's output format and encoding;
+/// to the job's output file unless `path` is given. Returns the path written.
+#[tauri::command]
+pub fn export_edited_job(
+    job_id: String,
+    path: Option<String>,
+    app: AppHandle,
+    state: State<AppState>,
+) -> Result<String, String> {
+    let (doc, source, translated, options, output_path) = {
+        let files = state.files.lock().unwrap();
+        let jobs = state.jobs.lock().unwrap();
+        let job = jobs
+            .get(&job_id)
+            .ok_or_else(|| format!("Job not found: {}", job_id))?;
+        let translated = job
+            .translated
+            .clone()
+            .ok_or_else(|| "This job has no translation yet.".to_string())?;
+        let options = job.options.clone().ok_or("Job options not found")?;
+        let file_data = files
+            .get(&job.info.file_id)
+            .ok_or_else(|| format!("File not found: {}", job.info.file_id))?;
+        let output_format = options.output_format.unwrap_or_else(|| file_data.source.format());
+        let output_path = path
+            .or_else(|| job.info.output_path.clone())
+            .unwrap_or_else(|| translated_output_path(&file_data.item.path, output_format.extension()));
+        (
+            job.document.clone().unwrap_or_else(|| file_data.document.clone()),
+            file_data.source.clone(),
+            translated,
+            options,
+            output_path,
+        )
+    };
+
+    write_job_output(&app, &output_path, &doc, &source, &translated, &options)?;
+    Ok(output_path)
+}
@@ -1,47 +1,23 @@
-use std::path::Path;
 use tauri::State;
 
-use crate::state::{AppState, FileData, FileItem, FileStatus, generate_id};
-use crate::srt::parse_srt_file;
+use crate::state::{AppState, FileData, FileItem, generate_id};
 
+/// Import subtitle files. Damaged SRT is repaired unless `lenient` is false (then it's rejected);
+/// each returned item's `diagnostics` lists the fixes, for an "imported with N fixes" notice.
 #[tauri::command]
 pub fn import_srt_files(
     paths: Vec<String>,
+    lenient: Option<bool>,
     state: State<AppState>,
 ) -> Result<Vec<FileItem>, String> {
+    let lenient = lenient.unwrap_or(true);
     let mut files = state.files.lock().unwrap();
     let mut imported = Vec::new();
 
     for path_str in paths {
-        let path = Path::new(&path_str);
-        
-        // Parse the SRT file
-        let document = parse_srt_file(path).map_err(|e| e.to_string())?;
-        
-        let file_id = generate_id();
-        let file_name = path
-            .file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or("unknown.srt")
-            .to_string();
-        
-        let item = FileItem {
-            id: file_id.clone(),
-            path: path_str,
-            name: file_name,
-            cue_count: document.cues.len(),
-            status: FileStatus::Ready,
-        };
-        
-        files.insert(
-            file_id,
-            FileData {
-                item: item.clone(),
-                document,
-            },
-        );
-        
-        imported.push(item);
+        let file = FileData::load(generate_id(), &path_str, lenient)?;
+        imported.push(file.item.clone());
+        files.insert(file.item.id.clone(), file);
     }
 
     Ok(imported)
@@ -0,0 +1,17 @@
+use crate::translate::glossary::{self, Glossary};
+
+#[tauri::command]
+pub fn list_glossaries() -> Result<Vec<Glossary>, String> {
+    glossary::list_glossaries()
+}
+
+/// Create or replace the glossary with this name.
+#[tauri::command]
+pub fn save_glossary(glossary: Glossary) -> Result<(), String> {
+    glossary::save_glossary(&glossary)
+}
+
+#[tauri::command]
+pub fn delete_glossary(name: String) -> Result<(), String> {
+    glossary::delete_glossary(&name)
+}
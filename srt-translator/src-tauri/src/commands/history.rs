@@ -0,0 +1,111 @@
+use std::path::Path;
+use tauri::State;
+
+use crate::editor::EditLog;
+use crate::history::{write_history_csv, HistoryFilter, HistoryRecord};
+use crate::state::{generate_id, AppState, FileData, JobInfo, TranslationJob};
+use crate::translate::control::JobControl;
+
+/// Past jobs matching `filter`, newest first.
+#[tauri::command]
+pub fn list_history(
+    filter: Option<HistoryFilter>,
+    state: State<AppState>,
+) -> Result<Vec<HistoryRecord>, String> {
+    Ok(state.history.lock().unwrap().list(&filter.unwrap_or_default()))
+}
+
+/// Bring an old job's translations back as a job (with its file re-imported if needed),
+/// so they can be reviewed, exported or edited like a fresh one.
+#[tauri::command]
+pub fn reopen_history_job(
+    job_id: String,
+    state: State<AppState>,
+) -> Result<JobInfo, String> {
+    if let Some(job) = state.jobs.lock().unwrap().get(&job_id) {
+        return Ok(job.info.clone());
+    }
+
+    let (record, saved) = {
+        let history = state.history.lock().unwrap();
+        let record = history
+            .get(&job_id)
+            .cloned()
+            .ok_or_else(|| format!("Job not in history: {}", job_id))?;
+        (record, history.translations(&job_id)?)
+    };
+
+    let mut files = state.files.lock().unwrap();
+    let existing = files
+        .values()
+        .find(|f| f.item.path == record.file_path)
+        .map(|f| (f.item.id.clone(), f.source_hash.clone()));
+    let (file_id, source_hash) = match existing {
+        Some(found) => found,
+        None => {
+            let file = FileData::load(generate_id(), &record.file_path, true)?;
+            let found = (file.item.id.clone(), file.source_hash.clone());
+            files.insert(file.item.id.clone(), file);
+            found
+        }
+    };
+    if !saved.source_hash.is_empty() && saved.source_hash != source_hash {
+        return Err(format!(
+            "{} changed since this job translated it; its translations no longer line up with the cues.",
+            record.file_name
+        ));
+    }
+    drop(files);
+
+    let info = JobInfo {
+        id: record.job_id.clone(),
+        file_id,
+        status: record.status,
+        progress: 100.0,
+        eta_seconds: 0,
+        output_path: record.output_path,
+        error: record.error,
+        failed: Vec::new(),
+    };
+    let edits = saved.edits.map(|edits| EditLog::restore(&saved.translated, edits));
+    state.jobs.lock().unwrap().insert(
+        record.job_id,
+        TranslationJob {
+            info: info.clone(),
+            options: Some(saved.options),
+            translated: Some(saved.translated),
+            document: saved.document,
+            cue_origins: saved.cue_origins,
+            control: JobControl::new(),
+            edits,
+        },
+    );
+    Ok(info)
+}
+
+/// Remove entries and their saved translations; returns how many were removed.
+#[tauri::command]
+pub fn delete_history(
+    job_ids: Vec<String>,
+    state: State<AppState>,
+) -> Result<usize, String> {
+    state.history.lock().unwrap().delete(&job_ids)
+}
+
+/// Export matching entries as JSON or CSV (by extension); returns how many were written.
+#[tauri::command]
+pub fn export_history(
+    path: String,
+    filter: Option<HistoryFilter>,
+    state: State<AppState>,
+) -> Result<usize, String> {
+    let records = state.history.lock().unwrap().list(&filter.unwrap_or_default());
+    let path = Path::new(&path);
+    let content = match path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase()).as_deref() {
+        Some("json") => serde_json::to_string_pretty(&records).map_err(|e| e.to_string())?,
+        Some("csv") => write_history_csv(&records),
+        _ => return Err(format!("Unsupported file type: {} (use .json or .csv)", path.display())),
+    };
+    std::fs::write(path, content).map_err(|e| format!("Failed to save file: {}", e))?;
+    Ok(records.len())
+}
@@ -1,9 +1,18 @@
 use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
 use tauri::{AppHandle, State, Emitter};
 
+use crate::editor::cue_mapping;
+use crate::history::{HistoryRecord, HistoryTranslations};
 use crate::state::{AppState, JobInfo, JobStatus, TranslationJob, generate_id};
-use crate::translate::worker::{TranslationOptions, translate_document};
-use crate::srt::write_srt;
+use crate::translate::checkpoint::{checkpoint_path, Checkpoint, CheckpointHeader};
+use crate::translate::control::JobControl;
+use crate::translate::memory::now_secs;
+use crate::translate::speakers::detect_turns;
+use crate::translate::tokens::TokenUsage;
+use crate::translate::worker::{DocumentTranslation, JobHandles, TranslateError, TranslationOptions, UntranslatedText, translate_document};
+use crate::srt::{bilingual::{merge_texts, write_bilingual}, encoding::{encode_text, resolve_encoding, unrepresentable_cues, UnrepresentableCue}, qa::{check_translation, QaProfile, QaReport}, restructure::fit_reading_speed, wrap::wrap_text, write_subtitle, SrtDocument, SubtitleSource};
 
 #[tauri::command]
 pub fn create_job(
@@ -15,6 +24,13 @@ pub fn create_job(
     let file_data = files
         .get(&file_id)
         .ok_or_else(|| format!("File not found: {}", file_id))?;
+
+    if let Some(out) = &options.output_encoding {
+        resolve_encoding(&out.encoding)?;
+    }
+    if let Some(format) = options.output_format {
+        file_data.source.check_output(format)?;
+    }
     
     let job_id = generate_id();
     let info = JobInfo {
@@ -25,6 +41,7 @@ pub fn create_job(
         eta_seconds: 0,
         output_path: None,
         error: None,
+        failed: Vec::new(),
     };
     
     drop(files);
@@ -36,106 +53,508 @@ pub fn create_job(
             info: info.clone(),
             options: Some(options),
             translated: None,
+            document: None,
+            cue_origins: None,
+            control: JobControl::new(),
+            edits: None,
         },
     );
     
     Ok(info)
 }
 
+/// "movie.en.ass" -> "movie.en_translated.ass", next to the source file.
+pub fn translated_output_path(file_path: &str, extension: &str) -> String {
+    let path = Path::new(file_path);
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("output");
+    path.with_file_name(format!("{}_translated.{}", stem, extension))
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Write `translated` over the source in the job's output format and encoding; cues without
+/// a translation get their source text or a marker. Returns the encoding used and the cues it
+/// couldn't represent.
+pub fn write_job_output(
+    app: &AppHandle,
+    output_path: &str,
+    doc: &SrtDocument,
+    source: &SubtitleSource,
+    translated: &HashMap<usize, String>,
+    opts: &TranslationOptions,
+) -> Result<(String, Vec<UnrepresentableCue>), String> {
+    let output_format = opts.output_format.unwrap_or_else(|| source.format());
+    let output_encoding = &opts.output_encoding;
+
+    // Cues of failed batches are written as their source text or a marker;
+    // `translated` keeps only real translations (for QA, export, the job)
+    let mut output = translated.clone();
+    for cue in &doc.cues {
+        output.entry(cue.id).or_insert_with(|| match &opts.untranslated_text {
+            UntranslatedText::Source => cue.text_lines.join("\n"),
+            UntranslatedText::Marker(marker) => marker.clone(),
+        });
+    }
+
+    let (srt_content, written) = match &opts.bilingual {
+        Some(bi) => (
+            write_bilingual(doc, source, &output, bi, output_format),
+            merge_texts(doc, &output, bi, output_format),
+        ),
+        None => (write_subtitle(doc, source, &output, output_format), output.clone()),
+    };
+    let srt_content = srt_content.map_err(|e| format!("Failed to write subtitles: {}", e))?;
+
+    // Encode: the requested output encoding, else the source encoding
+    // (e.g. a Windows-1258 file stays Windows-1258)
+    let (encoding, bom) = match output_encoding {
+        Some(out) => (out.encoding.clone(), out.bom),
+        None => (doc.encoding.clone(), doc.bom),
+    };
+    let mut unrepresentable = unrepresentable_cues(doc, &written, &encoding)?;
+    let (encoding, bom) = if output_encoding.is_none() && !unrepresentable.is_empty() {
+        // The source encoding can't hold the target language; don't write '?' unasked
+        let _ = app.emit(
+            "translation://warning",
+            format!(
+                "The translation can't be stored as {} ({} cues affected), so it was saved as UTF-8.",
+                encoding,
+                unrepresentable.len()
+            ),
+        );
+        unrepresentable.clear();
+        ("UTF-8".to_string(), false)
+    } else {
+        (encoding, bom)
+    };
+    if !unrepresentable.is_empty() {
+        let _ = app.emit(
+            "translation://warning",
+            format!(
+                "{} cues contain characters that {} can't represent; they were written as '?'.",
+                unrepresentable.len(),
+                encoding
+            ),
+        );
+    }
+
+    let encoded = encode_text(&srt_content, &encoding, bom)?;
+    std::fs::write(output_path, encoded.bytes)
+        .map_err(|e| format!("Failed to save file: {}", e))?;
+    Ok((encoding, unrepresentable))
+}
+
 #[tauri::command]
 pub async fn start_job(
     job_id: String,
     app: AppHandle,
     state: State<'_, AppState>,
 ) -> Result<(), String> {
-    // Get file document
-    let (doc, file_name, file_path) = {
+    // Claim the job and get its file document; the check and the claim share one lock,
+    // so two starts (or a start racing the queue) can't both run it
+    let (doc, source, file_name, file_path, file_id, source_hash, control, opts, previous_status) = {
         let files = state.files.lock().unwrap();
-        let jobs = state.jobs.lock().unwrap();
+        let mut jobs = state.jobs.lock().unwrap();
         
         let job = jobs
-            .get(&job_id)
+            .get_mut(&job_id)
             .ok_or_else(|| format!("Job not found: {}", job_id))?;
+        match job.info.status {
+            JobStatus::Running | JobStatus::Paused => return Err("This job is already running.".into()),
+            JobStatus::Cancelled => return Err("This job was cancelled.".into()),
+            _ => {}
+        }
         
         let file_data = files
             .get(&job.info.file_id)
             .ok_or_else(|| format!("File not found: {}", job.info.file_id))?;
+        let opts = job.options.clone().ok_or("Job options not found")?;
         
+        // A new run: whatever state the last one ended in no longer applies
+        job.control.reset();
+        let previous_status = std::mem::replace(&mut job.info.status, JobStatus::Running);
         (
             file_data.document.clone(),
+            file_data.source.clone(),
             file_data.item.name.clone(),
             file_data.item.path.clone(),
+            job.info.file_id.clone(),
+            file_data.source_hash.clone(),
+            job.control.clone(),
+            opts,
+            previous_status,
         )
     };
-    
-    // Get translation options from job
-    let opts = {
-        let jobs = state.jobs.lock().unwrap();
-        let job = jobs.get(&job_id).unwrap();
-        
-        // Use options stored in job
-        job.options.clone().expect("Job options not found")
+    // Hand the job back as it was if it can't start after all
+    let release = |error: String| {
+        if let Some(job) = state.jobs.lock().unwrap().get_mut(&job_id) {
+            job.info.status = previous_status.clone();
+        }
+        error
     };
-    
-    // Update job status to running
-    {
-        let mut jobs = state.jobs.lock().unwrap();
-        if let Some(job) = jobs.get_mut(&job_id) {
-            job.info.status = JobStatus::Running;
+    let output_format = opts.output_format.unwrap_or_else(|| source.format());
+    let qa_profile = opts.qa_profile.clone().unwrap_or_default();
+    let wrap = opts.wrap.clone();
+    let fit = opts.fit_reading_speed;
+    let untranslated_text = opts.untranslated_text.clone();
+    let job_options = opts.clone();
+
+    // Speakers come from ASS actor fields, or `NAME:` prefixes in the text
+    let turns = match &opts.speakers {
+        Some(table) => {
+            let actors: Option<Vec<String>> = match &source {
+                SubtitleSource::Ass(ass) => Some(ass.events.iter().map(|ev| ev.actor.clone()).collect()),
+                _ => None,
+            };
+            detect_turns(&doc.cues, actors.as_deref(), table)
         }
-    }
+        None => HashMap::new(),
+    };
+
+    // Resume from the job's checkpoint if it has one; a checkpoint is only a safety net,
+    // so failing to start one doesn't stop the job
+    let checkpoint = match checkpoint_path(&job_id) {
+        Ok(path) if path.exists() => {
+            let (checkpoint, header) = Checkpoint::open(&path).map_err(release)?;
+            if header.source_hash != source_hash {
+                return Err(release("The source file changed since this job was saved; create a new job to translate it again.".into()));
+            }
+            Some(checkpoint)
+        }
+        Ok(path) => {
+            let header = CheckpointHeader {
+                job_id: job_id.clone(),
+                file_id,
+                file_path: file_path.clone(),
+                file_name: file_name.clone(),
+                source_hash: source_hash.clone(),
+                options: opts.clone(),
+                created_at: now_secs(),
+            };
+            match Checkpoint::create(&path, &header) {
+                Ok(checkpoint) => Some(checkpoint),
+                Err(e) => {
+                    let _ = app.emit("translation://warning", e);
+                    None
+                }
+            }
+        }
+        Err(e) => {
+            let _ = app.emit("translation://warning", e);
+            None
+        }
+    };
+    
+    // Started by hand: it no longer waits in the queue
+    state.queue.lock().unwrap().remove(&job_id);
+
+    emit_status(&app, &job_id, &JobStatus::Running);
     
     // Start translation
+    let started_at = now_secs();
+    let usage = Arc::new(Mutex::new(TokenUsage::default()));
+    let request_slots = state.queue.lock().unwrap().request_slots();
     let result = translate_document(
         app.clone(),
         job_id.clone(),
         file_name.clone(),
         doc.clone(),
         opts,
+        JobHandles {
+            memory: state.memory.clone(),
+            control: control.clone(),
+            checkpoint: checkpoint.clone(),
+            request_slots,
+            usage: usage.clone(),
+        },
+        turns,
     )
     .await;
+
+    // A pause that came in while the last batch was in flight ends with the run
+    if !matches!(result, Err(TranslateError::Cancelled)) {
+        control.reset();
+    }
     
+    let cue_count = doc.cues.len();
+    let history_record = |status: JobStatus, translated_cues: usize, failed_cues: usize, output_path: Option<String>, error: Option<String>| {
+        let usage = *usage.lock().unwrap();
+        HistoryRecord {
+            job_id: job_id.clone(),
+            file_name: file_name.clone(),
+            file_path: file_path.clone(),
+            source_lang: job_options.source_lang.label().to_string(),
+            target_lang: job_options.target_lang.label().to_string(),
+            model: job_options.provider.model.clone(),
+            status,
+            started_at,
+            finished_at: now_secs(),
+            cue_count,
+            translated_cues,
+            failed_cues,
+            cost_usd: usage.cost_usd(&job_options.provider.model),
+            usage,
+            output_path,
+            error,
+        }
+    };
+
     match result {
-        Ok(translated) => {
+        Ok(output) => {
+            let DocumentTranslation { translated, memory_matches, failed } = output;
+
+            // ASS/VTT are written from the source events, so their cues can't be merged/split;
+            // nor can a partially done job's, whose failed cues must keep their ids for a retry
+            let (doc, mut translated, cue_origins) = if fit && matches!(source, SubtitleSource::Srt) && failed.is_empty() {
+                let r = fit_reading_speed(&doc, &translated, &qa_profile);
+                (r.document, r.translated, Some(r.origins))
+            } else {
+                if fit && !matches!(source, SubtitleSource::Srt) {
+                    let _ = app.emit(
+                        "translation://warning",
+                        "Cues can only be merged/split for .srt files; timing was left as is.".to_string(),
+                    );
+                } else if fit {
+                    let _ = app.emit(
+                        "translation://warning",
+                        "Some cues failed, so cues were not merged/split; retry the failed batches to fit reading speed.".to_string(),
+                    );
+                }
+                (doc, translated, None)
+            };
+
+            if let Some(wrap) = &wrap {
+                for text in translated.values_mut() {
+                    *text = wrap_text(text, wrap);
+                }
+            }
+
+            // A retried job keeps the cues edited by hand, wherever they are after merges/splits
+            let mut saved_edits = None;
+            if let Some(job) = state.jobs.lock().unwrap().get_mut(&job_id) {
+                if let (Some(edits), Some(edited)) = (job.edits.as_mut(), job.translated.as_ref()) {
+                    let moved = cue_mapping(job.cue_origins.as_deref(), cue_origins.as_deref());
+                    let dropped = edits.rebase(&mut translated, edited, moved);
+                    if dropped > 0 {
+                        let _ = app.emit(
+                            "translation://warning",
+                            format!("{} edited cues were merged or split differently this time; their edits were dropped.", dropped),
+                        );
+                    }
+                    saved_edits = Some(edits.saved());
+                }
+            }
+
             // Write output file
-            let output_path = file_path.replace(".srt", "_translated.srt");
-            let srt_content = write_srt(&doc, &translated)
-                .map_err(|e| format!("Failed to write SRT: {}", e))?;
-            
-            std::fs::write(&output_path, srt_content)
-                .map_err(|e| format!("Failed to save file: {}", e))?;
+            let output_path = translated_output_path(&file_path, output_format.extension());
+            // The checkpoint still holds every batch, so a restart after fixing the cause is cheap
+            let (encoding, unrepresentable) =
+                match write_job_output(&app, &output_path, &doc, &source, &translated, &job_options) {
+                    Ok(written) => written,
+                    Err(e) => {
+                        let record = history_record(JobStatus::Error, 0, 0, None, Some(e.clone()));
+                        return Err(fail_job(&app, &state, &job_id, record, e));
+                    }
+                };
+            let qa = check_translation(&doc, &translated, &qa_profile);
             
-            // Update job status
+            // Update job status; a partial job keeps its checkpoint for `retry_failed_batches`
+            let status = if failed.is_empty() { JobStatus::Done } else { JobStatus::PartiallyDone };
+            let failed_cues: usize = failed.iter().map(|r| r.last_cue - r.first_cue + 1).sum();
+            let record = history_record(status.clone(), translated.len(), failed_cues, Some(output_path.clone()), None);
+            let history_translations = HistoryTranslations {
+                options: job_options.clone(),
+                source_hash: source_hash.clone(),
+                translated: translated.clone(),
+                document: cue_origins.is_some().then(|| doc.clone()),
+                cue_origins: cue_origins.clone(),
+                edits: saved_edits,
+            };
+            add_history(&app, &state, record, Some(history_translations));
             let mut jobs = state.jobs.lock().unwrap();
             if let Some(job) = jobs.get_mut(&job_id) {
-                job.info.status = JobStatus::Done;
+                job.info.status = status.clone();
                 job.info.progress = 100.0;
+                job.info.error = None;
+                job.info.failed = failed.clone();
                 job.info.output_path = Some(output_path.clone());
                 job.translated = Some(translated);
+                job.document = cue_origins.is_some().then(|| doc.clone());
+                job.cue_origins = cue_origins.clone();
             }
+            drop(jobs);
+            if failed.is_empty() {
+                if let Some(checkpoint) = &checkpoint {
+                    let _ = checkpoint.remove();
+                }
+            } else {
+                let _ = app.emit(
+                    "translation://warning",
+                    format!("{} cues couldn't be translated and were left as {}; retry the failed batches to fill them in.",
+                        failed_cues,
+                        match &untranslated_text {
+                            UntranslatedText::Source => "the original text",
+                            UntranslatedText::Marker(_) => "a marker",
+                        }),
+                );
+            }
+            emit_status(&app, &job_id, &status);
             
             // Emit finished event
             let _ = app.emit("translation://finished", serde_json::json!({
                 "job_id": job_id,
                 "output_path": output_path,
+                "encoding": encoding,
+                "unrepresentable_cues": unrepresentable,
+                "qa": qa,
+                "cue_origins": cue_origins,
+                "memory_matches": memory_matches,
+                "failed": failed,
             }));
             
             Ok(())
         }
-        Err(e) => {
-            // Update job status to error
+        Err(TranslateError::Cancelled) => {
+            // Batches finished before the cancel stay in the translation memory
             let mut jobs = state.jobs.lock().unwrap();
             if let Some(job) = jobs.get_mut(&job_id) {
-                job.info.status = JobStatus::Error;
-                job.info.error = Some(e.to_string());
+                job.info.status = JobStatus::Cancelled;
             }
-            
-            // Emit error event
-            let _ = app.emit("translation://error", e.to_string());
-            
-            Err(e.to_string())
+            drop(jobs);
+            if let Some(checkpoint) = &checkpoint {
+                let _ = checkpoint.remove();
+            }
+            add_history(&app, &state, history_record(JobStatus::Cancelled, 0, 0, None, None), None);
+            emit_status(&app, &job_id, &JobStatus::Cancelled);
+            Ok(())
         }
+        Err(e) => {
+            let record = history_record(JobStatus::Error, 0, 0, None, Some(e.to_string()));
+            Err(fail_job(&app, &state, &job_id, record, e.to_string()))
+        }
+    }
+}
+
+/// Mark a job as failed (status, history, events); returns `error` for the command result.
+fn fail_job(app: &AppHandle, state: &AppState, job_id: &str, record: HistoryRecord, error: String) -> String {
+    let mut jobs = state.jobs.lock().unwrap();
+    if let Some(job) = jobs.get_mut(job_id) {
+        job.info.status = JobStatus::Error;
+        job.info.error = Some(error.clone());
     }
+    drop(jobs);
+    add_history(app, state, record, None);
+    emit_status(app, job_id, &JobStatus::Error);
+
+    // Emit error event
+    let _ = app.emit("translation://error", error.clone());
+    error
+}
+
+/// Keep a finished job in the history; failing to is only worth a warning.
+fn add_history(app: &AppHandle, state: &AppState, record: HistoryRecord, translations: Option<HistoryTranslations>) {
+    if let Err(e) = state.history.lock().unwrap().add(record, translations) {
+        let _ = app.emit("translation://warning", format!("The job couldn't be added to the history: {}", e));
+    }
+}
+
+/// `job://status` event, sent on every status change.
+fn emit_status(app: &AppHandle, job_id: &str, status: &JobStatus) {
+    let _ = app.emit("job://status", serde_json::json!({
+        "job_id": job_id,
+        "status": status,
+    }));
+}
+
+/// Translate again only the cues a partially done job couldn't; the output file is rewritten.
+/// Cues edited by hand keep their edits when the new run builds them from the same source cues;
+/// edits on cues merged/split differently are dropped, with a `translation://warning`.
+#[tauri::command]
+pub async fn retry_failed_batches(
+    job_id: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    {
+        let jobs = state.jobs.lock().unwrap();
+        let job = jobs
+            .get(&job_id)
+            .ok_or_else(|| format!("Job not found: {}", job_id))?;
+        if !matches!(job.info.status, JobStatus::PartiallyDone) {
+            return Err("Only partially done jobs have failed batches to retry.".into());
+        }
+    }
+    // The checkpoint holds every finished item, so only the failed ones are sent again
+    start_job(job_id, app, state).await
+}
+
+/// Stop a job: a queued one never starts, a running one aborts its in-flight requests.
+#[tauri::command]
+pub fn cancel_job(
+    job_id: String,
+    app: AppHandle,
+    state: State<AppState>,
+) -> Result<(), String> {
+    let mut jobs = state.jobs.lock().unwrap();
+    let job = jobs
+        .get_mut(&job_id)
+        .ok_or_else(|| format!("Job not found: {}", job_id))?;
+    match job.info.status {
+        JobStatus::Queued => {
+            job.info.status = JobStatus::Cancelled;
+            drop(jobs);
+            state.queue.lock().unwrap().remove(&job_id);
+            emit_status(&app, &job_id, &JobStatus::Cancelled);
+        }
+        // start_job sets Cancelled once the running batches have stopped
+        JobStatus::Running | JobStatus::Paused => {
+            job.control.cancel();
+        }
+        _ => return Err("Only queued or running jobs can be cancelled.".into()),
+    }
+    Ok(())
+}
+
+/// Start no new batches until resumed; running ones finish.
+#[tauri::command]
+pub fn pause_job(
+    job_id: String,
+    app: AppHandle,
+    state: State<AppState>,
+) -> Result<(), String> {
+    let mut jobs = state.jobs.lock().unwrap();
+    let job = jobs
+        .get_mut(&job_id)
+        .ok_or_else(|| format!("Job not found: {}", job_id))?;
+    if !matches!(job.info.status, JobStatus::Running) || !job.control.pause() {
+        return Err("Only running jobs can be paused.".into());
+    }
+    job.info.status = JobStatus::Paused;
+    drop(jobs);
+    emit_status(&app, &job_id, &JobStatus::Paused);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn resume_job(
+    job_id: String,
+    app: AppHandle,
+    state: State<AppState>,
+) -> Result<(), String> {
+    let mut jobs = state.jobs.lock().unwrap();
+    let job = jobs
+        .get_mut(&job_id)
+        .ok_or_else(|| format!("Job not found: {}", job_id))?;
+    if !matches!(job.info.status, JobStatus::Paused) || !job.control.resume() {
+        return Err("Only paused jobs can be resumed.".into());
+    }
+    job.info.status = JobStatus::Running;
+    drop(jobs);
+    emit_status(&app, &job_id, &JobStatus::Running);
+    Ok(())
 }
 
 #[tauri::command]
@@ -155,3 +574,31 @@ pub fn list_jobs(state: State<AppState>) -> Result<Vec<JobInfo>, String> {
     let jobs = state.jobs.lock().unwrap();
     Ok(jobs.values().map(|j| j.info.clone()).collect())
 }
+
+/// Re-run readability QA on a finished job, e.g. with a different profile.
+#[tauri::command]
+pub fn get_qa_report(
+    job_id: String,
+    profile: Option<QaProfile>,
+    state: State<AppState>,
+) -> Result<QaReport, String> {
+    let files = state.files.lock().unwrap();
+    let jobs = state.jobs.lock().unwrap();
+
+    let job = jobs
+        .get(&job_id)
+        .ok_or_else(|| format!("Job not found: {}", job_id))?;
+    let translated = job
+        .translated
+        .as_ref()
+        .ok_or_else(|| "This job has no translation yet.".to_string())?;
+    let file_data = files
+        .get(&job.info.file_id)
+        .ok_or_else(|| format!("File not found: {}", job.info.file_id))?;
+
+    let profile = profile
+        .or_else(|| job.options.as_ref().and_then(|o| o.qa_profile.clone()))
+        .unwrap_or_default();
+    let document = job.document.as_ref().unwrap_or(&file_data.document);
+    Ok(check_translation(document, translated, &profile))
+}
@@ -0,0 +1,119 @@
+use std::path::Path;
+use tauri::State;
+
+use crate::state::AppState;
+use crate::translate::exchange::{self, ExchangeFormat, ExportMeta, ExportRow, MemoryImportOptions};
+use crate::translate::memory::{MemoryEntry, MemoryStats, PruneFilter};
+
+fn exchange_format(path: &Path) -> Result<ExchangeFormat, String> {
+    ExchangeFormat::from_path(path)
+        .ok_or_else(|| format!("Unsupported file type: {} (use .tmx, .csv or .tsv)", path.display()))
+}
+
+#[tauri::command]
+pub fn get_memory_stats(state: State<AppState>) -> Result<MemoryStats, String> {
+    Ok(state.memory.lock().unwrap().stats())
+}
+
+/// Newest entries first, optionally only those whose source or translation contains `query`.
+#[tauri::command]
+pub fn search_memory(
+    query: Option<String>,
+    limit: Option<usize>,
+    state: State<AppState>,
+) -> Result<Vec<MemoryEntry>, String> {
+    let memory = state.memory.lock().unwrap();
+    Ok(memory.search(query.as_deref(), limit.unwrap_or(200)))
+}
+
+/// Remove matching entries; returns how many were removed.
+#[tauri::command]
+pub fn prune_memory(filter: PruneFilter, state: State<AppState>) -> Result<usize, String> {
+    state.memory.lock().unwrap().prune(&filter)
+}
+
+#[tauri::command]
+pub fn clear_memory(state: State<AppState>) -> Result<(), String> {
+    state.memory.lock().unwrap().clear()
+}
+
+/// Import approved pairs from a TMX, CSV or TSV file; returns how many were added.
+#[tauri::command]
+pub fn import_memory_file(
+    path: String,
+    options: MemoryImportOptions,
+    state: State<AppState>,
+) -> Result<usize, String> {
+    let path = Path::new(&path);
+    let format = exchange_format(path)?;
+    let bytes = std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let content = exchange::decode_exchange_file(&bytes)?;
+
+    let pairs = match format {
+        ExchangeFormat::Tmx => exchange::parse_tmx(&content, &options.source_lang, &options.target_lang)?,
+        ExchangeFormat::Csv => exchange::parse_delimited(&content, ',')?,
+        ExchangeFormat::Tsv => exchange::parse_delimited(&content, '\t')?,
+    };
+    let count = pairs.len();
+    state
+        .memory
+        .lock()
+        .unwrap()
+        .insert_many(pairs.into_iter().map(|p| (options.key(&p.source), p.target)))?;
+    Ok(count)
+}
+
+/// Export a finished job's source/translation pairs as TMX, CSV or TSV (by extension).
+#[tauri::command]
+pub fn export_job_translations(
+    job_id: String,
+    path: String,
+    state: State<AppState>,
+) -> Result<usize, String> {
+    let files = state.files.lock().unwrap();
+    let jobs = state.jobs.lock().unwrap();
+
+    let job = jobs
+        .get(&job_id)
+        .ok_or_else(|| format!("Job not found: {}", job_id))?;
+    let translated = job
+        .translated
+        .as_ref()
+        .ok_or_else(|| "This job has no translation yet.".to_string())?;
+    let file_data = files
+        .get(&job.info.file_id)
+        .ok_or_else(|| format!("File not found: {}", job.info.file_id))?;
+    let options = job
+        .options
+        .as_ref()
+        .ok_or_else(|| "Job options not found".to_string())?;
+
+    let document = job.document.as_ref().unwrap_or(&file_data.document);
+    let rows: Vec<ExportRow> = document
+        .cues
+        .iter()
+        .filter_map(|cue| {
+            translated.get(&cue.id).map(|target| ExportRow {
+                cue: cue.index_line.clone(),
+                source: cue.text_lines.join("\n"),
+                target: target.clone(),
+            })
+        })
+        .collect();
+    let meta = ExportMeta {
+        job_id: job_id.clone(),
+        file_name: file_data.item.name.clone(),
+        source_lang: options.source_lang.clone(),
+        target_lang: options.target_lang.clone(),
+        model: options.provider.model.clone(),
+    };
+
+    let path = Path::new(&path);
+    let content = match exchange_format(path)? {
+        ExchangeFormat::Tmx => exchange::write_tmx(&rows, &meta),
+        ExchangeFormat::Csv => exchange::write_delimited(&rows, &meta, ','),
+        ExchangeFormat::Tsv => exchange::write_delimited(&rows, &meta, '\t'),
+    };
+    std::fs::write(path, content).map_err(|e| format!("Failed to save file: {}", e))?;
+    Ok(rows.len())
+}
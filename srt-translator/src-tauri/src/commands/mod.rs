@@ -1,5 +1,12 @@
 pub mod files;
 pub mod jobs;
+pub mod queue;
+pub mod history;
+pub mod editor;
+pub mod timing;
+pub mod memory;
+pub mod glossary;
+pub mod speakers;
 pub mod proxypal;
 pub mod proxy_config;
 pub mod browser;
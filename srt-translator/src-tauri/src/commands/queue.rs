@@ -0,0 +1,187 @@
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::commands::jobs::{create_job, start_job};
+use crate::queue::{QueueLimits, QueueSnapshot};
+use crate::state::{AppState, JobInfo, JobStatus};
+use crate::translate::worker::TranslationOptions;
+
+/// `queue://changed` event with the new order.
+fn emit_queue(app: &AppHandle, snapshot: QueueSnapshot) {
+    let _ = app.emit("queue://changed", snapshot);
+}
+
+/// Start waiting jobs while fewer than `max_running_jobs` queued jobs are running.
+fn pump_queue(app: &AppHandle) {
+    let state = app.state::<AppState>();
+    let (started, snapshot) = {
+        let mut queue = state.queue.lock().unwrap();
+        (queue.take_startable(), queue.snapshot())
+    };
+    if started.is_empty() {
+        return;
+    }
+    emit_queue(app, snapshot);
+
+    for job_id in started {
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            let state = app.state::<AppState>();
+            // Translation errors are reported by start_job; this catches jobs that couldn't start
+            if let Err(e) = start_job(job_id.clone(), app.clone(), state.clone()).await {
+                let _ = app.emit("translation://warning", format!("Queued job didn't run: {}", e));
+            }
+            state.queue.lock().unwrap().finish(&job_id);
+            pump_queue(&app);
+        });
+    }
+}
+
+fn enqueue(app: &AppHandle, state: &State<'_, AppState>, job_id: &str, priority: i32) -> Result<(), String> {
+    {
+        let jobs = state.jobs.lock().unwrap();
+        let job = jobs
+            .get(job_id)
+            .ok_or_else(|| format!("Job not found: {}", job_id))?;
+        if !matches!(job.info.status, JobStatus::Queued | JobStatus::Error | JobStatus::PartiallyDone) {
+            return Err("Only new, failed or partially done jobs can be queued.".into());
+        }
+    }
+    let snapshot = {
+        let mut queue = state.queue.lock().unwrap();
+        if !queue.push(job_id, priority) {
+            return Err("This job is already queued.".into());
+        }
+        queue.snapshot()
+    };
+    emit_queue(app, snapshot);
+    Ok(())
+}
+
+/// Queue a job to start when a slot is free; higher `priority` runs first.
+#[tauri::command]
+pub fn enqueue_job(
+    job_id: String,
+    priority: Option<i32>,
+    app: AppHandle,
+    state: State<AppState>,
+) -> Result<(), String> {
+    enqueue(&app, &state, &job_id, priority.unwrap_or(0))?;
+    pump_queue(&app);
+    Ok(())
+}
+
+/// Create a job with the same options for every imported file (by file name) and queue them all.
+/// Files that already have a job waiting or running are skipped.
+#[tauri::command]
+pub fn translate_all_files(
+    options: TranslationOptions,
+    priority: Option<i32>,
+    app: AppHandle,
+    state: State<AppState>,
+) -> Result<Vec<JobInfo>, String> {
+    let mut files: Vec<(String, String)> = {
+        let files = state.files.lock().unwrap();
+        let jobs = state.jobs.lock().unwrap();
+        files
+            .values()
+            .filter(|f| {
+                !jobs.values().any(|j| {
+                    j.info.file_id == f.item.id
+                        && matches!(j.info.status, JobStatus::Queued | JobStatus::Running | JobStatus::Paused)
+                })
+            })
+            .map(|f| (f.item.name.clone(), f.item.id.clone()))
+            .collect()
+    };
+    files.sort();
+
+    let mut created = Vec::with_capacity(files.len());
+    for (_, file_id) in files {
+        let info = create_job(file_id, options.clone(), state.clone())?;
+        enqueue(&app, &state, &info.id, priority.unwrap_or(0))?;
+        created.push(info);
+    }
+    pump_queue(&app);
+    Ok(created)
+}
+
+#[tauri::command]
+pub fn get_queue(state: State<AppState>) -> Result<QueueSnapshot, String> {
+    Ok(state.queue.lock().unwrap().snapshot())
+}
+
+/// Change a waiting job's priority; it goes to the back of its new priority.
+#[tauri::command]
+pub fn set_job_priority(
+    job_id: String,
+    priority: i32,
+    app: AppHandle,
+    state: State<AppState>,
+) -> Result<(), String> {
+    let snapshot = {
+        let mut queue = state.queue.lock().unwrap();
+        if !queue.set_priority(&job_id, priority) {
+            return Err("This job isn't waiting in the queue.".into());
+        }
+        queue.snapshot()
+    };
+    emit_queue(&app, snapshot);
+    Ok(())
+}
+
+/// Move a waiting job to `position` in the queue (0 = next to start).
+#[tauri::command]
+pub fn move_queued_job(
+    job_id: String,
+    position: usize,
+    app: AppHandle,
+    state: State<AppState>,
+) -> Result<(), String> {
+    let snapshot = {
+        let mut queue = state.queue.lock().unwrap();
+        if !queue.move_to(&job_id, position) {
+            return Err("This job isn't waiting in the queue.".into());
+        }
+        queue.snapshot()
+    };
+    emit_queue(&app, snapshot);
+    Ok(())
+}
+
+/// Take a waiting job out of the queue; the job itself stays and can be started or queued again.
+#[tauri::command]
+pub fn dequeue_job(
+    job_id: String,
+    app: AppHandle,
+    state: State<AppState>,
+) -> Result<(), String> {
+    let snapshot = {
+        let mut queue = state.queue.lock().unwrap();
+        if !queue.remove(&job_id) {
+            return Err("This job isn't waiting in the queue.".into());
+        }
+        queue.snapshot()
+    };
+    emit_queue(&app, snapshot);
+    Ok(())
+}
+
+/// The request limit applies at once, running jobs included; the job limit when jobs next start.
+#[tauri::command]
+pub fn set_queue_limits(
+    limits: QueueLimits,
+    app: AppHandle,
+    state: State<AppState>,
+) -> Result<(), String> {
+    if limits.max_running_jobs == 0 || limits.max_concurrent_requests == 0 {
+        return Err("Limits must be at least 1.".into());
+    }
+    let snapshot = {
+        let mut queue = state.queue.lock().unwrap();
+        queue.set_limits(limits);
+        queue.snapshot()
+    };
+    emit_queue(&app, snapshot);
+    pump_queue(&app);
+    Ok(())
+}
@@ -0,0 +1,17 @@
+use crate::translate::speakers::{self, SpeakerTable};
+
+#[tauri::command]
+pub fn list_speaker_tables() -> Result<Vec<SpeakerTable>, String> {
+    speakers::list_speaker_tables()
+}
+
+/// Create or replace the speaker table with this name.
+#[tauri::command]
+pub fn save_speaker_table(table: SpeakerTable) -> Result<(), String> {
+    speakers::save_speaker_table(&table)
+}
+
+#[tauri::command]
+pub fn delete_speaker_table(name: String) -> Result<(), String> {
+    speakers::delete_speaker_table(&name)
+}
@@ -0,0 +1,121 @@
+use tauri::State;
+
+use crate::state::{AppState, JobStatus, TranslationJob};
+use crate::srt::{
+    timeline::{self, SyncPoint},
+    timing::{self, TimingConfig, TimingReport},
+    write_subtitle, SrtDocument, SubtitleSource,
+};
+
+/// Retime an imported file: apply `f` to its cues, carry the new times into its container
+/// (ASS/VTT output is written from it) and into the own cues of its jobs that merged/split cues,
+/// so `save_subtitle_file` and `export_edited_job` write the new timing.
+fn retime(
+    state: &AppState,
+    file_id: &str,
+    f: impl Fn(&mut SrtDocument) -> Result<(), String>,
+) -> Result<(), String> {
+    let mut files = state.files.lock().unwrap();
+    let file_data = files
+        .get_mut(file_id)
+        .ok_or_else(|| format!("File not found: {}", file_id))?;
+    let mut jobs = state.jobs.lock().unwrap();
+    let mut jobs: Vec<&mut TranslationJob> = jobs.values_mut().filter(|job| job.info.file_id == file_id).collect();
+    if jobs.iter().any(|job| matches!(job.info.status, JobStatus::Running | JobStatus::Paused)) {
+        return Err("A job is translating this file; retime it once the job has finished.".into());
+    }
+
+    f(&mut file_data.document)?;
+    file_data.source.set_times(&file_data.document);
+    for job in jobs.iter_mut() {
+        if let Some(doc) = job.document.as_mut() {
+            f(doc)?;
+        }
+    }
+    Ok(())
+}
+
+/// Check (and unless `dry_run`, fix) cue timing of an imported file.
+/// Fixing may reorder and renumber cues, so it is refused once the file has jobs:
+/// their translations and checkpoints are keyed by the current cue ids.
+/// ASS/VTT cues are never reordered, since their container keeps its own order.
+#[tauri::command]
+pub fn fix_timing(
+    file_id: String,
+    config: Option<TimingConfig>,
+    dry_run: bool,
+    state: State<AppState>,
+) -> Result<TimingReport, String> {
+    let mut config = config.unwrap_or_default();
+    let mut files = state.files.lock().unwrap();
+    let file_data = files
+        .get_mut(&file_id)
+        .ok_or_else(|| format!("File not found: {}", file_id))?;
+    if dry_run {
+        return Ok(timing::fix_timing(&mut file_data.document, &config, true));
+    }
+
+    if state.jobs.lock().unwrap().values().any(|job| job.info.file_id == file_id) {
+        return Err("This file already has translation jobs; fix its timing before creating them.".into());
+    }
+    if !matches!(file_data.source, SubtitleSource::Srt) {
+        config.sort_by_start = false;
+    }
+    let report = timing::fix_timing(&mut file_data.document, &config, false);
+    file_data.source.set_times(&file_data.document);
+    if report.reordered {
+        file_data.rehash()?;
+    }
+    Ok(report)
+}
+
+/// Move every cue by `offset_ms` (negative = earlier). Like the other timeline commands,
+/// it also moves the cues of the file's finished jobs; see `retime`.
+#[tauri::command]
+pub fn shift_timeline(
+    file_id: String,
+    offset_ms: i64,
+    state: State<AppState>,
+) -> Result<(), String> {
+    retime(&state, &file_id, |doc| {
+        timeline::shift(doc, offset_ms);
+        Ok(())
+    })
+}
+
+/// Stretch the timeline so `first.from` lands on `first.to` and `second.from` on `second.to`.
+#[tauri::command]
+pub fn stretch_timeline(
+    file_id: String,
+    first: SyncPoint,
+    second: SyncPoint,
+    state: State<AppState>,
+) -> Result<(), String> {
+    retime(&state, &file_id, |doc| timeline::stretch(doc, &first, &second))
+}
+
+/// Retime for a different framerate (e.g. 23.976 -> 25).
+#[tauri::command]
+pub fn convert_framerate(
+    file_id: String,
+    from_fps: f64,
+    to_fps: f64,
+    state: State<AppState>,
+) -> Result<(), String> {
+    retime(&state, &file_id, |doc| timeline::convert_framerate(doc, from_fps, to_fps))
+}
+
+/// Save an imported file as it now is (e.g. retimed), in its own format, to `path`.
+#[tauri::command]
+pub fn save_subtitle_file(
+    file_id: String,
+    path: String,
+    state: State<AppState>,
+) -> Result<(), String> {
+    let files = state.files.lock().unwrap();
+    let file_data = files
+        .get(&file_id)
+        .ok_or_else(|| format!("File not found: {}", file_id))?;
+    let content = write_subtitle(&file_data.document, &file_data.source, &file_data.source_texts(), file_data.source.format())?;
+    std::fs::write(&path, content).map_err(|e| format!("Failed to save file: {}", e))
+}
@@ -0,0 +1,300 @@
+//! Cue-level edits of a finished job's translation.
+//!
+//! Goals:
+//! - Edit or revert one cue at a time; the job's translation map is always the edited version,
+//!   so QA, export and re-writing the subtitle file need nothing special
+//! - Keep the machine output next to it, so any cue can go back to it
+//! - Undo/redo through an operation log of before/after states
+//! - Know per cue whether its text came from the model or from a person
+//! - Survive a reload: `SavedEdits` goes into the job history with the edited translation
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum Provenance {
+    #[default]
+    Machine,
+    Human,
+}
+
+/// A cue's translation at one point in time (None = untranslated).
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CueState {
+    text: Option<String>,
+    origin: Provenance,
+}
+
+/// One operation: `cue_id` went from `before` to `after`.
+#[derive(Debug, Clone)]
+struct CueEdit {
+    cue_id: usize,
+    before: CueState,
+    after: CueState,
+}
+
+/// What an `EditLog` keeps across a reload (the undo history doesn't).
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SavedEdits {
+    pub human: HashSet<usize>,
+    /// What the model wrote for the human cues; every other cue's is the translation itself.
+    pub machine: HashMap<usize, String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct EditLog {
+    /// What the model produced, by cue id.
+    machine: HashMap<usize, String>,
+    /// Cues a person has written; every other cue is machine output.
+    human: HashSet<usize>,
+    undo: Vec<CueEdit>,
+    redo: Vec<CueEdit>,
+}
+
+impl EditLog {
+    /// Start editing `translated`, as the model left it.
+    pub fn new(translated: &HashMap<usize, String>) -> Self {
+        Self { machine: translated.clone(), ..Default::default() }
+    }
+
+    /// Pick up editing an edited `translated` again, from what `saved` kept.
+    pub fn restore(translated: &HashMap<usize, String>, mut saved: SavedEdits) -> Self {
+        let mut machine = translated.clone();
+        for cue_id in &saved.human {
+            match saved.machine.remove(cue_id) {
+                Some(text) => machine.insert(*cue_id, text),
+                None => machine.remove(cue_id),
+            };
+        }
+        Self { machine, human: saved.human, ..Default::default() }
+    }
+
+    pub fn saved(&self) -> SavedEdits {
+        let machine = self
+            .human
+            .iter()
+            .filter_map(|id| self.machine.get(id).map(|text| (*id, text.clone())))
+            .collect();
+        SavedEdits { human: self.human.clone(), machine }
+    }
+
+    pub fn provenance(&self, cue_id: usize) -> Provenance {
+        if self.human.contains(&cue_id) {
+            Provenance::Human
+        } else {
+            Provenance::Machine
+        }
+    }
+
+    pub fn machine_text(&self, cue_id: usize) -> Option<&str> {
+        self.machine.get(&cue_id).map(String::as_str)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    fn state(&self, translated: &HashMap<usize, String>, cue_id: usize) -> CueState {
+        CueState { text: translated.get(&cue_id).cloned(), origin: self.provenance(cue_id) }
+    }
+
+    fn apply(&mut self, translated: &mut HashMap<usize, String>, cue_id: usize, state: &CueState) {
+        match &state.text {
+            Some(text) => translated.insert(cue_id, text.clone()),
+            None => translated.remove(&cue_id),
+        };
+        match state.origin {
+            Provenance::Human => self.human.insert(cue_id),
+            Provenance::Machine => self.human.remove(&cue_id),
+        };
+    }
+
+    /// Log and apply a change; false if it changes nothing.
+    fn change(&mut self, translated: &mut HashMap<usize, String>, cue_id: usize, after: CueState) -> bool {
+        let before = self.state(translated, cue_id);
+        if before == after {
+            return false;
+        }
+        self.apply(translated, cue_id, &after);
+        self.undo.push(CueEdit { cue_id, before, after });
+        self.redo.clear();
+        true
+    }
+
+    /// Replace a cue's translation with a person's text.
+    pub fn edit(&mut self, translated: &mut HashMap<usize, String>, cue_id: usize, text: String) -> bool {
+        self.change(translated, cue_id, CueState { text: Some(text), origin: Provenance::Human })
+    }
+
+    /// Put back what the model wrote.
+    pub fn revert(&mut self, translated: &mut HashMap<usize, String>, cue_id: usize) -> bool {
+        let machine = self.machine.get(&cue_id).cloned();
+        self.change(translated, cue_id, CueState { text: machine, origin: Provenance::Machine })
+    }
+
+    /// Undo the last operation; the cue it touched.
+    pub fn undo(&mut self, translated: &mut HashMap<usize, String>) -> Option<usize> {
+        let edit = self.undo.pop()?;
+        self.apply(translated, edit.cue_id, &edit.before);
+        let cue_id = edit.cue_id;
+        self.redo.push(edit);
+        Some(cue_id)
+    }
+
+    pub fn redo(&mut self, translated: &mut HashMap<usize, String>) -> Option<usize> {
+        let edit = self.redo.pop()?;
+        self.apply(translated, edit.cue_id, &edit.after);
+        let cue_id = edit.cue_id;
+        self.undo.push(edit);
+        Some(cue_id)
+    }
+
+    /// The job was translated again (e.g. failed batches retried): `translated` is the new
+    /// machine output, and `moved` gives each old cue id's new one (see `cue_mapping`).
+    /// Human cues keep their text; the operation log starts over.
+    /// Returns how many human cues had no counterpart and were dropped.
+    pub fn rebase(
+        &mut self,
+        translated: &mut HashMap<usize, String>,
+        edited: &HashMap<usize, String>,
+        moved: impl Fn(usize) -> Option<usize>,
+    ) -> usize {
+        self.machine = translated.clone();
+        let mut dropped = 0;
+        for cue_id in std::mem::take(&mut self.human) {
+            let Some(new_id) = moved(cue_id) else {
+                dropped += 1;
+                continue;
+            };
+            match edited.get(&cue_id) {
+                Some(text) => translated.insert(new_id, text.clone()),
+                None => translated.remove(&new_id),
+            };
+            self.human.insert(new_id);
+        }
+        self.undo.clear();
+        self.redo.clear();
+        dropped
+    }
+}
+
+/// Old cue id -> new cue id between two runs of a job, for cues built from the same file cues.
+/// `from`/`to` are the runs' `cue_origins` (None = the file's own cues).
+pub fn cue_mapping<'a>(from: Option<&'a [Vec<usize>]>, to: Option<&[Vec<usize>]>) -> impl Fn(usize) -> Option<usize> + 'a {
+    let index: Option<HashMap<Vec<usize>, usize>> =
+        to.map(|to| to.iter().enumerate().map(|(id, origins)| (origins.clone(), id)).collect());
+    move |cue_id| {
+        let origins = match from {
+            Some(from) => from.get(cue_id)?.clone(),
+            None => vec![cue_id],
+        };
+        match &index {
+            Some(index) => index.get(&origins).copied(),
+            None => (origins.len() == 1).then(|| origins[0]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn machine() -> HashMap<usize, String> {
+        HashMap::from([(0, "Xin chào".to_string()), (1, "Tạm biệt".to_string())])
+    }
+
+    #[test]
+    fn test_edit_revert_and_provenance() {
+        let mut translated = machine();
+        let mut log = EditLog::new(&translated);
+
+        assert!(log.edit(&mut translated, 1, "Hẹn gặp lại".into()));
+        assert!(!log.edit(&mut translated, 1, "Hẹn gặp lại".into()));
+        assert_eq!(translated[&1], "Hẹn gặp lại");
+        assert_eq!(log.provenance(1), Provenance::Human);
+        assert_eq!(log.provenance(0), Provenance::Machine);
+
+        assert!(log.revert(&mut translated, 1));
+        assert_eq!(translated[&1], "Tạm biệt");
+        assert_eq!(log.provenance(1), Provenance::Machine);
+        assert!(!log.revert(&mut translated, 1));
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut translated = machine();
+        let mut log = EditLog::new(&translated);
+        log.edit(&mut translated, 0, "Chào".into());
+        log.edit(&mut translated, 0, "Chào bạn".into());
+        // A cue the model left untranslated
+        log.edit(&mut translated, 2, "Ừ".into());
+
+        assert_eq!(log.undo(&mut translated), Some(2));
+        assert!(!translated.contains_key(&2));
+        assert_eq!(log.undo(&mut translated), Some(0));
+        assert_eq!(translated[&0], "Chào");
+        assert_eq!(log.undo(&mut translated), Some(0));
+        assert_eq!((translated[&0].as_str(), log.provenance(0)), ("Xin chào", Provenance::Machine));
+        assert_eq!(log.undo(&mut translated), None);
+
+        assert_eq!(log.redo(&mut translated), Some(0));
+        assert_eq!((translated[&0].as_str(), log.provenance(0)), ("Chào", Provenance::Human));
+        // A new edit drops what could be redone
+        log.edit(&mut translated, 1, "Bye".into());
+        assert!(!log.can_redo());
+    }
+
+    #[test]
+    fn test_rebase_keeps_human_cues() {
+        let mut translated = machine();
+        let mut log = EditLog::new(&translated);
+        log.edit(&mut translated, 1, "Hẹn gặp lại".into());
+        let edited = translated.clone();
+
+        let mut retried = HashMap::from([(0, "Chào".to_string()), (1, "Tạm biệt".to_string()), (2, "Ừ".to_string())]);
+        assert_eq!(log.rebase(&mut retried, &edited, Some), 0);
+        assert_eq!(retried[&0], "Chào");
+        assert_eq!(retried[&1], "Hẹn gặp lại");
+        assert_eq!(log.machine_text(1), Some("Tạm biệt"));
+        assert!(!log.can_undo());
+    }
+
+    #[test]
+    fn test_rebase_follows_merged_cues() {
+        let mut translated = HashMap::from([(0, "A".to_string()), (1, "B".to_string()), (2, "C".to_string())]);
+        let mut log = EditLog::new(&translated);
+        log.edit(&mut translated, 0, "a".into());
+        log.edit(&mut translated, 2, "c".into());
+        let edited = translated.clone();
+
+        // The retry merged file cues 0 and 1; cue 2 is now cue 1
+        let origins = vec![vec![0, 1], vec![2]];
+        let mut retried = HashMap::from([(0, "AB".to_string()), (1, "C".to_string())]);
+        assert_eq!(log.rebase(&mut retried, &edited, cue_mapping(None, Some(&origins))), 1);
+        assert_eq!(retried[&0], "AB");
+        assert_eq!(retried[&1], "c");
+        assert_eq!(log.provenance(1), Provenance::Human);
+        assert_eq!(log.provenance(0), Provenance::Machine);
+    }
+
+    #[test]
+    fn test_restore_from_saved() {
+        let mut translated = machine();
+        let mut log = EditLog::new(&translated);
+        log.edit(&mut translated, 1, "Hẹn gặp lại".into());
+        log.edit(&mut translated, 2, "Ừ".into());
+        let saved: SavedEdits = serde_json::from_str(&serde_json::to_string(&log.saved()).unwrap()).unwrap();
+
+        let mut restored = EditLog::restore(&translated, saved);
+        assert_eq!(restored.provenance(1), Provenance::Human);
+        assert_eq!(restored.provenance(0), Provenance::Machine);
+        assert!(!restored.can_undo());
+        assert!(restored.revert(&mut translated, 1));
+        assert!(restored.revert(&mut translated, 2));
+        assert_eq!(translated, machine());
+    }
+}
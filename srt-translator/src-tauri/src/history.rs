@@ -0,0 +1,356 @@
+//! Job history: every finished, failed or cancelled job, kept across restarts.
+//!
+//! Goals:
+//! - One JSONL line per job in the config dir; translations in a side file per job,
+//!   read only when an old job is reopened
+//! - Filter by date range, languages, model, status and file name
+//! - Delete entries with their translations; export the list as JSON or CSV
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::editor::SavedEdits;
+use crate::proxy_config::AppConfig;
+use crate::srt::SrtDocument;
+use crate::state::JobStatus;
+use crate::translate::exchange::delimited_field;
+use crate::translate::tokens::TokenUsage;
+use crate::translate::worker::TranslationOptions;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryRecord {
+    pub job_id: String,
+    pub file_name: String,
+    pub file_path: String,
+    pub source_lang: String,
+    pub target_lang: String,
+    pub model: String,
+    pub status: JobStatus,
+    pub started_at: u64,
+    pub finished_at: u64,
+    pub cue_count: usize,
+    pub translated_cues: usize,
+    #[serde(default)]
+    pub failed_cues: usize,
+    #[serde(default)]
+    pub usage: TokenUsage,
+    #[serde(default)]
+    pub cost_usd: Option<f64>,
+    pub output_path: Option<String>,
+    pub error: Option<String>,
+}
+
+impl HistoryRecord {
+    pub fn duration_secs(&self) -> u64 {
+        self.finished_at.saturating_sub(self.started_at)
+    }
+}
+
+/// What's needed to reopen a job: its options and output, against the cues it translated.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryTranslations {
+    pub options: TranslationOptions,
+    /// `source_hash` of the file the job translated; cue ids only line up with that version.
+    #[serde(default)]
+    pub source_hash: String,
+    pub translated: HashMap<usize, String>,
+    /// Set when the job merged/split cues, as in `TranslationJob`.
+    #[serde(default)]
+    pub document: Option<SrtDocument>,
+    #[serde(default)]
+    pub cue_origins: Option<Vec<Vec<usize>>>,
+    /// Set once the translation was edited; `translated` then holds the edited text.
+    #[serde(default)]
+    pub edits: Option<SavedEdits>,
+}
+
+/// All fields optional; language and model match case-insensitively, `file_name` as a substring.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HistoryFilter {
+    /// Unix seconds, inclusive, on `finished_at`.
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+    pub source_lang: Option<String>,
+    pub target_lang: Option<String>,
+    pub model: Option<String>,
+    pub status: Option<JobStatus>,
+    pub file_name: Option<String>,
+}
+
+impl HistoryFilter {
+    fn matches(&self, record: &HistoryRecord) -> bool {
+        let same = |want: &Option<String>, have: &str| want.as_ref().is_none_or(|w| w.eq_ignore_ascii_case(have));
+        self.from.is_none_or(|from| record.finished_at >= from)
+            && self.to.is_none_or(|to| record.finished_at <= to)
+            && same(&self.source_lang, &record.source_lang)
+            && same(&self.target_lang, &record.target_lang)
+            && same(&self.model, &record.model)
+            && self.status.as_ref().is_none_or(|s| *s == record.status)
+            && self
+                .file_name
+                .as_ref()
+                .is_none_or(|name| record.file_name.to_lowercase().contains(&name.to_lowercase()))
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct JobHistory {
+    /// None keeps the history in RAM only (e.g. when the config dir is unavailable).
+    dir: Option<PathBuf>,
+    records: Vec<HistoryRecord>,
+    /// Translations of jobs recorded without a dir.
+    translations: HashMap<String, HistoryTranslations>,
+}
+
+impl JobHistory {
+    /// Load (or start) the history in `dir`. Unreadable lines are skipped.
+    pub fn open(dir: &Path) -> Result<Self, String> {
+        let path = dir.join("history.jsonl");
+        let mut records = Vec::new();
+        if path.exists() {
+            let content = fs::read_to_string(&path).map_err(|e| format!("Failed to read job history: {}", e))?;
+            records.extend(content.lines().filter_map(|line| serde_json::from_str::<HistoryRecord>(line).ok()));
+        }
+        Ok(Self { dir: Some(dir.to_path_buf()), records, translations: HashMap::new() })
+    }
+
+    /// The history in the app's config dir.
+    pub fn open_default() -> Result<Self, String> {
+        Self::open(&AppConfig::get_config_dir()?.join("history"))
+    }
+
+    fn translations_path(dir: &Path, job_id: &str) -> PathBuf {
+        dir.join(format!("{}.json", job_id))
+    }
+
+    fn write_translations(dir: &Path, job_id: &str, translations: &HistoryTranslations) -> Result<(), String> {
+        let json = serde_json::to_string(translations).map_err(|e| e.to_string())?;
+        fs::write(Self::translations_path(dir, job_id), json).map_err(|e| format!("Failed to write job translations: {}", e))
+    }
+
+    /// Record a job (replacing an earlier record of the same job, e.g. after a retry).
+    pub fn add(&mut self, record: HistoryRecord, translations: Option<HistoryTranslations>) -> Result<(), String> {
+        let replaced = self.records.iter().any(|r| r.job_id == record.job_id);
+        self.records.retain(|r| r.job_id != record.job_id);
+        self.records.push(record.clone());
+
+        let Some(dir) = &self.dir else {
+            if let Some(translations) = translations {
+                self.translations.insert(record.job_id, translations);
+            }
+            return Ok(());
+        };
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create history directory: {}", e))?;
+        if let Some(translations) = &translations {
+            Self::write_translations(dir, &record.job_id, translations)?;
+        }
+        if replaced {
+            return self.rewrite();
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join("history.jsonl"))
+            .map_err(|e| format!("Failed to open job history: {}", e))?;
+        let line = serde_json::to_string(&record).map_err(|e| e.to_string())?;
+        file.write_all(format!("{}\n", line).as_bytes())
+            .map_err(|e| format!("Failed to write job history: {}", e))
+    }
+
+    /// Matching records, newest first.
+    pub fn list(&self, filter: &HistoryFilter) -> Vec<HistoryRecord> {
+        let mut found: Vec<HistoryRecord> = self.records.iter().filter(|r| filter.matches(r)).cloned().collect();
+        found.sort_by_key(|r| std::cmp::Reverse(r.finished_at));
+        found
+    }
+
+    pub fn get(&self, job_id: &str) -> Option<&HistoryRecord> {
+        self.records.iter().find(|r| r.job_id == job_id)
+    }
+
+    pub fn translations(&self, job_id: &str) -> Result<HistoryTranslations, String> {
+        let Some(dir) = &self.dir else {
+            return self
+                .translations
+                .get(job_id)
+                .cloned()
+                .ok_or_else(|| "No translations were kept for this job.".to_string());
+        };
+        let content = fs::read_to_string(Self::translations_path(dir, job_id))
+            .map_err(|_| "No translations were kept for this job.".to_string())?;
+        serde_json::from_str(&content).map_err(|e| format!("Saved translations are unreadable: {}", e))
+    }
+
+    /// Keep a job's edited translation, so reopening it brings the edits back.
+    pub fn save_edits(&mut self, job_id: &str, translated: &HashMap<usize, String>, edits: SavedEdits) -> Result<(), String> {
+        // Jobs recorded without translations have nothing to reopen
+        let Ok(mut saved) = self.translations(job_id) else {
+            return Ok(());
+        };
+        saved.translated = translated.clone();
+        saved.edits = Some(edits);
+        match &self.dir {
+            Some(dir) => Self::write_translations(dir, job_id, &saved),
+            None => {
+                self.translations.insert(job_id.to_string(), saved);
+                Ok(())
+            }
+        }
+    }
+
+    /// Delete records and their translations; returns how many were removed.
+    pub fn delete(&mut self, job_ids: &[String]) -> Result<usize, String> {
+        let before = self.records.len();
+        self.records.retain(|r| !job_ids.contains(&r.job_id));
+        let removed = before - self.records.len();
+        for job_id in job_ids {
+            self.translations.remove(job_id);
+            if let Some(dir) = &self.dir {
+                let _ = fs::remove_file(Self::translations_path(dir, job_id));
+            }
+        }
+        if removed > 0 {
+            self.rewrite()?;
+        }
+        Ok(removed)
+    }
+
+    fn rewrite(&self) -> Result<(), String> {
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+        let mut content = String::new();
+        for record in &self.records {
+            content.push_str(&serde_json::to_string(record).map_err(|e| e.to_string())?);
+            content.push('\n');
+        }
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create history directory: {}", e))?;
+        // Write beside and rename, so a crash can't leave half a history
+        let tmp = dir.join("history.jsonl.tmp");
+        fs::write(&tmp, content).map_err(|e| format!("Failed to write job history: {}", e))?;
+        fs::rename(&tmp, dir.join("history.jsonl")).map_err(|e| format!("Failed to write job history: {}", e))
+    }
+}
+
+/// CSV of `records`, one row per job, with a header row.
+pub fn write_history_csv(records: &[HistoryRecord]) -> String {
+    let mut out = String::from(
+        "job_id,file_name,source_lang,target_lang,model,status,started_at,finished_at,duration_secs,cue_count,translated_cues,failed_cues,prompt_tokens,completion_tokens,cost_usd,output_path,error\n",
+    );
+    for r in records {
+        let fields = [
+            r.job_id.clone(),
+            r.file_name.clone(),
+            r.source_lang.clone(),
+            r.target_lang.clone(),
+            r.model.clone(),
+            format!("{:?}", r.status),
+            r.started_at.to_string(),
+            r.finished_at.to_string(),
+            r.duration_secs().to_string(),
+            r.cue_count.to_string(),
+            r.translated_cues.to_string(),
+            r.failed_cues.to_string(),
+            r.usage.prompt_tokens.to_string(),
+            r.usage.completion_tokens.to_string(),
+            r.cost_usd.map(|c| format!("{:.4}", c)).unwrap_or_default(),
+            r.output_path.clone().unwrap_or_default(),
+            r.error.clone().unwrap_or_default(),
+        ];
+        let row: Vec<String> = fields.iter().map(|f| delimited_field(f, ',')).collect();
+        out.push_str(&row.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::translate::temp_path;
+
+    fn record(job_id: &str, model: &str, status: JobStatus, finished_at: u64) -> HistoryRecord {
+        HistoryRecord {
+            job_id: job_id.into(),
+            file_name: format!("Show S01E0{}.srt", job_id),
+            file_path: String::new(),
+            source_lang: "English".into(),
+            target_lang: "Vietnamese".into(),
+            model: model.into(),
+            status,
+            started_at: finished_at - 60,
+            finished_at,
+            cue_count: 10,
+            translated_cues: 10,
+            failed_cues: 0,
+            usage: TokenUsage::default(),
+            cost_usd: None,
+            output_path: None,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_filter_and_order() {
+        let mut history = JobHistory::default();
+        history.add(record("1", "gpt-4o-mini", JobStatus::Done, 1_000), None).unwrap();
+        history.add(record("2", "claude-3-5-sonnet", JobStatus::Error, 2_000), None).unwrap();
+        history.add(record("3", "gpt-4o-mini", JobStatus::Done, 3_000), None).unwrap();
+
+        let ids = |filter: HistoryFilter| history.list(&filter).into_iter().map(|r| r.job_id).collect::<Vec<_>>();
+        assert_eq!(ids(HistoryFilter::default()), vec!["3", "2", "1"]);
+        assert_eq!(ids(HistoryFilter { model: Some("GPT-4o-mini".into()), ..Default::default() }), vec!["3", "1"]);
+        assert_eq!(ids(HistoryFilter { status: Some(JobStatus::Error), ..Default::default() }), vec!["2"]);
+        assert_eq!(ids(HistoryFilter { from: Some(1_500), to: Some(2_500), ..Default::default() }), vec!["2"]);
+        assert_eq!(ids(HistoryFilter { file_name: Some("s01e03".into()), ..Default::default() }), vec!["3"]);
+    }
+
+    #[test]
+    fn test_persists_and_deletes() {
+        let dir = temp_path("history-persist");
+        let mut history = JobHistory::open(&dir).unwrap();
+        history.add(record("1", "m", JobStatus::Done, 1_000), None).unwrap();
+        history.add(record("2", "m", JobStatus::Done, 2_000), None).unwrap();
+        // A retry replaces the earlier record
+        history.add(record("1", "m", JobStatus::PartiallyDone, 3_000), None).unwrap();
+
+        let mut reopened = JobHistory::open(&dir).unwrap();
+        assert_eq!(reopened.list(&HistoryFilter::default()).len(), 2);
+        assert_eq!(reopened.get("1").unwrap().status, JobStatus::PartiallyDone);
+        assert_eq!(reopened.delete(&["2".to_string()]).unwrap(), 1);
+        assert_eq!(JobHistory::open(&dir).unwrap().list(&HistoryFilter::default()).len(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_csv_export() {
+        let csv = write_history_csv(&[record("1", "gpt-4o-mini", JobStatus::Done, 1_000)]);
+        let row = csv.lines().nth(1).unwrap();
+        assert!(row.starts_with("1,Show S01E01.srt,English,Vietnamese,gpt-4o-mini,Done,940,1000,60,10,10,0,"));
+    }
+
+    #[test]
+    fn test_edits_survive_reopen() {
+        let dir = temp_path("history-edits");
+        let mut history = JobHistory::open(&dir).unwrap();
+        let translations = HistoryTranslations {
+            options: TranslationOptions::for_tests(),
+            source_hash: "abc".into(),
+            translated: HashMap::from([(0, "Xin chào".to_string())]),
+            document: None,
+            cue_origins: None,
+            edits: None,
+        };
+        history.add(record("1", "m", JobStatus::Done, 1_000), Some(translations)).unwrap();
+
+        let edits = SavedEdits { human: [0].into(), machine: HashMap::from([(0, "Xin chào".to_string())]) };
+        history.save_edits("1", &HashMap::from([(0, "Chào".to_string())]), edits.clone()).unwrap();
+        let saved = JobHistory::open(&dir).unwrap().translations("1").unwrap();
+        assert_eq!(saved.translated[&0], "Chào");
+        assert_eq!(saved.edits, Some(edits));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -3,6 +3,9 @@ mod translate;
 mod state;
 mod commands;
 mod proxy_config;
+mod queue;
+mod history;
+mod editor;
 mod clipproxy;
 
 use tauri::Manager;
@@ -37,6 +40,45 @@ pub fn run() {
             commands::jobs::start_job,
             commands::jobs::get_job,
             commands::jobs::list_jobs,
+            commands::jobs::get_qa_report,
+            commands::jobs::cancel_job,
+            commands::jobs::pause_job,
+            commands::jobs::resume_job,
+            commands::jobs::retry_failed_batches,
+            commands::queue::enqueue_job,
+            commands::queue::translate_all_files,
+            commands::queue::get_queue,
+            commands::queue::set_job_priority,
+            commands::queue::move_queued_job,
+            commands::queue::dequeue_job,
+            commands::queue::set_queue_limits,
+            commands::history::list_history,
+            commands::history::reopen_history_job,
+            commands::history::delete_history,
+            commands::history::export_history,
+            commands::editor::get_job_cues,
+            commands::editor::edit_cue,
+            commands::editor::revert_cue,
+            commands::editor::undo_edit,
+            commands::editor::redo_edit,
+            commands::editor::export_edited_job,
+            commands::timing::fix_timing,
+            commands::timing::shift_timeline,
+            commands::timing::stretch_timeline,
+            commands::timing::convert_framerate,
+            commands::timing::save_subtitle_file,
+            commands::memory::get_memory_stats,
+            commands::memory::search_memory,
+            commands::memory::prune_memory,
+            commands::memory::clear_memory,
+            commands::memory::import_memory_file,
+            commands::memory::export_job_translations,
+            commands::glossary::list_glossaries,
+            commands::glossary::save_glossary,
+            commands::glossary::delete_glossary,
+            commands::speakers::list_speaker_tables,
+            commands::speakers::save_speaker_table,
+            commands::speakers::delete_speaker_table,
             commands::proxypal::get_proxypal_status,
             commands::proxy_config::get_proxy_config,
             commands::proxy_config::save_proxy_config,
@@ -0,0 +1,270 @@
+//! Job queue: which waiting jobs start next, across all imported files.
+//!
+//! Goals:
+//! - Higher priority first, first-in first-out within a priority
+//! - At most `max_running_jobs` queued jobs translate at once
+//! - One request semaphore shared by every job, so a whole season can't flood the API
+//! - Reorder by priority or by moving a job to a position
+
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct QueueEntry {
+    pub job_id: String,
+    #[serde(default)]
+    pub priority: i32,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct QueueLimits {
+    pub max_running_jobs: usize,
+    /// Requests in flight across all jobs (each job is also held to its own `threads`).
+    pub max_concurrent_requests: usize,
+}
+
+impl Default for QueueLimits {
+    fn default() -> Self {
+        Self { max_running_jobs: 2, max_concurrent_requests: 6 }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueSnapshot {
+    /// In start order.
+    pub waiting: Vec<QueueEntry>,
+    pub running: Vec<String>,
+    pub limits: QueueLimits,
+}
+
+/// The request limit shared by every job; resized in place, so jobs that are already
+/// running are held to the new limit too.
+#[derive(Debug, Clone)]
+pub struct RequestSlots {
+    semaphore: Arc<Semaphore>,
+    /// Slots to retire as they're released, after the limit was lowered while they were held.
+    excess: Arc<AtomicUsize>,
+}
+
+/// One request in flight; gives its slot back (or retires it) on drop.
+#[derive(Debug)]
+pub struct RequestPermit {
+    permit: Option<OwnedSemaphorePermit>,
+    excess: Arc<AtomicUsize>,
+}
+
+impl Drop for RequestPermit {
+    fn drop(&mut self) {
+        let retire = self
+            .excess
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok();
+        if let (true, Some(permit)) = (retire, self.permit.take()) {
+            permit.forget();
+        }
+    }
+}
+
+impl RequestSlots {
+    fn new(limit: usize) -> Self {
+        Self { semaphore: Arc::new(Semaphore::new(limit)), excess: Arc::new(AtomicUsize::new(0)) }
+    }
+
+    pub async fn acquire(&self) -> RequestPermit {
+        // Never closed
+        let permit = self.semaphore.clone().acquire_owned().await.unwrap();
+        RequestPermit { permit: Some(permit), excess: self.excess.clone() }
+    }
+
+    pub fn available(&self) -> usize {
+        self.semaphore.available_permits()
+    }
+
+    fn resize(&self, from: usize, to: usize) {
+        if to > from {
+            // Slots still due to retire cover part of the raise
+            let grow = to - from;
+            let cancelled = self
+                .excess
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| Some(n.saturating_sub(grow)))
+                .unwrap()
+                .min(grow);
+            self.semaphore.add_permits(grow - cancelled);
+        } else {
+            let shrink = from - to;
+            let forgotten = self.semaphore.forget_permits(shrink);
+            self.excess.fetch_add(shrink - forgotten, Ordering::SeqCst);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct JobQueue {
+    /// Sorted: priority descending, then arrival.
+    waiting: Vec<QueueEntry>,
+    /// Jobs the queue started that haven't finished.
+    running: Vec<String>,
+    limits: QueueLimits,
+    request_slots: RequestSlots,
+}
+
+impl Default for JobQueue {
+    fn default() -> Self {
+        Self::new(QueueLimits::default())
+    }
+}
+
+impl JobQueue {
+    pub fn new(limits: QueueLimits) -> Self {
+        Self {
+            waiting: Vec::new(),
+            running: Vec::new(),
+            request_slots: RequestSlots::new(limits.max_concurrent_requests.max(1)),
+            limits,
+        }
+    }
+
+    pub fn snapshot(&self) -> QueueSnapshot {
+        QueueSnapshot { waiting: self.waiting.clone(), running: self.running.clone(), limits: self.limits }
+    }
+
+    /// Shared by every job.
+    pub fn request_slots(&self) -> RequestSlots {
+        self.request_slots.clone()
+    }
+
+    /// A new request limit applies at once, to running jobs as well.
+    pub fn set_limits(&mut self, limits: QueueLimits) {
+        self.request_slots.resize(
+            self.limits.max_concurrent_requests.max(1),
+            limits.max_concurrent_requests.max(1),
+        );
+        self.limits = limits;
+    }
+
+    pub fn contains(&self, job_id: &str) -> bool {
+        self.waiting.iter().any(|e| e.job_id == job_id) || self.running.iter().any(|id| id == job_id)
+    }
+
+    /// Add behind every job of the same or higher priority; false if already queued or running.
+    pub fn push(&mut self, job_id: &str, priority: i32) -> bool {
+        if self.contains(job_id) {
+            return false;
+        }
+        let at = self.waiting.iter().position(|e| e.priority < priority).unwrap_or(self.waiting.len());
+        self.waiting.insert(at, QueueEntry { job_id: job_id.to_string(), priority });
+        true
+    }
+
+    /// Take a waiting job out of the queue.
+    pub fn remove(&mut self, job_id: &str) -> bool {
+        let before = self.waiting.len();
+        self.waiting.retain(|e| e.job_id != job_id);
+        self.waiting.len() != before
+    }
+
+    /// Requeue at the back of its new priority.
+    pub fn set_priority(&mut self, job_id: &str, priority: i32) -> bool {
+        self.remove(job_id) && self.push(job_id, priority)
+    }
+
+    /// Move a waiting job to `position` (0 = next); it takes the priority of the job it lands
+    /// behind (or in front of, at the head), so later pushes keep the order.
+    pub fn move_to(&mut self, job_id: &str, position: usize) -> bool {
+        let Some(from) = self.waiting.iter().position(|e| e.job_id == job_id) else {
+            return false;
+        };
+        let mut entry = self.waiting.remove(from);
+        let at = position.min(self.waiting.len());
+        let neighbour = if at > 0 { self.waiting.get(at - 1) } else { self.waiting.first() };
+        if let Some(neighbour) = neighbour {
+            entry.priority = neighbour.priority;
+        }
+        self.waiting.insert(at, entry);
+        true
+    }
+
+    /// Jobs to start now, moved to `running`.
+    pub fn take_startable(&mut self) -> Vec<String> {
+        let room = self.limits.max_running_jobs.max(1).saturating_sub(self.running.len());
+        let started: Vec<String> = self.waiting.drain(..room.min(self.waiting.len())).map(|e| e.job_id).collect();
+        self.running.extend(started.iter().cloned());
+        started
+    }
+
+    pub fn finish(&mut self, job_id: &str) {
+        self.running.retain(|id| id != job_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(queue: &JobQueue) -> Vec<&str> {
+        queue.waiting.iter().map(|e| e.job_id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_priority_then_fifo() {
+        let mut queue = JobQueue::default();
+        queue.push("ep1", 0);
+        queue.push("ep2", 0);
+        queue.push("urgent", 5);
+        queue.push("ep3", 0);
+        assert!(!queue.push("ep2", 9));
+        assert_eq!(order(&queue), vec!["urgent", "ep1", "ep2", "ep3"]);
+
+        queue.set_priority("ep3", 5);
+        assert_eq!(order(&queue), vec!["urgent", "ep3", "ep1", "ep2"]);
+    }
+
+    #[test]
+    fn test_move_keeps_order_for_later_pushes() {
+        let mut queue = JobQueue::default();
+        for id in ["a", "b", "c"] {
+            queue.push(id, 0);
+        }
+        queue.push("hot", 3);
+        assert!(queue.move_to("c", 0));
+        assert_eq!(order(&queue), vec!["c", "hot", "a", "b"]);
+        queue.push("d", 3);
+        assert_eq!(order(&queue), vec!["c", "hot", "d", "a", "b"]);
+    }
+
+    #[test]
+    fn test_take_startable_respects_cap() {
+        let mut queue = JobQueue::new(QueueLimits { max_running_jobs: 2, max_concurrent_requests: 4 });
+        for id in ["a", "b", "c"] {
+            queue.push(id, 0);
+        }
+        assert_eq!(queue.take_startable(), vec!["a", "b"]);
+        assert!(queue.take_startable().is_empty());
+        queue.finish("a");
+        assert_eq!(queue.take_startable(), vec!["c"]);
+        assert_eq!(queue.request_slots().available(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_request_limit_changes_while_held() {
+        let mut queue = JobQueue::new(QueueLimits { max_running_jobs: 2, max_concurrent_requests: 2 });
+        let slots = queue.request_slots();
+        let first = slots.acquire().await;
+        let second = slots.acquire().await;
+
+        // Both slots are in use; the one released first is retired
+        queue.set_limits(QueueLimits { max_running_jobs: 2, max_concurrent_requests: 1 });
+        drop(first);
+        assert_eq!(slots.available(), 0);
+        drop(second);
+        assert_eq!(slots.available(), 1);
+
+        let held = slots.acquire().await;
+        queue.set_limits(QueueLimits { max_running_jobs: 2, max_concurrent_requests: 3 });
+        assert_eq!(slots.available(), 2);
+        drop(held);
+        assert_eq!(queue.request_slots().available(), 3);
+    }
+}
@@ -0,0 +1,471 @@
+//! ASS/SSA (Advanced SubStation Alpha) parsing + writing.
+//!
+//! Goals:
+//! - Keep `[Script Info]`, `[V4+ Styles]`, comments and any other section untouched
+//! - Keep every `Dialogue:` field except Text byte-for-byte (layer, style, actor, margins, effect)
+//! - Expose only the Text field to the batcher, as an `SrtDocument` view
+//! - Write back a file that differs from the input only in dialogue text
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::bilingual::BilingualOptions;
+use super::{CueLayout, NewlineStyle, SrtCue, SrtDocument, SrtError, SrtTime};
+
+/// Event columns used when a file has no `Format:` line in `[Events]` (ASS v4+ default).
+const DEFAULT_EVENT_FORMAT: [&str; 10] = [
+    "Layer", "Start", "End", "Style", "Name", "MarginL", "MarginR", "MarginV", "Effect", "Text",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssEvent {
+    /// Index into `AssDocument::lines` of the `Dialogue:` line.
+    pub line_no: usize,
+
+    /// Everything before the Text field (including the last comma), kept verbatim.
+    pub prefix: String,
+
+    /// Raw Text field: override tags and `\N` hard breaks included.
+    pub text: String,
+
+    pub start: SrtTime,
+    pub end: SrtTime,
+    pub style: String,
+
+    /// The `Name` column (speaker/actor).
+    pub actor: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssDocument {
+    pub newline: NewlineStyle,
+
+    /// Every line of the file, without line terminators.
+    pub lines: Vec<String>,
+
+    /// Whether the last line was terminated by a newline.
+    pub final_newline: bool,
+
+    /// Dialogue events in file order; event N becomes cue id N.
+    pub events: Vec<AssEvent>,
+}
+
+impl AssDocument {
+    /// Cue view of the dialogue text for the batcher/translator.
+    /// ASS hard breaks (`\N`) become separate text lines.
+    pub fn to_srt_document(&self) -> SrtDocument {
+        let cues = self
+            .events
+            .iter()
+            .enumerate()
+            .map(|(id, ev)| SrtCue {
+                id,
+                index_line: (id + 1).to_string(),
+                timing_line: format!("{} --> {}", ev.start.format(), ev.end.format()),
+                start: ev.start.clone(),
+                end: ev.end.clone(),
+                text_lines: ev.text.split("\\N").map(str::to_string).collect(),
+                layout: CueLayout::default(),
+            })
+            .collect();
+
+        SrtDocument::new(self.newline, cues)
+    }
+
+    /// Take the times of the cue view (cue N = event N) back into the Dialogue lines,
+    /// e.g. after the cues were shifted; only the Start and End fields change.
+    pub fn set_times(&mut self, doc: &SrtDocument) {
+        let format = event_format(&self.lines);
+        let column = |name: &str| format.iter().position(|f| f.eq_ignore_ascii_case(name));
+        let (Some(start_col), Some(end_col)) = (column("Start"), column("End")) else {
+            return;
+        };
+
+        for (ev, cue) in self.events.iter_mut().zip(&doc.cues) {
+            // The prefix ends with the comma before Text, so its fields line up with `format`
+            let mut fields: Vec<String> = ev.prefix.split(',').map(str::to_string).collect();
+            if let Some(field) = fields.get_mut(start_col) {
+                *field = format_ass_time(&cue.start);
+            }
+            if let Some(field) = fields.get_mut(end_col) {
+                *field = format_ass_time(&cue.end);
+            }
+            ev.prefix = fields.join(",");
+            ev.start = cue.start.clone();
+            ev.end = cue.end.clone();
+            self.lines[ev.line_no] = format!("{}{}", ev.prefix, ev.text);
+        }
+    }
+}
+
+/// Columns of the `[Events]` Format line (the v4+ default when there is none).
+fn event_format(lines: &[String]) -> Vec<String> {
+    let mut section = String::new();
+    for line in lines {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') && trimmed.ends_with(']') {
+            section = trimmed.to_ascii_lowercase();
+        } else if let (true, Some(rest)) = (section == "[events]", trimmed.strip_prefix("Format:")) {
+            return rest.split(',').map(|f| f.trim().to_string()).collect();
+        }
+    }
+    DEFAULT_EVENT_FORMAT.iter().map(|s| s.to_string()).collect()
+}
+
+/// Parse decoded ASS/SSA text.
+pub fn parse_ass_str(text: &str, newline: NewlineStyle) -> Result<AssDocument, SrtError> {
+    let normalized = text.replace("\r\n", "\n").replace('\r', "\n");
+    let final_newline = normalized.ends_with('\n');
+    let mut lines: Vec<String> = normalized.split('\n').map(str::to_string).collect();
+    if final_newline {
+        lines.pop();
+    }
+
+    let mut section = String::new();
+    let mut format: Vec<String> = DEFAULT_EVENT_FORMAT.iter().map(|s| s.to_string()).collect();
+    let mut events: Vec<AssEvent> = Vec::new();
+
+    for (i, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') && trimmed.ends_with(']') {
+            section = trimmed.to_ascii_lowercase();
+            continue;
+        }
+        if section != "[events]" {
+            continue;
+        }
+
+        if let Some(rest) = trimmed.strip_prefix("Format:") {
+            format = rest.split(',').map(|f| f.trim().to_string()).collect();
+            if format.last().map(String::as_str) != Some("Text") {
+                return Err(SrtError::Format {
+                    line: i + 1,
+                    message: "The [Events] Format line must end with the Text column.".into(),
+                });
+            }
+            continue;
+        }
+
+        if trimmed.starts_with("Dialogue:") {
+            events.push(parse_dialogue_line(line, i, &format)?);
+        }
+    }
+
+    if events.is_empty() {
+        return Err(SrtError::Format {
+            line: 1,
+            message: "No Dialogue lines found. Make sure this is a valid .ass/.ssa file.".into(),
+        });
+    }
+
+    Ok(AssDocument {
+        newline,
+        lines,
+        final_newline,
+        events,
+    })
+}
+
+fn parse_dialogue_line(line: &str, line_no: usize, format: &[String]) -> Result<AssEvent, SrtError> {
+    let fmt_err = |message: String| SrtError::Format {
+        line: line_no + 1,
+        message,
+    };
+
+    let body_start = line.find("Dialogue:").map(|p| p + "Dialogue:".len()).unwrap_or(0);
+
+    // Text is the last column and may itself contain commas, so only the
+    // first (columns - 1) commas are separators.
+    let mut text_start = body_start;
+    let mut fields: Vec<&str> = Vec::with_capacity(format.len());
+    for _ in 0..format.len() - 1 {
+        let comma = line[text_start..].find(',').ok_or_else(|| {
+            fmt_err(format!(
+                "Dialogue line has fewer fields than the {} declared by the Format line.",
+                format.len()
+            ))
+        })?;
+        fields.push(line[text_start..text_start + comma].trim());
+        text_start += comma + 1;
+    }
+
+    let field = |name: &str| -> &str {
+        format
+            .iter()
+            .position(|f| f.eq_ignore_ascii_case(name))
+            .and_then(|idx| fields.get(idx).copied())
+            .unwrap_or("")
+    };
+
+    let start = parse_ass_time(field("Start")).map_err(|e| fmt_err(format!("Invalid start time: {e}")))?;
+    let end = parse_ass_time(field("End")).map_err(|e| fmt_err(format!("Invalid end time: {e}")))?;
+
+    Ok(AssEvent {
+        line_no,
+        prefix: line[..text_start].to_string(),
+        text: line[text_start..].to_string(),
+        start,
+        end,
+        style: field("Style").to_string(),
+        actor: field("Name").to_string(),
+    })
+}
+
+/// "H:MM:SS.cc", rounded down to the centisecond.
+fn format_ass_time(t: &SrtTime) -> String {
+    format!("{}:{:02}:{:02}.{:02}", t.hours, t.minutes, t.seconds, t.millis / 10)
+}
+
+/// Parse "H:MM:SS.cc" (centiseconds; 1–3 fractional digits are accepted).
+fn parse_ass_time(s: &str) -> Result<SrtTime, String> {
+    let parts: Vec<&str> = s.split(':').collect();
+    if parts.len() != 3 {
+        return Err(format!("Expected time like H:MM:SS.cc but got '{s}'"));
+    }
+
+    let hours: u32 = parts[0].parse().map_err(|_| format!("Invalid hours in '{s}'"))?;
+    let minutes: u32 = parts[1].parse().map_err(|_| format!("Invalid minutes in '{s}'"))?;
+    let (sec_str, frac_str) = parts[2].split_once('.').unwrap_or((parts[2], "0"));
+    let seconds: u32 = sec_str.parse().map_err(|_| format!("Invalid seconds in '{s}'"))?;
+
+    if frac_str.is_empty() || frac_str.len() > 3 {
+        return Err(format!("Invalid fraction of a second in '{s}'"));
+    }
+    let frac: u32 = frac_str.parse().map_err(|_| format!("Invalid fraction of a second in '{s}'"))?;
+    let millis = frac * 10u32.pow(3 - frac_str.len() as u32);
+
+    if minutes > 59 || seconds > 59 {
+        return Err(format!("Time components out of range in '{s}'"));
+    }
+
+    Ok(SrtTime {
+        hours,
+        minutes,
+        seconds,
+        millis,
+    })
+}
+
+/// Write the ASS file back with translated dialogue text.
+/// `translated` maps cue_id (event index) -> text; '\n' is written as `\N`.
+pub fn write_ass(doc: &AssDocument, translated: &HashMap<usize, String>) -> Result<String, String> {
+    Ok(finish(doc, translated_lines(doc, translated)?))
+}
+
+/// All lines of the file with dialogue text replaced.
+fn translated_lines(doc: &AssDocument, translated: &HashMap<usize, String>) -> Result<Vec<String>, String> {
+    if translated.len() != doc.events.len() {
+        return Err(format!(
+            "Translation mismatch: expected {} dialogue lines but got {} translations.",
+            doc.events.len(),
+            translated.len()
+        ));
+    }
+
+    let mut lines = doc.lines.clone();
+    for (id, ev) in doc.events.iter().enumerate() {
+        let text = translated
+            .get(&id)
+            .ok_or_else(|| format!("Missing translation for cue id {id}"))?;
+        lines[ev.line_no] = format!("{}{}", ev.prefix, ass_text(text));
+    }
+    Ok(lines)
+}
+
+fn ass_text(text: &str) -> String {
+    text.replace("\r\n", "\n").replace('\n', "\\N")
+}
+
+fn finish(doc: &AssDocument, lines: Vec<String>) -> String {
+    let nl = doc.newline.as_str();
+    let mut out = lines.join(nl);
+    if doc.final_newline {
+        out.push_str(nl);
+    }
+    out
+}
+
+/// Suffix of the style copies that carry the second track of bilingual output.
+const SECONDARY_STYLE_SUFFIX: &str = " - Secondary";
+
+/// Write the ASS file with two tracks: `first` replaces each dialogue's text,
+/// `second` is added right after it as a new dialogue on a top-aligned copy of its style
+/// (italic/colored as `opts` asks).
+pub fn write_ass_two_tracks(
+    doc: &AssDocument,
+    first: &HashMap<usize, String>,
+    second: &HashMap<usize, String>,
+    opts: &BilingualOptions,
+) -> Result<String, String> {
+    let lines = translated_lines(doc, first)?;
+
+    // Lines to add after a given line index of the source
+    let mut inserts: HashMap<usize, Vec<String>> = HashMap::new();
+
+    let used_styles: Vec<&str> = doc.events.iter().map(|ev| ev.style.as_str()).collect();
+    let mut section = String::new();
+    let mut style_format: Vec<String> = Vec::new();
+    let mut event_format: Vec<String> = DEFAULT_EVENT_FORMAT.iter().map(|s| s.to_string()).collect();
+    let mut copies: Vec<String> = Vec::new();
+    let mut last_style_line = None;
+
+    for (i, line) in doc.lines.iter().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') && trimmed.ends_with(']') {
+            section = trimmed.to_ascii_lowercase();
+            continue;
+        }
+        let columns = || -> Vec<String> { trimmed["Format:".len()..].split(',').map(|f| f.trim().to_string()).collect() };
+
+        match section.as_str() {
+            "[v4+ styles]" | "[v4 styles]" => {
+                if trimmed.starts_with("Format:") {
+                    style_format = columns();
+                } else if let Some(rest) = trimmed.strip_prefix("Style:") {
+                    let mut fields: Vec<String> = rest.split(',').map(|f| f.trim().to_string()).collect();
+                    let name = fields.first().cloned().unwrap_or_default();
+                    last_style_line = Some(i);
+                    if !used_styles.contains(&name.as_str()) {
+                        continue;
+                    }
+
+                    // SSA (v4) uses its own alignment numbers: 6 is top center
+                    let top = if section == "[v4 styles]" { "6" } else { "8" };
+                    for (col, value) in style_format.iter().zip(fields.iter_mut()) {
+                        match col.as_str() {
+                            "Name" => *value = format!("{name}{SECONDARY_STYLE_SUFFIX}"),
+                            "Alignment" => *value = top.to_string(),
+                            "Italic" if opts.secondary_italic => *value = "-1".to_string(),
+                            "PrimaryColour" => {
+                                if let Some(color) = opts.ass_color() {
+                                    *value = color;
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                    copies.push(format!("Style: {}", fields.join(",")));
+                }
+            }
+            "[events]" if trimmed.starts_with("Format:") => event_format = columns(),
+            _ => {}
+        }
+    }
+
+    if let Some(after) = last_style_line {
+        inserts.entry(after).or_default().extend(copies);
+    }
+
+    let style_col = event_format
+        .iter()
+        .position(|f| f.eq_ignore_ascii_case("Style"))
+        .ok_or("The [Events] Format line has no Style column.")?;
+    for (id, ev) in doc.events.iter().enumerate() {
+        let text = second
+            .get(&id)
+            .ok_or_else(|| format!("Missing translation for cue id {id}"))?;
+
+        // The prefix ends with the comma before Text; swap only the Style field
+        let mut fields: Vec<String> = ev.prefix.split(',').map(str::to_string).collect();
+        if let Some(field) = fields.get_mut(style_col) {
+            *field = format!("{}{SECONDARY_STYLE_SUFFIX}", field.trim());
+        }
+        inserts.entry(ev.line_no).or_default().push(format!("{}{}", fields.join(","), ass_text(text)));
+    }
+
+    let mut out_lines: Vec<String> = Vec::new();
+    for (i, line) in lines.into_iter().enumerate() {
+        out_lines.push(line);
+        if let Some(extra) = inserts.remove(&i) {
+            out_lines.extend(extra);
+        }
+    }
+    Ok(finish(doc, out_lines))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "[Script Info]\r\n; Script generated by Aegisub\r\nTitle: Sample\r\nScriptType: v4.00+\r\n\r\n[V4+ Styles]\r\nFormat: Name, Fontname, Fontsize, PrimaryColour\r\nStyle: Default,Arial,20,&H00FFFFFF\r\n\r\n[Events]\r\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\r\nComment: 0,0:00:00.00,0:00:01.00,Default,,0,0,0,,karaoke template\r\nDialogue: 0,0:00:01.50,0:00:03.00,Default,Tom,0,0,0,,Hello, {\\i1}world{\\i0}\r\nDialogue: 1,0:00:04.00,0:00:06.25,Sign,,10,10,20,Scroll up;10,First\\NSecond\r\n";
+
+    #[test]
+    fn test_parse_dialogue_fields() {
+        let doc = parse_ass_str(SAMPLE, NewlineStyle::CrLf).unwrap();
+
+        assert_eq!(doc.events.len(), 2);
+        assert_eq!(doc.events[0].actor, "Tom");
+        assert_eq!(doc.events[0].text, "Hello, {\\i1}world{\\i0}");
+        assert_eq!(doc.events[0].start.millis, 500);
+        assert_eq!(doc.events[1].style, "Sign");
+        assert_eq!(doc.events[1].end.millis, 250);
+
+        let cues = doc.to_srt_document().cues;
+        assert_eq!(cues[1].text_lines, vec!["First", "Second"]);
+        assert_eq!(cues[1].timing_line, "00:00:04,000 --> 00:00:06,250");
+    }
+
+    #[test]
+    fn test_write_ass_identity_is_byte_identical() {
+        let doc = parse_ass_str(SAMPLE, NewlineStyle::CrLf).unwrap();
+        let translated: HashMap<usize, String> = doc
+            .to_srt_document()
+            .cues
+            .iter()
+            .map(|c| (c.id, c.text_lines.join("\n")))
+            .collect();
+
+        assert_eq!(write_ass(&doc, &translated).unwrap(), SAMPLE);
+    }
+
+    #[test]
+    fn test_write_ass_replaces_only_text() {
+        let doc = parse_ass_str(SAMPLE, NewlineStyle::CrLf).unwrap();
+        let mut translated = HashMap::new();
+        translated.insert(0, "Xin chào, {\\i1}thế giới{\\i0}".to_string());
+        translated.insert(1, "Một\nHai".to_string());
+
+        let out = write_ass(&doc, &translated).unwrap();
+
+        assert!(out.contains("Dialogue: 0,0:00:01.50,0:00:03.00,Default,Tom,0,0,0,,Xin chào, {\\i1}thế giới{\\i0}\r\n"));
+        assert!(out.contains("Dialogue: 1,0:00:04.00,0:00:06.25,Sign,,10,10,20,Scroll up;10,Một\\NHai\r\n"));
+        assert!(out.contains("Comment: 0,0:00:00.00,0:00:01.00,Default,,0,0,0,,karaoke template\r\n"));
+        assert!(out.starts_with("[Script Info]\r\n; Script generated by Aegisub\r\n"));
+    }
+
+    #[test]
+    fn test_write_two_tracks_adds_secondary_style_and_events() {
+        let doc = parse_ass_str(SAMPLE, NewlineStyle::CrLf).unwrap();
+        let first = HashMap::from([(0, "Xin chào".to_string()), (1, "Một\nHai".to_string())]);
+        let second = HashMap::from([(0, "Hello".to_string()), (1, "One".to_string())]);
+        let opts = BilingualOptions {
+            secondary_italic: true,
+            secondary_color: Some("#FFFF00".into()),
+            ..Default::default()
+        };
+
+        let out = write_ass_two_tracks(&doc, &first, &second, &opts).unwrap();
+
+        assert!(out.contains("Style: Default,Arial,20,&H00FFFFFF\r\nStyle: Default - Secondary,Arial,20,&H0000FFFF\r\n"));
+        assert!(out.contains(
+            "Dialogue: 0,0:00:01.50,0:00:03.00,Default,Tom,0,0,0,,Xin chào\r\nDialogue: 0,0:00:01.50,0:00:03.00,Default - Secondary,Tom,0,0,0,,Hello\r\n"
+        ));
+        assert!(out.contains("Dialogue: 1,0:00:04.00,0:00:06.25,Sign - Secondary,,10,10,20,Scroll up;10,One\r\n"));
+        assert!(out.ends_with("\r\n"));
+    }
+
+    #[test]
+    fn test_set_times_rewrites_start_and_end() {
+        let mut doc = parse_ass_str(SAMPLE, NewlineStyle::CrLf).unwrap();
+        let mut cues = doc.to_srt_document();
+        crate::srt::timeline::shift(&mut cues, 2_300);
+        doc.set_times(&cues);
+
+        let translated = cues.cues.iter().map(|c| (c.id, c.text_lines.join("\n"))).collect();
+        let out = write_ass(&doc, &translated).unwrap();
+        assert!(out.contains("Dialogue: 0,0:00:03.80,0:00:05.30,Default,Tom,0,0,0,,Hello, {\\i1}world{\\i0}\r\n"));
+        assert!(out.contains("Dialogue: 1,0:00:06.30,0:00:08.55,Sign,,10,10,20,Scroll up;10,First\\NSecond\r\n"));
+        // Comments are not dialogue and keep their times
+        assert!(out.contains("Comment: 0,0:00:00.00,0:00:01.00,"));
+    }
+}
@@ -0,0 +1,186 @@
+//! Bilingual (original + translation) output.
+//!
+//! Goals:
+//! - Show both texts in every cue, in a configurable order, joined by a configurable separator
+//! - Optionally style the second text (`<i>`, `<font color>`) so the two are easy to tell apart
+//! - For ASS output, write the second text as its own track on a copy of the event's style
+//! - Build on the `SrtDocument` cue view and the translated map; nothing else changes
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::{ass, write_subtitle, SrtDocument, SubtitleFormat, SubtitleSource};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum BilingualOrder {
+    OriginalFirst,
+    TranslationFirst,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BilingualOptions {
+    pub order: BilingualOrder,
+
+    /// Between the two texts in SRT/VTT output; "\n" puts them on separate lines.
+    pub separator: String,
+
+    /// Italicize the second text.
+    #[serde(default)]
+    pub secondary_italic: bool,
+
+    /// Color of the second text as "#RRGGBB" (SRT and ASS only; WebVTT has no font colors).
+    #[serde(default)]
+    pub secondary_color: Option<String>,
+}
+
+impl Default for BilingualOptions {
+    fn default() -> Self {
+        Self {
+            order: BilingualOrder::TranslationFirst,
+            separator: "\n".into(),
+            secondary_italic: false,
+            secondary_color: None,
+        }
+    }
+}
+
+impl BilingualOptions {
+    /// (first, second) for one cue.
+    fn arrange<'a>(&self, original: &'a str, translation: &'a str) -> (&'a str, &'a str) {
+        match self.order {
+            BilingualOrder::OriginalFirst => (original, translation),
+            BilingualOrder::TranslationFirst => (translation, original),
+        }
+    }
+
+    /// `secondary_color`, when it is a valid "#RRGGBB"; anything else is ignored.
+    fn hex_color(&self) -> Option<&str> {
+        let color = self.secondary_color.as_deref()?.trim();
+        let hex = color.strip_prefix('#')?;
+        (hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit())).then_some(color)
+    }
+
+    /// Color as ASS "&H00BBGGRR", when a valid "#RRGGBB" was given.
+    pub(super) fn ass_color(&self) -> Option<String> {
+        let hex = &self.hex_color()?[1..];
+        Some(format!("&H00{}{}{}", &hex[4..6], &hex[2..4], &hex[0..2]).to_ascii_uppercase())
+    }
+}
+
+/// Original cue text, as it would be written back.
+fn originals(doc: &SrtDocument) -> HashMap<usize, String> {
+    doc.cues.iter().map(|c| (c.id, c.text_lines.join("\n"))).collect()
+}
+
+/// Cue text with both languages, styled for `format` (SRT/VTT).
+pub fn merge_texts(
+    doc: &SrtDocument,
+    translated: &HashMap<usize, String>,
+    opts: &BilingualOptions,
+    format: SubtitleFormat,
+) -> HashMap<usize, String> {
+    let originals = originals(doc);
+    let mut merged = HashMap::with_capacity(translated.len());
+
+    for (id, translation) in translated {
+        let original = originals.get(id).map(String::as_str).unwrap_or("");
+        let (first, second) = opts.arrange(original, translation);
+
+        let mut second = second.to_string();
+        if opts.secondary_italic {
+            second = format!("<i>{second}</i>");
+        }
+        if let (Some(color), SubtitleFormat::Srt) = (opts.hex_color(), format) {
+            second = format!("<font color=\"{color}\">{second}</font>");
+        }
+
+        merged.insert(*id, format!("{first}{}{second}", opts.separator));
+    }
+    merged
+}
+
+/// Write bilingual output as `format`.
+/// ASS (from an ASS source) gets two tracks; SRT/VTT get both texts in each cue.
+pub fn write_bilingual(
+    doc: &SrtDocument,
+    source: &SubtitleSource,
+    translated: &HashMap<usize, String>,
+    opts: &BilingualOptions,
+    format: SubtitleFormat,
+) -> Result<String, String> {
+    if let (SubtitleSource::Ass(ass_doc), SubtitleFormat::Ass) = (source, format) {
+        let originals = originals(doc);
+        let mut first = HashMap::with_capacity(translated.len());
+        let mut second = HashMap::with_capacity(translated.len());
+        for (id, translation) in translated {
+            let original = ass_doc.events.get(*id).map(|ev| ev.text.replace("\\N", "\n"));
+            let original = original.or_else(|| originals.get(id).cloned()).unwrap_or_default();
+            let (a, b) = opts.arrange(&original, translation);
+            first.insert(*id, a.to_string());
+            second.insert(*id, b.to_string());
+        }
+        return ass::write_ass_two_tracks(ass_doc, &first, &second, opts);
+    }
+
+    write_subtitle(doc, source, &merge_texts(doc, translated, opts, format), format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::srt::parse_srt_bytes;
+
+    #[test]
+    fn test_srt_bilingual_with_styling() {
+        let doc = parse_srt_bytes(b"1\n00:00:01,000 --> 00:00:02,000\nHello\n").unwrap();
+        let translated = HashMap::from([(0, "Xin chào".to_string())]);
+        let opts = BilingualOptions {
+            secondary_italic: true,
+            secondary_color: Some("#ffff00".into()),
+            ..Default::default()
+        };
+
+        let out = write_bilingual(&doc, &SubtitleSource::Srt, &translated, &opts, SubtitleFormat::Srt).unwrap();
+        assert_eq!(
+            out,
+            "1\n00:00:01,000 --> 00:00:02,000\nXin chào\n<font color=\"#ffff00\"><i>Hello</i></font>\n"
+        );
+    }
+
+    #[test]
+    fn test_vtt_bilingual_original_first_without_color() {
+        let doc = parse_srt_bytes(b"1\n00:00:01,000 --> 00:00:02,000\nHello\n").unwrap();
+        let translated = HashMap::from([(0, "Xin chào".to_string())]);
+        let opts = BilingualOptions {
+            order: BilingualOrder::OriginalFirst,
+            separator: " / ".into(),
+            secondary_italic: false,
+            secondary_color: Some("#ffff00".into()),
+        };
+
+        let merged = merge_texts(&doc, &translated, &opts, SubtitleFormat::Vtt);
+        assert_eq!(merged[&0], "Hello / Xin chào");
+    }
+
+    #[test]
+    fn test_ass_color_conversion() {
+        let opts = BilingualOptions {
+            secondary_color: Some("#12a4ff".into()),
+            ..Default::default()
+        };
+        assert_eq!(opts.ass_color().as_deref(), Some("&H00FFA412"));
+    }
+
+    #[test]
+    fn test_invalid_color_is_dropped() {
+        let doc = parse_srt_bytes(b"1\n00:00:01,000 --> 00:00:02,000\nHello\n").unwrap();
+        let translated = HashMap::from([(0, "Xin chào".to_string())]);
+        let opts = BilingualOptions {
+            secondary_color: Some("yellow\"><script>".into()),
+            ..Default::default()
+        };
+
+        assert_eq!(merge_texts(&doc, &translated, &opts, SubtitleFormat::Srt)[&0], "Xin chào\nHello");
+        assert_eq!(opts.ass_color(), None);
+    }
+}
@@ -0,0 +1,174 @@
+//! Encoding written output back to bytes.
+//!
+//! Goals:
+//! - Write files back in the encoding they were read in (incl. BOM)
+//! - Support UTF-16 (which encoding_rs only decodes)
+//! - Never emit HTML numeric references: unmappable characters become '?'
+//! - Report which cues contain characters the target encoding cannot represent
+
+use encoding_rs::{EncoderResult, Encoding};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::SrtDocument;
+
+/// Requested encoding for written files.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutputEncoding {
+    /// encoding_rs label: "utf-8", "utf-16le", "windows-1252", "gb18030", "big5", "shift_jis", ...
+    pub encoding: String,
+
+    /// Write a byte order mark (UTF-8 / UTF-16 only).
+    #[serde(default)]
+    pub bom: bool,
+}
+
+/// A cue whose text cannot be written losslessly in the target encoding.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnrepresentableCue {
+    pub cue_id: usize,
+    pub index_line: String,
+    /// The offending characters, deduplicated, in order of appearance.
+    pub chars: String,
+}
+
+pub struct EncodedText {
+    pub bytes: Vec<u8>,
+
+    /// Characters that had no representation in the target encoding, in order of appearance.
+    pub unmappable: Vec<char>,
+}
+
+/// Encode `text` as `encoding` (an encoding_rs name/label such as "UTF-8", "windows-1258",
+/// "UTF-16LE"), prefixing a byte order mark when `bom` is set.
+/// BOMs are only written for Unicode encodings.
+pub fn encode_text(text: &str, encoding: &str, bom: bool) -> Result<EncodedText, String> {
+    let enc = resolve_encoding(encoding)?;
+
+    if enc == encoding_rs::UTF_16LE || enc == encoding_rs::UTF_16BE {
+        let big_endian = enc == encoding_rs::UTF_16BE;
+        let mut bytes = Vec::with_capacity(2 + text.len() * 2);
+        let units = bom.then_some(0xFEFFu16).into_iter().chain(text.encode_utf16());
+        for unit in units {
+            bytes.extend_from_slice(&if big_endian { unit.to_be_bytes() } else { unit.to_le_bytes() });
+        }
+        return Ok(EncodedText {
+            bytes,
+            unmappable: Vec::new(),
+        });
+    }
+
+    if enc == encoding_rs::UTF_8 {
+        let mut bytes = Vec::with_capacity(3 + text.len());
+        if bom {
+            bytes.extend_from_slice(&[0xEF, 0xBB, 0xBF]);
+        }
+        bytes.extend_from_slice(text.as_bytes());
+        return Ok(EncodedText {
+            bytes,
+            unmappable: Vec::new(),
+        });
+    }
+
+    let mut encoder = enc.new_encoder();
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut unmappable = Vec::new();
+    let mut rest = text;
+    loop {
+        let needed = encoder
+            .max_buffer_length_from_utf8_without_replacement(rest.len())
+            .unwrap_or(rest.len() * 4 + 16);
+        bytes.reserve(needed);
+
+        let (result, read) = encoder.encode_from_utf8_to_vec_without_replacement(rest, &mut bytes, true);
+        rest = &rest[read..];
+        match result {
+            EncoderResult::InputEmpty => break,
+            EncoderResult::OutputFull => continue,
+            EncoderResult::Unmappable(c) => {
+                unmappable.push(c);
+                bytes.push(b'?');
+            }
+        }
+    }
+
+    Ok(EncodedText { bytes, unmappable })
+}
+
+/// Look up an encoding by label and check that files can be written in it.
+pub fn resolve_encoding(label: &str) -> Result<&'static Encoding, String> {
+    let enc = Encoding::for_label(label.trim().as_bytes())
+        .ok_or_else(|| format!("Unknown output encoding '{label}'."))?;
+
+    let writable = enc == encoding_rs::UTF_16LE || enc == encoding_rs::UTF_16BE || enc.output_encoding() == enc;
+    if !writable {
+        return Err(format!("Writing files as {} is not supported.", enc.name()));
+    }
+    Ok(enc)
+}
+
+/// Check every translated cue against `encoding` (cue by cue, so the UI can point at lines).
+pub fn unrepresentable_cues(
+    doc: &SrtDocument,
+    translated: &HashMap<usize, String>,
+    encoding: &str,
+) -> Result<Vec<UnrepresentableCue>, String> {
+    let mut report = Vec::new();
+
+    for cue in &doc.cues {
+        let Some(text) = translated.get(&cue.id) else {
+            continue;
+        };
+        let encoded = encode_text(text, encoding, false)?;
+        if encoded.unmappable.is_empty() {
+            continue;
+        }
+
+        let mut chars = String::new();
+        for c in encoded.unmappable {
+            if !chars.contains(c) {
+                chars.push(c);
+            }
+        }
+        report.push(UnrepresentableCue {
+            cue_id: cue.id,
+            index_line: cue.index_line.clone(),
+            chars,
+        });
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_utf16le_with_bom() {
+        let out = encode_text("Hi", "UTF-16LE", true).unwrap();
+        assert_eq!(out.bytes, vec![0xFF, 0xFE, b'H', 0, b'i', 0]);
+    }
+
+    #[test]
+    fn test_encode_legacy_reports_unmappable() {
+        let out = encode_text("café 中", "windows-1252", false).unwrap();
+        assert_eq!(out.bytes, vec![b'c', b'a', b'f', 0xE9, b' ', b'?']);
+        assert_eq!(out.unmappable, vec!['中']);
+    }
+
+    #[test]
+    fn test_unrepresentable_cues_report() {
+        let doc = super::super::parse_srt_bytes(b"1\n00:00:01,000 --> 00:00:02,000\nA\n\n2\n00:00:03,000 --> 00:00:04,000\nB\n").unwrap();
+        let mut translated = HashMap::new();
+        translated.insert(0, "Ni hao".to_string());
+        translated.insert(1, "你好 你".to_string());
+
+        let report = unrepresentable_cues(&doc, &translated, "windows-1252").unwrap();
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0].cue_id, 1);
+        assert_eq!(report[0].chars, "你好");
+
+        assert!(unrepresentable_cues(&doc, &translated, "gb18030").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,107 @@
+//! Inline markup when converting between subtitle formats.
+//!
+//! Goals:
+//! - `<i>`, `<b>`, `<u>` work everywhere and are kept
+//! - Tags the target format doesn't know are dropped, not shown as text:
+//!   VTT voice/class/lang/ruby spans and timestamps in SRT; ASS override blocks and `<font>` in VTT
+//! - ASS italic/bold/underline become HTML tags in SRT; `{\anN}` positioning is kept (SRT players read it)
+
+use super::SubtitleFormat;
+
+/// `text`, written for `from`, with its markup made valid for `to`.
+pub fn convert_markup(text: &str, from: SubtitleFormat, to: SubtitleFormat) -> String {
+    if from == to {
+        return text.to_string();
+    }
+
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(pos) = rest.find(['<', '{']) {
+        out.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+        // "a < b" or "I <3 it" are text, not tags
+        let brace = rest[pos..].starts_with('{');
+        let is_tag = brace || after.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '/');
+        let close = if brace { '}' } else { '>' };
+        match after.find(close).filter(|_| is_tag) {
+            Some(len) => {
+                out.push_str(&convert_tag(&rest[pos..pos + len + 2], to));
+                rest = &after[len + 1..];
+            }
+            None => {
+                out.push_str(&rest[pos..pos + 1]);
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// One `<tag>` or `{block}` as `to` should have it (possibly nothing).
+fn convert_tag(tag: &str, to: SubtitleFormat) -> String {
+    let inner = &tag[1..tag.len() - 1];
+    if tag.starts_with('{') {
+        return match to {
+            SubtitleFormat::Srt => ass_overrides_as_html(inner),
+            _ => String::new(),
+        };
+    }
+
+    let name = inner
+        .trim_start_matches('/')
+        .split([' ', '.'])
+        .next()
+        .unwrap_or("")
+        .to_ascii_lowercase();
+    let keep = match name.as_str() {
+        "i" | "b" | "u" => true,
+        "font" => to == SubtitleFormat::Srt,
+        _ => false,
+    };
+    if keep {
+        tag.to_string()
+    } else {
+        String::new()
+    }
+}
+
+/// An ASS override block (without braces) in SRT terms.
+fn ass_overrides_as_html(block: &str) -> String {
+    let mut out = String::new();
+    for code in block.split('\\').map(str::trim).filter(|c| !c.is_empty()) {
+        match code {
+            "i1" => out.push_str("<i>"),
+            "i0" => out.push_str("</i>"),
+            "b1" => out.push_str("<b>"),
+            "b0" => out.push_str("</b>"),
+            "u1" => out.push_str("<u>"),
+            "u0" => out.push_str("</u>"),
+            _ if code.starts_with("an") => out.push_str(&format!("{{\\{}}}", code)),
+            _ => {}
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vtt_spans_are_dropped_in_srt() {
+        let text = "<v Tom><c.loud>Hello</c> <i>there</i></v> <00:00:01.500>now";
+        assert_eq!(convert_markup(text, SubtitleFormat::Vtt, SubtitleFormat::Srt), "Hello <i>there</i> now");
+        assert_eq!(convert_markup("a < b", SubtitleFormat::Vtt, SubtitleFormat::Srt), "a < b");
+    }
+
+    #[test]
+    fn test_srt_and_ass_markup_in_vtt() {
+        let text = "{\\an8}<font color=\"#ffff00\"><b>Sign</b></font>";
+        assert_eq!(convert_markup(text, SubtitleFormat::Srt, SubtitleFormat::Vtt), "<b>Sign</b>");
+        assert_eq!(
+            convert_markup("{\\an8\\i1\\fs20}Hi{\\i0}", SubtitleFormat::Ass, SubtitleFormat::Srt),
+            "{\\an8}<i>Hi</i>"
+        );
+    }
+}
@@ -4,7 +4,25 @@
 //! - Parse common + slightly malformed SRT files
 //! - Preserve timecodes and cue ordering
 //! - Detect encoding (UTF-8/UTF-16 + best-effort fallback)
+//! - Keep encoding, BOM and blank-line layout so untouched files write back byte-for-byte
 //! - Return helpful errors with line numbers and suggestions
+//! - Read/write ASS/SSA and WebVTT containers through the same cue view (see `ass`, `vtt`)
+//! - Check and repair cue timing (see `timing`); shift/stretch/retime the whole file (see `timeline`)
+//! - Check translated cues against reading-speed/line-length limits (see `qa`)
+//! - Re-wrap translated text into balanced lines (see `wrap`)
+//! - Write original + translation together (see `bilingual`)
+//! - Merge/split cues to fit the translation (see `restructure`)
+
+pub mod ass;
+pub mod bilingual;
+pub mod encoding;
+pub mod markup;
+pub mod qa;
+pub mod restructure;
+pub mod timeline;
+pub mod timing;
+pub mod vtt;
+pub mod wrap;
 
 use serde::{Deserialize, Serialize};
 use std::fs;
@@ -42,6 +60,37 @@ impl SrtTime {
             self.hours, self.minutes, self.seconds, self.millis
         )
     }
+
+    /// WebVTT timestamp ("HH:MM:SS.mmm").
+    pub fn format_vtt(&self) -> String {
+        format!(
+            "{:02}:{:02}:{:02}.{:03}",
+            self.hours, self.minutes, self.seconds, self.millis
+        )
+    }
+
+    pub fn to_millis(&self) -> u64 {
+        ((self.hours as u64 * 60 + self.minutes as u64) * 60 + self.seconds as u64) * 1000 + self.millis as u64
+    }
+
+    pub fn from_millis(ms: u64) -> Self {
+        Self {
+            hours: (ms / 3_600_000) as u32,
+            minutes: (ms / 60_000 % 60) as u32,
+            seconds: (ms / 1000 % 60) as u32,
+            millis: (ms % 1000) as u32,
+        }
+    }
+
+    /// This time moved by `delta_ms` (may be negative); clamped at 00:00:00,000.
+    pub fn add(&self, delta_ms: i64) -> Self {
+        Self::from_millis((self.to_millis() as i64).saturating_add(delta_ms).max(0) as u64)
+    }
+
+    /// This time multiplied by `factor`, rounded to the nearest millisecond.
+    pub fn scale(&self, factor: f64) -> Self {
+        Self::from_millis((self.to_millis() as f64 * factor).round().max(0.0) as u64)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -60,14 +109,95 @@ pub struct SrtCue {
 
     /// Subtitle text lines as-is (without trailing newline).
     pub text_lines: Vec<String>,
+
+    /// Source layout needed to write the cue back byte-for-byte.
+    #[serde(default)]
+    pub layout: CueLayout,
+}
+
+impl SrtCue {
+    /// Set new start/end times, rewriting `timing_line` (SRT coordinates after the end time are kept).
+    pub fn set_times(&mut self, start: SrtTime, end: SrtTime) {
+        let settings = self
+            .timing_line
+            .split_once("-->")
+            .and_then(|(_, right)| right.trim().split_once(char::is_whitespace))
+            .map(|(_, settings)| settings.trim().to_string())
+            .unwrap_or_default();
+
+        self.timing_line = format!("{} --> {}", start.format(), end.format());
+        if !settings.is_empty() {
+            self.timing_line.push(' ');
+            self.timing_line.push_str(&settings);
+        }
+        self.start = start;
+        self.end = end;
+    }
+}
+
+/// How a cue was laid out in the source file.
+/// Defaults describe a canonical cue: index line present, one blank line after the text.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CueLayout {
+    /// Index line as read, when it carried extra whitespace.
+    pub raw_index_line: Option<String>,
+
+    /// The source had no index line (`index_line` was recovered).
+    pub index_missing: bool,
+
+    /// Timing line as read, when it carried extra whitespace.
+    pub raw_timing_line: Option<String>,
+
+    /// Blank (or whitespace-only) lines after the text, verbatim.
+    /// None means a single empty line.
+    pub separator: Option<Vec<String>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SrtDocument {
     pub newline: NewlineStyle,
+
+    /// Source encoding (encoding_rs name, e.g. "UTF-8", "windows-1258", "UTF-16LE").
+    #[serde(default = "default_encoding_name")]
+    pub encoding: String,
+
+    /// Whether the source started with a byte order mark.
+    #[serde(default)]
+    pub bom: bool,
+
+    /// Blank lines before the first cue, verbatim.
+    #[serde(default)]
+    pub leading_lines: Vec<String>,
+
+    /// Whether the last line of the source ended with a newline.
+    #[serde(default = "default_final_newline")]
+    pub final_newline: bool,
+
     pub cues: Vec<SrtCue>,
 }
 
+fn default_encoding_name() -> String {
+    encoding_rs::UTF_8.name().to_string()
+}
+
+fn default_final_newline() -> bool {
+    true
+}
+
+impl SrtDocument {
+    /// A UTF-8 document with canonical layout (used by the ASS/VTT cue views).
+    pub fn new(newline: NewlineStyle, cues: Vec<SrtCue>) -> Self {
+        Self {
+            newline,
+            encoding: default_encoding_name(),
+            bom: false,
+            leading_lines: Vec::new(),
+            final_newline: true,
+            cues,
+        }
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum SrtError {
     #[error("Failed to read file: {0}")]
@@ -80,16 +210,176 @@ pub enum SrtError {
     Format { line: usize, message: String },
 }
 
-/// Read + decode bytes from disk and parse into SrtDocument.
-pub fn parse_srt_file(path: &Path) -> Result<SrtDocument, SrtError> {
+/// Subtitle container a file was imported from.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum SubtitleFormat {
+    Srt,
+    Ass,
+    Vtt,
+}
+
+impl SubtitleFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            SubtitleFormat::Srt => "srt",
+            SubtitleFormat::Ass => "ass",
+            SubtitleFormat::Vtt => "vtt",
+        }
+    }
+}
+
+/// Format-specific data kept next to the `SrtDocument` cue view,
+/// so the original container can be written back after translation.
+#[derive(Debug, Clone)]
+pub enum SubtitleSource {
+    Srt,
+    Ass(ass::AssDocument),
+    Vtt(vtt::VttDocument),
+}
+
+const ASS_NEEDS_ASS_SOURCE: &str = "ASS output is only available for files imported as .ass/.ssa.";
+
+impl SubtitleSource {
+    pub fn format(&self) -> SubtitleFormat {
+        match self {
+            SubtitleSource::Srt => SubtitleFormat::Srt,
+            SubtitleSource::Ass(_) => SubtitleFormat::Ass,
+            SubtitleSource::Vtt(_) => SubtitleFormat::Vtt,
+        }
+    }
+
+    /// Take retimed cues back into the container, which ASS/VTT output is written from.
+    pub fn set_times(&mut self, doc: &SrtDocument) {
+        match self {
+            SubtitleSource::Srt => {}
+            SubtitleSource::Ass(ass_doc) => ass_doc.set_times(doc),
+            SubtitleSource::Vtt(vtt_doc) => vtt_doc.set_times(doc),
+        }
+    }
+
+    /// Whether this source can be written as `format` (ASS needs an ASS source to write into).
+    pub fn check_output(&self, format: SubtitleFormat) -> Result<(), String> {
+        match (self, format) {
+            (SubtitleSource::Ass(_), _) | (_, SubtitleFormat::Srt | SubtitleFormat::Vtt) => Ok(()),
+            (_, SubtitleFormat::Ass) => Err(ASS_NEEDS_ASS_SOURCE.into()),
+        }
+    }
+}
+
+/// A parsed subtitle file.
+pub struct ParsedSubtitle {
+    pub document: SrtDocument,
+    pub source: SubtitleSource,
+
+    /// Repairs made by the lenient SRT parser (always empty in strict mode).
+    pub diagnostics: Vec<ParseDiagnostic>,
+}
+
+/// Read any supported subtitle file (.srt, .ass, .ssa, .vtt).
+/// The format is picked from the extension, falling back to content sniffing.
+/// `lenient` selects the repairing SRT parser (see `parse_srt_str_lenient`).
+pub fn parse_subtitle_file(path: &Path, lenient: bool) -> Result<ParsedSubtitle, SrtError> {
     let bytes = fs::read(path)?;
-    parse_srt_bytes(&bytes)
+    parse_subtitle_bytes(path, &bytes, lenient)
+}
+
+/// Parse a subtitle file's `bytes`, already read from `path` (used only to pick the format).
+pub fn parse_subtitle_bytes(path: &Path, bytes: &[u8], lenient: bool) -> Result<ParsedSubtitle, SrtError> {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+        .unwrap_or_default();
+    let head = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]).unwrap_or(bytes);
+    let sniff = &head[head.iter().position(|b| !b.is_ascii_whitespace()).unwrap_or(head.len())..];
+    let is_ass = ext == "ass" || ext == "ssa" || (ext != "srt" && sniff.starts_with(b"[Script Info]"));
+    let is_vtt = ext == "vtt" || (ext != "srt" && sniff.starts_with(b"WEBVTT"));
+
+    let (document, source, diagnostics) = if is_ass {
+        let decoded = decode_best_effort(bytes)?;
+        let ass_doc = ass::parse_ass_str(&decoded.text, decoded.newline)?;
+        (decoded.tag(ass_doc.to_srt_document()), SubtitleSource::Ass(ass_doc), Vec::new())
+    } else if is_vtt {
+        let decoded = decode_best_effort(bytes)?;
+        let vtt_doc = vtt::parse_vtt_str(&decoded.text, decoded.newline)?;
+        (decoded.tag(vtt_doc.to_srt_document()), SubtitleSource::Vtt(vtt_doc), Vec::new())
+    } else if lenient {
+        let (doc, diagnostics) = parse_srt_bytes_lenient(bytes)?;
+        (doc, SubtitleSource::Srt, diagnostics)
+    } else {
+        (parse_srt_bytes(bytes)?, SubtitleSource::Srt, Vec::new())
+    };
+
+    Ok(ParsedSubtitle {
+        document,
+        source,
+        diagnostics,
+    })
+}
+
+/// Write translated output as `format`.
+/// Writing the source's own format keeps container details (ASS styles, VTT settings);
+/// other formats are converted from the cue view, with markup the target doesn't know converted or dropped.
+/// SRT↔VTT (and ASS→SRT/VTT) are supported.
+pub fn write_subtitle(
+    doc: &SrtDocument,
+    source: &SubtitleSource,
+    translated: &std::collections::HashMap<usize, String>,
+    format: SubtitleFormat,
+) -> Result<String, String> {
+    let converted = || -> std::collections::HashMap<usize, String> {
+        translated
+            .iter()
+            .map(|(&id, text)| (id, markup::convert_markup(text, source.format(), format)))
+            .collect()
+    };
+    match (source, format) {
+        (SubtitleSource::Ass(ass_doc), SubtitleFormat::Ass) => ass::write_ass(ass_doc, translated),
+        (SubtitleSource::Vtt(vtt_doc), SubtitleFormat::Vtt) => vtt::write_vtt(vtt_doc, translated),
+        (_, SubtitleFormat::Srt) => write_srt(doc, &converted()),
+        (_, SubtitleFormat::Vtt) => vtt::write_cues_as_vtt(doc, &converted()),
+        (_, SubtitleFormat::Ass) => Err(ASS_NEEDS_ASS_SOURCE.into()),
+    }
 }
 
 /// Parse an SRT file from raw bytes.
 pub fn parse_srt_bytes(bytes: &[u8]) -> Result<SrtDocument, SrtError> {
-    let (text, newline) = decode_best_effort(bytes)?;
-    parse_srt_str(&text, newline)
+    let decoded = decode_best_effort(bytes)?;
+    let doc = parse_srt_str(&decoded.text, decoded.newline)?;
+    Ok(decoded.tag(doc))
+}
+
+/// Parse an SRT file from raw bytes, repairing what can be repaired.
+pub fn parse_srt_bytes_lenient(bytes: &[u8]) -> Result<(SrtDocument, Vec<ParseDiagnostic>), SrtError> {
+    let decoded = decode_best_effort(bytes)?;
+    let (doc, diagnostics) = parse_srt_str_lenient(&decoded.text, decoded.newline)?;
+    Ok((decoded.tag(doc), diagnostics))
+}
+
+/// Decoded source text plus what is needed to encode it back.
+pub(crate) struct Decoded {
+    pub(crate) text: String,
+    newline: NewlineStyle,
+    encoding: &'static encoding_rs::Encoding,
+    bom: bool,
+}
+
+impl Decoded {
+    fn new(s: &str, encoding: &'static encoding_rs::Encoding, bom: bool) -> Self {
+        Self {
+            text: normalize_bom(s),
+            newline: detect_newline_style(s),
+            encoding,
+            bom,
+        }
+    }
+
+    /// Record the source encoding on a document parsed from this text.
+    fn tag(&self, mut doc: SrtDocument) -> SrtDocument {
+        doc.encoding = self.encoding.name().to_string();
+        doc.bom = self.bom;
+        doc
+    }
 }
 
 /// Best-effort decode:
@@ -97,13 +387,13 @@ pub fn parse_srt_bytes(bytes: &[u8]) -> Result<SrtDocument, SrtError> {
 /// - UTF-16 LE/BE BOM
 /// - UTF-8 (strict)
 /// - fallback: chardetng guess + encoding_rs decode
-fn decode_best_effort(bytes: &[u8]) -> Result<(String, NewlineStyle), SrtError> {
+pub(crate) fn decode_best_effort(bytes: &[u8]) -> Result<Decoded, SrtError> {
     // Fast BOM checks
     if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
         let s = std::str::from_utf8(&bytes[3..]).map_err(|_| SrtError::Encoding {
             hint: "The file looks like UTF-8 with BOM, but it contains invalid UTF-8 bytes. Try re-saving as UTF-8.".into(),
         })?;
-        return Ok((normalize_bom(s), detect_newline_style(s)));
+        return Ok(Decoded::new(s, encoding_rs::UTF_8, true));
     }
 
     if bytes.starts_with(&[0xFF, 0xFE]) {
@@ -114,8 +404,7 @@ fn decode_best_effort(bytes: &[u8]) -> Result<(String, NewlineStyle), SrtError>
                 hint: "The file looks like UTF-16 (LE) but contains invalid sequences. Try exporting subtitles again as UTF-8.".into(),
             });
         }
-        let s = cow.into_owned();
-        return Ok((normalize_bom(&s), detect_newline_style(&s)));
+        return Ok(Decoded::new(&cow, encoding_rs::UTF_16LE, true));
     }
 
     if bytes.starts_with(&[0xFE, 0xFF]) {
@@ -126,13 +415,12 @@ fn decode_best_effort(bytes: &[u8]) -> Result<(String, NewlineStyle), SrtError>
                 hint: "The file looks like UTF-16 (BE) but contains invalid sequences. Try exporting subtitles again as UTF-8.".into(),
             });
         }
-        let s = cow.into_owned();
-        return Ok((normalize_bom(&s), detect_newline_style(&s)));
+        return Ok(Decoded::new(&cow, encoding_rs::UTF_16BE, true));
     }
 
     // Try UTF-8 strict
     if let Ok(s) = std::str::from_utf8(bytes) {
-        return Ok((normalize_bom(s), detect_newline_style(s)));
+        return Ok(Decoded::new(s, encoding_rs::UTF_8, false));
     }
 
     // Fallback: detect with chardetng + decode with encoding_rs
@@ -148,8 +436,7 @@ fn decode_best_effort(bytes: &[u8]) -> Result<(String, NewlineStyle), SrtError>
             ),
         });
     }
-    let s = cow.into_owned();
-    Ok((normalize_bom(&s), detect_newline_style(&s)))
+    Ok(Decoded::new(&cow, encoding, false))
 }
 
 fn normalize_bom(s: &str) -> String {
@@ -165,34 +452,122 @@ fn detect_newline_style(s: &str) -> NewlineStyle {
     }
 }
 
+/// Something the lenient parser repaired (or had to drop) while reading a file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParseDiagnostic {
+    /// 1-based line number in the source file.
+    pub line: usize,
+
+    /// What was wrong.
+    pub message: String,
+
+    /// What the parser did about it.
+    pub fix: String,
+}
+
 /// Parse SRT text into document.
 /// `newline` is what we detected; output writer will keep it.
 pub fn parse_srt_str(text: &str, newline: NewlineStyle) -> Result<SrtDocument, SrtError> {
+    parse_srt_lines(text, newline, None)
+}
+
+/// Lenient variant of `parse_srt_str`: repairs common damage instead of failing
+/// and reports every repair. Only a file without a single readable cue is an error.
+///
+/// Repairs:
+/// - stray text lines (no number/timing) are attached to the previous cue
+/// - a cue running into the next one without a blank line is split there
+/// - "00:00:01:000" (colon before millis) and "00:00:01" (no millis) timings
+/// - cues without a number line are numbered
+/// - cues whose timing can't be read at all are dropped
+pub fn parse_srt_str_lenient(text: &str, newline: NewlineStyle) -> Result<(SrtDocument, Vec<ParseDiagnostic>), SrtError> {
+    let mut diagnostics = Vec::new();
+    let doc = parse_srt_lines(text, newline, Some(&mut diagnostics))?;
+    Ok((doc, diagnostics))
+}
+
+/// Shared parser. `lenient` is None in strict mode; in lenient mode repairs are pushed to it.
+fn parse_srt_lines(
+    text: &str,
+    newline: NewlineStyle,
+    mut lenient: Option<&mut Vec<ParseDiagnostic>>,
+) -> Result<SrtDocument, SrtError> {
     // Normalize newlines for parsing.
     let normalized = text.replace("\r\n", "\n").replace('\r', "\n");
-    let lines: Vec<&str> = normalized.split('\n').collect();
+    let final_newline = normalized.ends_with('\n');
+    let mut lines: Vec<&str> = normalized.split('\n').collect();
+    if final_newline {
+        // split() yields an empty item after the last terminator
+        lines.pop();
+    }
 
     let mut cues: Vec<SrtCue> = Vec::new();
     let mut i: usize = 0;
     let mut next_recovered_index: usize = 1;
 
+    // Blank lines before the first cue
+    let mut leading_lines: Vec<String> = Vec::new();
+    while i < lines.len() && lines[i].trim().is_empty() {
+        leading_lines.push(lines[i].to_string());
+        i += 1;
+    }
+
     while i < lines.len() {
-        // Skip blank lines
-        while i < lines.len() && lines[i].trim().is_empty() {
-            i += 1;
-        }
-        if i >= lines.len() {
-            break;
+        if let Some(diags) = lenient.as_deref_mut() {
+            if !starts_cue(&lines, i) {
+                // Stray text: attach it to the previous cue, or drop it before the first cue
+                let first_line = i;
+                let mut stray: Vec<String> = Vec::new();
+                while i < lines.len() && !lines[i].trim().is_empty() && !starts_cue(&lines, i) {
+                    stray.push(lines[i].to_string());
+                    i += 1;
+                }
+                let separator = take_blank_lines(&lines, &mut i);
+
+                match cues.last_mut() {
+                    Some(prev) => {
+                        diags.push(ParseDiagnostic {
+                            line: first_line + 1,
+                            message: "Text line without a cue number or timing line.".into(),
+                            fix: format!("Attached {} line(s) to cue {}.", stray.len(), prev.index_line),
+                        });
+                        prev.text_lines.extend(stray);
+                        prev.layout.separator = Some(separator);
+                    }
+                    None => {
+                        diags.push(ParseDiagnostic {
+                            line: first_line + 1,
+                            message: "Text before the first cue.".into(),
+                            fix: format!("Dropped {} line(s).", stray.len()),
+                        });
+                        leading_lines.extend(separator);
+                    }
+                }
+                continue;
+            }
         }
 
         // Attempt to parse index line, but allow recovery if missing.
         let index_line = lines[i].trim().to_string();
+        let mut layout = CueLayout::default();
 
         let (index_line_used, timing_line_idx) = if is_all_digits(&index_line) {
+            if lines[i] != index_line {
+                layout.raw_index_line = Some(lines[i].to_string());
+            }
             (index_line, i + 1)
         } else if looks_like_timing_line(lines[i]) {
             // Missing index line, recover
             let recovered = next_recovered_index.to_string();
+            match lenient.as_deref_mut() {
+                Some(diags) => diags.push(ParseDiagnostic {
+                    line: i + 1,
+                    message: "Cue has no number line.".into(),
+                    fix: format!("Numbered it {recovered}."),
+                }),
+                // Strict mode keeps the file as it was
+                None => layout.index_missing = true,
+            }
             (recovered, i)
         } else {
             return Err(SrtError::Format {
@@ -209,40 +584,90 @@ pub fn parse_srt_str(text: &str, newline: NewlineStyle) -> Result<SrtDocument, S
             });
         }
 
-        let timing_raw = lines[timing_line_idx].trim();
-        if !looks_like_timing_line(timing_raw) {
+        let mut timing_line = lines[timing_line_idx].trim().to_string();
+        if !looks_like_timing_line(&timing_line) {
             return Err(SrtError::Format {
                 line: timing_line_idx + 1,
                 message: "Expected a timing line like '00:00:01,000 --> 00:00:03,000'.".into(),
             });
         }
 
-        let (start, end) = parse_timing_line(timing_raw).map_err(|msg| SrtError::Format {
-            line: timing_line_idx + 1,
-            message: msg,
-        })?;
+        let parsed = match (parse_timing_line(&timing_line), lenient.as_deref_mut()) {
+            (Ok(times), _) => Some(times),
+            (Err(msg), None) => {
+                return Err(SrtError::Format {
+                    line: timing_line_idx + 1,
+                    message: msg,
+                })
+            }
+            (Err(msg), Some(diags)) => match repair_timing_line(&timing_line) {
+                Ok((start, end, repaired)) => {
+                    diags.push(ParseDiagnostic {
+                        line: timing_line_idx + 1,
+                        message: msg,
+                        fix: format!("Read it as '{repaired}'."),
+                    });
+                    timing_line = repaired;
+                    Some((start, end))
+                }
+                Err(_) => {
+                    diags.push(ParseDiagnostic {
+                        line: timing_line_idx + 1,
+                        message: msg,
+                        fix: "Dropped this cue.".into(),
+                    });
+                    None
+                }
+            },
+        };
+        if lines[timing_line_idx] != timing_line {
+            layout.raw_timing_line = Some(lines[timing_line_idx].to_string());
+        }
 
         // Read text lines until blank line or EOF
+        // (lenient: or until the next cue starts without a blank line in between)
         let mut text_lines: Vec<String> = Vec::new();
         let mut j = timing_line_idx + 1;
+        let mut ran_into_next = false;
         while j < lines.len() && !lines[j].trim().is_empty() {
+            if let Some(diags) = lenient.as_deref_mut() {
+                if starts_cue(&lines, j) {
+                    diags.push(ParseDiagnostic {
+                        line: j + 1,
+                        message: "Cue starts without a blank line after the previous cue.".into(),
+                        fix: "Inserted the missing blank line.".into(),
+                    });
+                    ran_into_next = true;
+                    break;
+                }
+            }
             text_lines.push(lines[j].to_string());
             j += 1;
         }
 
+        // Keep the blank separator run verbatim
+        let separator = take_blank_lines(&lines, &mut j);
+        i = j;
+
+        let Some((start, end)) = parsed else {
+            continue;
+        };
+        // A cue that ran into the next one gets the canonical single blank line
+        layout.separator = (!ran_into_next).then_some(separator);
+
         // Build cue
         let cue_id = cues.len();
         cues.push(SrtCue {
             id: cue_id,
             index_line: index_line_used,
-            timing_line: timing_raw.to_string(),
+            timing_line,
             start,
             end,
             text_lines,
+            layout,
         });
 
         next_recovered_index += 1;
-        i = j + 1; // move past blank separator
     }
 
     if cues.is_empty() {
@@ -252,7 +677,27 @@ pub fn parse_srt_str(text: &str, newline: NewlineStyle) -> Result<SrtDocument, S
         });
     }
 
-    Ok(SrtDocument { newline, cues })
+    Ok(SrtDocument {
+        leading_lines,
+        final_newline,
+        ..SrtDocument::new(newline, cues)
+    })
+}
+
+/// Advance past a run of blank lines, returning them verbatim.
+fn take_blank_lines(lines: &[&str], i: &mut usize) -> Vec<String> {
+    let mut blank = Vec::new();
+    while *i < lines.len() && lines[*i].trim().is_empty() {
+        blank.push(lines[*i].to_string());
+        *i += 1;
+    }
+    blank
+}
+
+/// A cue starts at `i` with a number line followed by a timing line, or with a bare timing line.
+fn starts_cue(lines: &[&str], i: usize) -> bool {
+    looks_like_timing_line(lines[i])
+        || (is_all_digits(lines[i].trim()) && lines.get(i + 1).is_some_and(|l| looks_like_timing_line(l)))
 }
 
 fn is_all_digits(s: &str) -> bool {
@@ -282,6 +727,36 @@ fn parse_timing_line(line: &str) -> Result<(SrtTime, SrtTime), String> {
     Ok((start, end))
 }
 
+/// Lenient timing line parse; returns the times and the canonical timing line.
+fn repair_timing_line(line: &str) -> Result<(SrtTime, SrtTime, String), String> {
+    let (left, right) = line
+        .split_once("-->")
+        .ok_or_else(|| "Timing line is missing the '-->' separator.".to_string())?;
+    let right = right.trim();
+    let (end_token, settings) = right.split_once(char::is_whitespace).unwrap_or((right, ""));
+
+    let start = parse_time_lenient(left)?;
+    let end = parse_time_lenient(end_token)?;
+
+    let mut canonical = format!("{} --> {}", start.format(), end.format());
+    if !settings.trim().is_empty() {
+        canonical.push(' ');
+        canonical.push_str(settings.trim());
+    }
+    Ok((start, end, canonical))
+}
+
+/// Like `parse_time`, but also reads "HH:MM:SS:mmm" and "HH:MM:SS".
+fn parse_time_lenient(s: &str) -> Result<SrtTime, String> {
+    let s = s.trim();
+    let parts: Vec<&str> = s.split(':').collect();
+    match parts.as_slice() {
+        [h, m, sec, ms] => parse_time(&format!("{h}:{m}:{sec},{ms}")),
+        [h, m, sec] if !sec.contains([',', '.']) => parse_time(&format!("{h}:{m}:{sec},000")),
+        _ => parse_time(s),
+    }
+}
+
 fn parse_time(s: &str) -> Result<SrtTime, String> {
     // Accept "HH:MM:SS,mmm" or "HH:MM:SS.mmm"
     let s = s.trim();
@@ -330,36 +805,49 @@ pub fn validate_translation_count(doc: &SrtDocument, translated: &std::collectio
     Ok(())
 }
 
-/// Write translated SRT content, preserving timing lines and newline style.
+/// Write translated SRT content, preserving timing lines, newline style and blank-line layout.
 /// `translated` maps cue_id -> translated text (may contain '\n' to represent multi-line cues).
+/// With the source text as the "translation", the output equals the decoded input.
 pub fn write_srt(doc: &SrtDocument, translated: &std::collections::HashMap<usize, String>) -> Result<String, String> {
     validate_translation_count(doc, translated)?;
 
-    let nl = doc.newline.as_str();
-    let mut out = String::new();
+    let mut lines: Vec<&str> = doc.leading_lines.iter().map(String::as_str).collect();
 
     for cue in &doc.cues {
-        out.push_str(&cue.index_line);
-        out.push_str(nl);
-        out.push_str(&cue.timing_line);
-        out.push_str(nl);
+        if !cue.layout.index_missing {
+            lines.push(raw_or_canonical(&cue.layout.raw_index_line, &cue.index_line));
+        }
+        lines.push(raw_or_canonical(&cue.layout.raw_timing_line, &cue.timing_line));
 
         let text = translated
             .get(&cue.id)
             .ok_or_else(|| format!("Missing translation for cue id {}", cue.id))?;
 
         // Preserve multi-line by splitting on '\n'
-        for line in text.split('\n') {
-            out.push_str(line);
-            out.push_str(nl);
+        lines.extend(text.split('\n'));
+
+        match &cue.layout.separator {
+            Some(separator) => lines.extend(separator.iter().map(String::as_str)),
+            None => lines.push(""),
         }
+    }
 
+    let nl = doc.newline.as_str();
+    let mut out = lines.join(nl);
+    if doc.final_newline {
         out.push_str(nl);
     }
-
     Ok(out)
 }
 
+/// The raw source line is only reused while it still matches the (possibly edited) canonical line.
+fn raw_or_canonical<'a>(raw: &'a Option<String>, canonical: &'a str) -> &'a str {
+    match raw {
+        Some(r) if r.trim() == canonical => r,
+        _ => canonical,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -398,4 +886,147 @@ mod tests {
         assert!(output.contains("00:00:01,000 --> 00:00:03,000"));
         assert!(output.contains("Translated text"));
     }
+
+    /// parse -> write (source text as translation) -> encode must give back the input bytes.
+    fn assert_round_trip(name: &str, bytes: &[u8]) {
+        let doc = parse_srt_bytes(bytes).unwrap_or_else(|e| panic!("{name}: {e}"));
+        let identity: std::collections::HashMap<usize, String> =
+            doc.cues.iter().map(|c| (c.id, c.text_lines.join("\n"))).collect();
+
+        let text = write_srt(&doc, &identity).unwrap();
+        let out = encoding::encode_text(&text, &doc.encoding, doc.bom).unwrap();
+
+        assert!(out.bytes == bytes, "{name}: round trip changed the file ({})", doc.encoding);
+    }
+
+    /// Layout variants of a real file: newline style, BOM, blank-line runs,
+    /// trailing whitespace, missing final newline, UTF-16.
+    fn layout_variants(bytes: &[u8]) -> Vec<(&'static str, Vec<u8>)> {
+        let lf: Vec<u8> = bytes.iter().copied().filter(|&b| b != b'\r').collect();
+        let replace = |from: &[u8], to: &[u8]| -> Vec<u8> {
+            let mut out = Vec::new();
+            let mut i = 0;
+            while i < lf.len() {
+                if lf[i..].starts_with(from) {
+                    out.extend_from_slice(to);
+                    i += from.len();
+                } else {
+                    out.push(lf[i]);
+                    i += 1;
+                }
+            }
+            out
+        };
+
+        let mut variants = vec![
+            ("original", bytes.to_vec()),
+            ("lf", lf.clone()),
+            ("crlf", replace(b"\n", b"\r\n")),
+            ("blank runs", replace(b"\n\n", b"\n \n\t\n\n")),
+            ("trailing whitespace", replace(b"\n", b"  \n")),
+            ("leading blank lines", [b"\n\n".as_slice(), &lf].concat()),
+            ("no final newline", lf.strip_suffix(b"\n").unwrap_or(&lf).to_vec()),
+        ];
+
+        if let Ok(text) = std::str::from_utf8(&lf) {
+            variants.push(("utf-8 bom", [[0xEF, 0xBB, 0xBF].as_slice(), &lf].concat()));
+            variants.push(("utf-16le", encoding::encode_text(text, "UTF-16LE", true).unwrap().bytes));
+            variants.push(("utf-16be", encoding::encode_text(text, "UTF-16BE", true).unwrap().bytes));
+        }
+        variants
+    }
+
+    #[test]
+    fn test_round_trip_is_identity_on_corpus() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../test-files");
+        let mut checked = 0;
+
+        for entry in fs::read_dir(&dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().and_then(|e| e.to_str()) != Some("srt") {
+                continue;
+            }
+            let bytes = fs::read(&path).unwrap();
+            if parse_srt_bytes(&bytes).is_err() {
+                // malformed.srt is for the error paths
+                continue;
+            }
+
+            for (variant, variant_bytes) in layout_variants(&bytes) {
+                assert_round_trip(&format!("{} ({variant})", path.display()), &variant_bytes);
+                checked += 1;
+            }
+        }
+
+        assert!(checked > 0, "no corpus files found in {}", dir.display());
+    }
+
+    #[test]
+    fn test_detects_legacy_encoding() {
+        let bytes = fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("../test-files/vietnamese-cp1258.srt")).unwrap();
+        let doc = parse_srt_bytes(&bytes).unwrap();
+
+        assert_eq!(doc.encoding, "windows-1258");
+        assert!(!doc.bom);
+    }
+
+    #[test]
+    fn test_lenient_repairs_damaged_file() {
+        let srt = "1\n00:00:01:000 --> 00:00:03,000\nColon millis\n2\n00:00:04,000 --> 00:00:06,000\nNo blank line above\n\nStray line\n\n00:00:07 --> 00:00:08\nNo number\n\n4\n00:00:09,000 --> garbage\nDropped\n\n5\n00:00:10,000 --> 00:00:11,000\nLast\n";
+
+        assert!(parse_srt_str(srt, NewlineStyle::Lf).is_err());
+        let (doc, diagnostics) = parse_srt_str_lenient(srt, NewlineStyle::Lf).unwrap();
+
+        assert_eq!(doc.cues.len(), 4);
+        assert_eq!(doc.cues[0].timing_line, "00:00:01,000 --> 00:00:03,000");
+        assert_eq!(doc.cues[0].text_lines, vec!["Colon millis"]);
+        assert_eq!(doc.cues[1].text_lines, vec!["No blank line above", "Stray line"]);
+        assert_eq!(doc.cues[2].index_line, "3");
+        assert_eq!(doc.cues[2].start.seconds, 7);
+        assert_eq!(doc.cues[3].text_lines, vec!["Last"]);
+
+        let lines: Vec<usize> = diagnostics.iter().map(|d| d.line).collect();
+        assert_eq!(lines, vec![2, 4, 8, 10, 10, 14]);
+    }
+
+    #[test]
+    fn test_lenient_writes_repaired_cues_canonically() {
+        let srt = "1\n00:00:01,000 --> 00:00:02,000\nA\n2\n00:00:03,000 --> 00:00:04,000\nB\n";
+        let (doc, diagnostics) = parse_srt_str_lenient(srt, NewlineStyle::Lf).unwrap();
+        let identity: std::collections::HashMap<usize, String> =
+            doc.cues.iter().map(|c| (c.id, c.text_lines.join("\n"))).collect();
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            write_srt(&doc, &identity).unwrap(),
+            "1\n00:00:01,000 --> 00:00:02,000\nA\n\n2\n00:00:03,000 --> 00:00:04,000\nB\n"
+        );
+    }
+
+    #[test]
+    fn test_lenient_matches_strict_on_clean_files() {
+        let bytes = fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("../test-files/small.srt")).unwrap();
+        let strict = parse_srt_bytes(&bytes).unwrap();
+        let (lenient, diagnostics) = parse_srt_bytes_lenient(&bytes).unwrap();
+
+        assert!(diagnostics.is_empty());
+        assert_eq!(serde_json::to_string(&strict).unwrap(), serde_json::to_string(&lenient).unwrap());
+    }
+
+    #[test]
+    fn test_lenient_reads_malformed_sample() {
+        let bytes = fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("../test-files/malformed.srt")).unwrap();
+        assert!(parse_srt_bytes(&bytes).is_err());
+
+        let (doc, diagnostics) = parse_srt_bytes_lenient(&bytes).unwrap();
+        assert_eq!(doc.cues.len(), 5);
+        assert_eq!(doc.cues[3].text_lines, vec!["Extra blank lines above"]);
+        assert!(!diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_ass_output_needs_ass_source() {
+        assert!(SubtitleSource::Srt.check_output(SubtitleFormat::Ass).is_err());
+        assert!(SubtitleSource::Srt.check_output(SubtitleFormat::Vtt).is_ok());
+    }
 }
@@ -0,0 +1,184 @@
+//! Readability QA for translated cues.
+//!
+//! Goals:
+//! - Measure reading speed (characters per second), line length and line count per cue
+//! - Flag cues that break a configurable profile (Netflix-style 42 chars / 2 lines / 17 CPS, ...)
+//! - Ignore formatting tags (`<i>`, `{\an8}`) when counting characters
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::SrtDocument;
+
+/// Limits a translated cue must stay within.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QaProfile {
+    pub name: String,
+    pub max_chars_per_line: usize,
+    pub max_lines: usize,
+
+    /// Characters per second, counted without tags and line breaks.
+    pub max_cps: f32,
+}
+
+impl QaProfile {
+    /// Netflix Timed Text Style Guide, Latin-script languages (English, Vietnamese, ...).
+    pub fn netflix() -> Self {
+        Self {
+            name: "Netflix".into(),
+            max_chars_per_line: 42,
+            max_lines: 2,
+            max_cps: 17.0,
+        }
+    }
+
+    /// Netflix Timed Text Style Guide, Chinese/Japanese.
+    pub fn netflix_cjk() -> Self {
+        Self {
+            name: "Netflix (CJK)".into(),
+            max_chars_per_line: 16,
+            max_lines: 2,
+            max_cps: 9.0,
+        }
+    }
+
+    /// BBC subtitle guidelines (37 chars, ~180 words per minute).
+    pub fn bbc() -> Self {
+        Self {
+            name: "BBC".into(),
+            max_chars_per_line: 37,
+            max_lines: 2,
+            max_cps: 15.0,
+        }
+    }
+}
+
+impl Default for QaProfile {
+    fn default() -> Self {
+        Self::netflix()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum QaViolation {
+    ReadingSpeed,
+    LineLength,
+    LineCount,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CueQa {
+    pub cue_id: usize,
+    pub index_line: String,
+    pub cps: f32,
+    pub max_line_chars: usize,
+    pub line_count: usize,
+
+    /// Empty when the cue is within the profile.
+    pub violations: Vec<QaViolation>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QaReport {
+    pub profile: QaProfile,
+
+    /// One entry per translated cue, in cue order.
+    pub cues: Vec<CueQa>,
+
+    /// Number of cues with at least one violation.
+    pub flagged: usize,
+}
+
+/// Check every translated cue against `profile`.
+pub fn check_translation(doc: &SrtDocument, translated: &HashMap<usize, String>, profile: &QaProfile) -> QaReport {
+    let mut cues = Vec::new();
+
+    for cue in &doc.cues {
+        let Some(text) = translated.get(&cue.id) else {
+            continue;
+        };
+
+        let lines: Vec<String> = text.split('\n').map(visible_text).collect();
+        let line_count = lines.len();
+        let max_line_chars = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+        let total_chars: usize = lines.iter().map(|l| l.chars().count()).sum();
+
+        let duration_ms = cue.end.to_millis().saturating_sub(cue.start.to_millis()).max(1);
+        let cps = total_chars as f32 * 1000.0 / duration_ms as f32;
+
+        let mut violations = Vec::new();
+        if cps > profile.max_cps {
+            violations.push(QaViolation::ReadingSpeed);
+        }
+        if max_line_chars > profile.max_chars_per_line {
+            violations.push(QaViolation::LineLength);
+        }
+        if line_count > profile.max_lines {
+            violations.push(QaViolation::LineCount);
+        }
+
+        cues.push(CueQa {
+            cue_id: cue.id,
+            index_line: cue.index_line.clone(),
+            cps,
+            max_line_chars,
+            line_count,
+            violations,
+        });
+    }
+
+    let flagged = cues.iter().filter(|c| !c.violations.is_empty()).count();
+    QaReport {
+        profile: profile.clone(),
+        cues,
+        flagged,
+    }
+}
+
+/// Text as shown on screen: `<...>` and `{...}` tags removed, surrounding whitespace trimmed.
+pub fn visible_text(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut closing: Option<char> = None;
+
+    for c in line.chars() {
+        match closing {
+            Some(end) if c == end => closing = None,
+            Some(_) => {}
+            None if c == '<' => closing = Some('>'),
+            None if c == '{' => closing = Some('}'),
+            None => out.push(c),
+        }
+    }
+
+    out.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::srt::parse_srt_bytes;
+
+    #[test]
+    fn test_visible_text_strips_tags() {
+        assert_eq!(visible_text("{\\an8}<i>Hello</i> there"), "Hello there");
+    }
+
+    #[test]
+    fn test_flags_fast_and_long_cues() {
+        let doc = parse_srt_bytes(b"1\n00:00:01,000 --> 00:00:03,000\nA\n\n2\n00:00:04,000 --> 00:00:05,000\nB\n").unwrap();
+        let mut translated = HashMap::new();
+        translated.insert(0, "<i>Short and sweet.</i>".to_string());
+        translated.insert(1, "This translated line is far too long for a single second.\nand\nthree".to_string());
+
+        let report = check_translation(&doc, &translated, &QaProfile::netflix());
+
+        assert_eq!(report.flagged, 1);
+        assert!(report.cues[0].violations.is_empty());
+        assert_eq!(report.cues[0].max_line_chars, 16);
+        assert_eq!(report.cues[0].cps, 8.0);
+        assert_eq!(
+            report.cues[1].violations,
+            vec![QaViolation::ReadingSpeed, QaViolation::LineLength, QaViolation::LineCount]
+        );
+    }
+}
@@ -0,0 +1,317 @@
+//! Merging and splitting cues after translation.
+//!
+//! Goals:
+//! - Merge adjacent cues into one (time range from the first start to the last end)
+//! - Split a long cue into parts, dividing its time by each part's character count
+//! - Keep track of which original cues every new cue came from
+//! - Optional pass that uses both to fix cues the QA checker flags (see `fit_reading_speed`)
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::qa::{visible_text, QaProfile};
+use super::{SrtDocument, SrtTime};
+
+/// Largest gap between two cues that may still be merged.
+const MAX_MERGE_GAP_MS: u64 = 1000;
+
+/// A document and its translation, restructured together.
+/// Cue ids are always positions (0..N-1); `origins[id]` lists the original cue ids.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Restructured {
+    pub document: SrtDocument,
+    pub translated: HashMap<usize, String>,
+
+    /// For each cue, the original cue ids it was built from (the parts of a split cue share one).
+    pub origins: Vec<Vec<usize>>,
+}
+
+impl Restructured {
+    pub fn new(doc: &SrtDocument, translated: &HashMap<usize, String>) -> Self {
+        Self {
+            origins: doc.cues.iter().map(|c| vec![c.id]).collect(),
+            document: doc.clone(),
+            translated: translated.clone(),
+        }
+    }
+
+    /// Merge cues `pos..pos + count` into one.
+    pub fn merge(&mut self, pos: usize, count: usize) -> Result<(), String> {
+        let end = pos + count;
+        if count < 2 || end > self.document.cues.len() {
+            return Err(format!("Cannot merge {count} cues starting at cue {}.", pos + 1));
+        }
+
+        let mut texts = Vec::with_capacity(count);
+        let mut origins = Vec::new();
+        for id in pos..end {
+            texts.push(self.take_text(id).trim().to_string());
+            origins.extend(self.origins[id].iter().copied());
+        }
+
+        let mut removed: Vec<_> = self.document.cues.drain(pos + 1..end).collect();
+        let last = removed.pop().expect("count >= 2");
+        let cue = &mut self.document.cues[pos];
+        for other in removed.iter().chain(std::iter::once(&last)) {
+            cue.text_lines.extend(other.text_lines.iter().cloned());
+        }
+        cue.set_times(cue.start.clone(), last.end.clone());
+        cue.layout.separator = last.layout.separator;
+
+        self.origins.splice(pos..end, std::iter::once(origins));
+        self.translated.insert(pos, texts.join(" "));
+        self.renumber(pos, count, 1);
+        Ok(())
+    }
+
+    /// Split the cue at `pos` into `parts` cues, cutting its text near equal character counts
+    /// and giving each part a share of the time proportional to its length.
+    pub fn split(&mut self, pos: usize, parts: usize) -> Result<(), String> {
+        if parts < 2 || pos >= self.document.cues.len() {
+            return Err(format!("Cannot split cue {} into {parts} parts.", pos + 1));
+        }
+
+        let translation = self.take_text(pos);
+        let pieces = split_text(&translation, parts);
+        if pieces.len() < 2 {
+            self.translated.insert(pos, translation);
+            return Err(format!("Cue {} is too short to split.", pos + 1));
+        }
+        let original = split_text(&self.document.cues[pos].text_lines.join("\n"), pieces.len());
+
+        let cue = self.document.cues[pos].clone();
+        let start = cue.start.to_millis();
+        let duration = cue.end.to_millis().saturating_sub(start);
+        let total_chars: usize = pieces.iter().map(|p| p.chars().count()).sum::<usize>().max(1);
+
+        let mut new_cues = Vec::with_capacity(pieces.len());
+        let mut chars_so_far = 0;
+        for (k, piece) in pieces.iter().enumerate() {
+            let part_start = start + duration * chars_so_far as u64 / total_chars as u64;
+            chars_so_far += piece.chars().count();
+            let part_end = start + duration * chars_so_far as u64 / total_chars as u64;
+
+            let mut part = cue.clone();
+            part.text_lines = original.get(k).map(|t| t.split('\n').map(str::to_string).collect()).unwrap_or_default();
+            if k + 1 < pieces.len() {
+                part.layout.separator = None;
+            }
+            part.set_times(SrtTime::from_millis(part_start), SrtTime::from_millis(part_end));
+            new_cues.push(part);
+        }
+
+        let count = new_cues.len();
+        self.document.cues.splice(pos..pos + 1, new_cues);
+        let origin = self.origins[pos].clone();
+        self.origins.splice(pos..pos + 1, std::iter::repeat_n(origin, count));
+
+        // Shift later translations out of the way before inserting the parts
+        self.renumber(pos, 1, count);
+        for (k, piece) in pieces.into_iter().enumerate() {
+            self.translated.insert(pos + k, piece);
+        }
+        Ok(())
+    }
+
+    fn take_text(&mut self, id: usize) -> String {
+        self.translated.remove(&id).unwrap_or_default()
+    }
+
+    /// `old_count` cues at `pos` were replaced by `new_count`: fix ids, cue numbers and translation keys.
+    fn renumber(&mut self, pos: usize, old_count: usize, new_count: usize) {
+        let mut translated = HashMap::with_capacity(self.translated.len());
+        for (id, text) in self.translated.drain() {
+            let id = if id >= pos + old_count { id + new_count - old_count } else { id };
+            translated.insert(id, text);
+        }
+        self.translated = translated;
+
+        for (id, cue) in self.document.cues.iter_mut().enumerate() {
+            cue.id = id;
+            cue.index_line = (id + 1).to_string();
+            cue.layout.index_missing = false;
+        }
+    }
+}
+
+/// Cut `text` (lines joined by spaces) into up to `parts` pieces of about equal length,
+/// at spaces (preferring ones after punctuation), or between characters for unspaced scripts.
+/// Tags are never cut.
+pub fn split_text(text: &str, parts: usize) -> Vec<String> {
+    split_text_weighted(text, &vec![1; parts])
+}
+
+/// Like `split_text`, with piece lengths proportional to `weights` (e.g. cue durations).
+pub fn split_text_weighted(text: &str, weights: &[u64]) -> Vec<String> {
+    let parts = weights.len();
+    let flat = text.split('\n').map(str::trim).filter(|l| !l.is_empty()).collect::<Vec<_>>().join(" ");
+    let chars: Vec<char> = flat.chars().collect();
+    let visible = visible_text(&flat).chars().count();
+    if parts < 2 || visible < parts * 2 {
+        return vec![flat];
+    }
+
+    // Cut candidates: char positions outside tags, with a preference score
+    let mut candidates: Vec<(usize, bool)> = Vec::new();
+    let has_spaces = flat.contains(' ');
+    let mut in_tag: Option<char> = None;
+    for (i, &c) in chars.iter().enumerate() {
+        match in_tag {
+            Some(close) if c == close => in_tag = None,
+            Some(_) => {}
+            None if c == '<' => in_tag = Some('>'),
+            None if c == '{' => in_tag = Some('}'),
+            None if has_spaces && c == ' ' => {
+                let after_punct = i > 0 && matches!(chars[i - 1], ',' | '.' | ';' | ':' | '!' | '?');
+                candidates.push((i, after_punct));
+            }
+            None if !has_spaces && i > 0 => {
+                let after_punct = "，。！？；：、".contains(chars[i - 1]);
+                candidates.push((i, after_punct));
+            }
+            None => {}
+        }
+    }
+
+    let window = chars.len() / parts / 2;
+    let total_weight = weights.iter().sum::<u64>().max(1) as usize;
+    let mut cuts: Vec<usize> = Vec::new();
+    let mut weight_so_far = 0;
+    for weight in &weights[..parts - 1] {
+        weight_so_far += *weight as usize;
+        let target = chars.len() * weight_so_far / total_weight;
+        let floor = cuts.last().copied().unwrap_or(0);
+        let best = candidates
+            .iter()
+            .filter(|(pos, _)| *pos > floor)
+            .min_by_key(|(pos, after_punct)| {
+                let distance = pos.abs_diff(target);
+                if *after_punct { distance.saturating_sub(window) } else { distance }
+            });
+        if let Some(&(pos, _)) = best {
+            cuts.push(pos);
+        }
+    }
+
+    let mut pieces = Vec::with_capacity(cuts.len() + 1);
+    let mut from = 0;
+    for cut in cuts.into_iter().chain(std::iter::once(chars.len())) {
+        let piece: String = chars[from..cut].iter().collect();
+        if !piece.trim().is_empty() {
+            pieces.push(piece.trim().to_string());
+        }
+        from = cut;
+    }
+    pieces
+}
+
+/// Post-translation pass: cues read too fast are merged with a neighbour when the result
+/// fits `profile`; cues with too much text are split when each part can be read in time.
+pub fn fit_reading_speed(doc: &SrtDocument, translated: &HashMap<usize, String>, profile: &QaProfile) -> Restructured {
+    let mut r = Restructured::new(doc, translated);
+    let capacity = profile.max_chars_per_line * profile.max_lines;
+
+    // Merge pass
+    let mut pos = 0;
+    while pos < r.document.cues.len() {
+        if r.translated.contains_key(&pos) && reading_speed(&r, pos, pos) > profile.max_cps {
+            // Merging `a` and `a + 1` helps when the pair is close, fits on screen and reads in time
+            let fits = |r: &Restructured, a: usize| -> bool {
+                let gap = r.document.cues[a + 1].start.to_millis().saturating_sub(r.document.cues[a].end.to_millis());
+                gap <= MAX_MERGE_GAP_MS
+                    && visible_len(&r.translated, a) + 1 + visible_len(&r.translated, a + 1) <= capacity
+                    && reading_speed(r, a, a + 1) <= profile.max_cps
+            };
+
+            if pos + 1 < r.document.cues.len() && fits(&r, pos) {
+                r.merge(pos, 2).expect("adjacent cues");
+                continue;
+            }
+            if pos > 0 && fits(&r, pos - 1) {
+                r.merge(pos - 1, 2).expect("adjacent cues");
+                continue;
+            }
+        }
+        pos += 1;
+    }
+
+    // Split pass: only when every part still gets enough time to be read
+    let mut pos = 0;
+    while pos < r.document.cues.len() {
+        let parts = visible_len(&r.translated, pos).div_ceil(capacity.max(1));
+        if parts >= 2 && reading_speed(&r, pos, pos) <= profile.max_cps && r.split(pos, parts).is_ok() {
+            pos += parts;
+        } else {
+            pos += 1;
+        }
+    }
+
+    r
+}
+
+/// Characters per second of cues `first..=last` shown as one.
+fn reading_speed(r: &Restructured, first: usize, last: usize) -> f32 {
+    let chars: usize = (first..=last).map(|id| visible_len(&r.translated, id)).sum();
+    let start = r.document.cues[first].start.to_millis();
+    let duration = r.document.cues[last].end.to_millis().saturating_sub(start).max(1);
+    chars as f32 * 1000.0 / duration as f32
+}
+
+/// Characters counted like the QA checker: no tags, no line breaks.
+fn visible_len(translated: &HashMap<usize, String>, id: usize) -> usize {
+    translated.get(&id).map(|t| t.split('\n').map(|l| visible_text(l).chars().count()).sum()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::srt::{parse_srt_bytes, write_srt};
+
+    const SAMPLE: &[u8] = b"1\n00:00:01,000 --> 00:00:02,000\nI told him that\n\n2\n00:00:02,100 --> 00:00:03,500\nhe should leave.\n\n3\n00:00:10,000 --> 00:00:20,000\nLong\n";
+
+    #[test]
+    fn test_merge_combines_time_and_origins() {
+        let doc = parse_srt_bytes(SAMPLE).unwrap();
+        let translated = HashMap::from([(0, "Tôi đã bảo anh ta".into()), (1, "rằng anh ta nên đi.".into()), (2, "Dài".into())]);
+        let mut r = Restructured::new(&doc, &translated);
+        r.merge(0, 2).unwrap();
+
+        assert_eq!(r.document.cues.len(), 2);
+        assert_eq!(r.document.cues[0].timing_line, "00:00:01,000 --> 00:00:03,500");
+        assert_eq!(r.translated[&0], "Tôi đã bảo anh ta rằng anh ta nên đi.");
+        assert_eq!(r.translated[&1], "Dài");
+        assert_eq!(r.origins, vec![vec![0, 1], vec![2]]);
+        assert_eq!(r.document.cues[1].index_line, "2");
+        assert!(write_srt(&r.document, &r.translated).unwrap().starts_with("1\n00:00:01,000 --> 00:00:03,500\n"));
+    }
+
+    #[test]
+    fn test_split_divides_time_by_characters() {
+        let doc = parse_srt_bytes(SAMPLE).unwrap();
+        let translated = HashMap::from([(0, "a".into()), (1, "b".into()), (2, "One two three four, five six seven eight nine ten".into())]);
+        let mut r = Restructured::new(&doc, &translated);
+        r.split(2, 2).unwrap();
+
+        assert_eq!(r.document.cues.len(), 4);
+        assert_eq!(r.translated[&2], "One two three four,");
+        assert_eq!(r.translated[&3], "five six seven eight nine ten");
+        assert_eq!(r.document.cues[2].start.to_millis(), 10_000);
+        assert_eq!(r.document.cues[2].end.to_millis(), r.document.cues[3].start.to_millis());
+        assert_eq!(r.document.cues[3].end.to_millis(), 20_000);
+        assert_eq!(r.origins[2], vec![2]);
+        assert_eq!(r.origins[3], vec![2]);
+    }
+
+    #[test]
+    fn test_fit_reading_speed_merges_fast_fragments() {
+        let doc = parse_srt_bytes(SAMPLE).unwrap();
+        let translated = HashMap::from([
+            (0, "Tôi bảo anh ta rằng".into()),
+            (1, "anh ta nên đi.".into()),
+            (2, "Dài".into()),
+        ]);
+
+        let r = fit_reading_speed(&doc, &translated, &QaProfile::netflix());
+        assert_eq!(r.origins, vec![vec![0, 1], vec![2]]);
+    }
+}
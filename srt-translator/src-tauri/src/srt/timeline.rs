@@ -0,0 +1,115 @@
+//! Whole-file retiming: shift, two-point stretch, framerate conversion.
+//!
+//! Goals:
+//! - Move every cue by a fixed offset (e.g. +2.3s)
+//! - Stretch linearly so two known cue times land on two target times
+//! - Convert between framerates (23.976 <-> 25 fps releases)
+//! - Only rewrite `timing_line` of cues whose times change; text and layout stay untouched
+
+use serde::{Deserialize, Serialize};
+
+use super::{SrtDocument, SrtTime};
+
+/// A time in the file (`from`) and where it should be (`to`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncPoint {
+    pub from: SrtTime,
+    pub to: SrtTime,
+}
+
+/// Apply `f` to every start/end time.
+fn map_times(doc: &mut SrtDocument, f: impl Fn(&SrtTime) -> SrtTime) {
+    for cue in doc.cues.iter_mut() {
+        let start = f(&cue.start);
+        let end = f(&cue.end);
+        if start != cue.start || end != cue.end {
+            cue.set_times(start, end);
+        }
+    }
+}
+
+/// Move all cues by `offset_ms` (negative = earlier). Times before zero are clamped to zero.
+pub fn shift(doc: &mut SrtDocument, offset_ms: i64) {
+    map_times(doc, |t| t.add(offset_ms));
+}
+
+/// Linear retime through two sync points (e.g. first and last line as heard in the video).
+pub fn stretch(doc: &mut SrtDocument, a: &SyncPoint, b: &SyncPoint) -> Result<(), String> {
+    let (a_from, b_from) = (a.from.to_millis() as f64, b.from.to_millis() as f64);
+    let (a_to, b_to) = (a.to.to_millis() as f64, b.to.to_millis() as f64);
+    if a_from == b_from {
+        return Err("The two sync points must be at different times.".into());
+    }
+
+    let factor = (b_to - a_to) / (b_from - a_from);
+    if factor <= 0.0 {
+        return Err("The sync points would reverse the cue order.".into());
+    }
+
+    map_times(doc, |t| {
+        let ms = a_to + (t.to_millis() as f64 - a_from) * factor;
+        SrtTime::from_millis(ms.round().max(0.0) as u64)
+    });
+    Ok(())
+}
+
+/// Retime for a release at `to_fps` made from one at `from_fps` (e.g. 23.976 -> 25 plays 4% faster).
+pub fn convert_framerate(doc: &mut SrtDocument, from_fps: f64, to_fps: f64) -> Result<(), String> {
+    if !(from_fps > 0.0 && to_fps > 0.0) {
+        return Err("Framerates must be greater than zero.".into());
+    }
+    let factor = from_fps / to_fps;
+    map_times(doc, |t| t.scale(factor));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::srt::{parse_srt_str, NewlineStyle};
+
+    const SAMPLE: &str = "1\n00:00:01,000 --> 00:00:02,500\nA\n\n2\n00:01:00,000 --> 00:01:02,000 X1:1 X2:2\nB\n";
+
+    fn timings(doc: &SrtDocument) -> Vec<&str> {
+        doc.cues.iter().map(|c| c.timing_line.as_str()).collect()
+    }
+
+    #[test]
+    fn test_time_arithmetic() {
+        let t = SrtTime::from_millis(3_723_004);
+        assert_eq!(t.format(), "01:02:03,004");
+        assert_eq!(t.add(-4).format(), "01:02:03,000");
+        assert_eq!(t.add(-10_000_000).to_millis(), 0);
+        assert_eq!(SrtTime::from_millis(1000).scale(1.5).to_millis(), 1500);
+    }
+
+    #[test]
+    fn test_shift_keeps_settings_and_text() {
+        let mut doc = parse_srt_str(SAMPLE, NewlineStyle::Lf).unwrap();
+        shift(&mut doc, 2300);
+
+        assert_eq!(timings(&doc), vec!["00:00:03,300 --> 00:00:04,800", "00:01:02,300 --> 00:01:04,300 X1:1 X2:2"]);
+        assert_eq!(doc.cues[1].text_lines, vec!["B"]);
+    }
+
+    #[test]
+    fn test_stretch_through_sync_points() {
+        let mut doc = parse_srt_str(SAMPLE, NewlineStyle::Lf).unwrap();
+        let a = SyncPoint { from: SrtTime::from_millis(1000), to: SrtTime::from_millis(2000) };
+        let b = SyncPoint { from: SrtTime::from_millis(60_000), to: SrtTime::from_millis(120_000) };
+        stretch(&mut doc, &a, &b).unwrap();
+
+        assert_eq!(doc.cues[0].start.to_millis(), 2000);
+        assert_eq!(doc.cues[1].start.to_millis(), 120_000);
+        assert!(stretch(&mut doc, &a, &a).is_err());
+    }
+
+    #[test]
+    fn test_convert_framerate() {
+        let mut doc = parse_srt_str(SAMPLE, NewlineStyle::Lf).unwrap();
+        convert_framerate(&mut doc, 25.0, 23.976).unwrap();
+
+        assert_eq!(doc.cues[1].start.to_millis(), 62_563);
+        assert!(convert_framerate(&mut doc, 0.0, 25.0).is_err());
+    }
+}
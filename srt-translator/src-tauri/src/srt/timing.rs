@@ -0,0 +1,298 @@
+//! Timing validation + repair over `SrtDocument`.
+//!
+//! Goals:
+//! - Detect end-before-start, zero-length, overlapping and out-of-order cues
+//! - Detect cues shown too briefly or too long, and broken cue numbering
+//! - Optionally fix them: sort by start time, clamp overlaps to a minimum gap,
+//!   enforce min/max display duration, renumber `index_line`
+//! - Report every issue with the cue it belongs to, so a dry run can be shown first
+
+use serde::{Deserialize, Serialize};
+
+use super::{SrtDocument, SrtTime};
+
+/// Display length used for inverted or zero-length cues when `min_duration_ms` is 0.
+const FALLBACK_DURATION_MS: u64 = 1000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimingConfig {
+    pub sort_by_start: bool,
+    pub fix_overlaps: bool,
+    pub renumber: bool,
+
+    /// Gap kept between a clamped cue and the next one (e.g. 83 = 2 frames at 24 fps).
+    pub min_gap_ms: u64,
+
+    /// Shortest allowed display time; 0 disables the check.
+    pub min_duration_ms: u64,
+
+    /// Longest allowed display time; 0 disables the check.
+    pub max_duration_ms: u64,
+}
+
+impl Default for TimingConfig {
+    fn default() -> Self {
+        Self {
+            sort_by_start: true,
+            fix_overlaps: true,
+            renumber: true,
+            min_gap_ms: 83,
+            min_duration_ms: 833,
+            max_duration_ms: 7000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum TimingIssueKind {
+    EndBeforeStart,
+    ZeroLength,
+    OutOfOrder,
+    Overlap,
+    TooShort,
+    TooLong,
+    Numbering,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimingIssue {
+    pub cue_id: usize,
+    pub index_line: String,
+    pub kind: TimingIssueKind,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimingReport {
+    /// Issues found in the document as it was.
+    pub issues: Vec<TimingIssue>,
+
+    /// Issues still present after fixing (e.g. two cues starting at the same time).
+    /// Equal to `issues` in a dry run.
+    pub remaining: Vec<TimingIssue>,
+
+    /// Number of cues whose times or number changed.
+    pub changed_cues: usize,
+
+    /// Cues were reordered (cue ids follow the new order).
+    pub reordered: bool,
+}
+
+/// List timing problems without changing anything.
+pub fn check_timing(doc: &SrtDocument, cfg: &TimingConfig) -> Vec<TimingIssue> {
+    let mut issues = Vec::new();
+    let issue = |cue: &super::SrtCue, kind, message: String| TimingIssue {
+        cue_id: cue.id,
+        index_line: cue.index_line.clone(),
+        kind,
+        message,
+    };
+
+    for (pos, cue) in doc.cues.iter().enumerate() {
+        let start = cue.start.to_millis();
+        let end = cue.end.to_millis();
+
+        if end < start {
+            issues.push(issue(cue, TimingIssueKind::EndBeforeStart, format!("Ends {} ms before it starts.", start - end)));
+        } else if end == start {
+            issues.push(issue(cue, TimingIssueKind::ZeroLength, "Has zero length.".into()));
+        } else if cfg.min_duration_ms > 0 && end - start < cfg.min_duration_ms {
+            issues.push(issue(
+                cue,
+                TimingIssueKind::TooShort,
+                format!("Shown for {} ms (minimum {} ms).", end - start, cfg.min_duration_ms),
+            ));
+        } else if cfg.max_duration_ms > 0 && end - start > cfg.max_duration_ms {
+            issues.push(issue(
+                cue,
+                TimingIssueKind::TooLong,
+                format!("Shown for {} ms (maximum {} ms).", end - start, cfg.max_duration_ms),
+            ));
+        }
+
+        if let Some(prev) = pos.checked_sub(1).map(|p| &doc.cues[p]) {
+            if start < prev.start.to_millis() {
+                issues.push(issue(
+                    cue,
+                    TimingIssueKind::OutOfOrder,
+                    format!("Starts before the previous cue ({}).", prev.index_line),
+                ));
+            }
+        }
+
+        if let Some(next) = doc.cues.get(pos + 1) {
+            let next_start = next.start.to_millis();
+            if end > next_start && next_start >= start {
+                issues.push(issue(
+                    cue,
+                    TimingIssueKind::Overlap,
+                    format!("Overlaps the next cue ({}) by {} ms.", next.index_line, end - next_start),
+                ));
+            } else if end <= next_start && end + cfg.min_gap_ms > next_start && end > start {
+                issues.push(issue(
+                    cue,
+                    TimingIssueKind::Overlap,
+                    format!(
+                        "Ends {} ms before the next cue ({}); the minimum gap is {} ms.",
+                        next_start - end,
+                        next.index_line,
+                        cfg.min_gap_ms
+                    ),
+                ));
+            }
+        }
+
+        let expected = (pos + 1).to_string();
+        if cue.index_line != expected || cue.layout.index_missing {
+            issues.push(issue(cue, TimingIssueKind::Numbering, format!("Numbered '{}', expected {expected}.", cue.index_line)));
+        }
+    }
+
+    issues
+}
+
+/// Fix what `cfg` enables, in order: sort, repair inverted/zero-length cues,
+/// enforce min/max duration, clamp overlaps, renumber.
+/// With `dry_run` the document is left untouched and only the report is built.
+pub fn fix_timing(doc: &mut SrtDocument, cfg: &TimingConfig, dry_run: bool) -> TimingReport {
+    let issues = check_timing(doc, cfg);
+    if dry_run {
+        return TimingReport {
+            remaining: issues.clone(),
+            issues,
+            changed_cues: 0,
+            reordered: false,
+        };
+    }
+
+    let before: Vec<(String, String)> = doc.cues.iter().map(|c| (c.index_line.clone(), c.timing_line.clone())).collect();
+
+    let mut reordered = false;
+    if cfg.sort_by_start && doc.cues.windows(2).any(|w| w[1].start.to_millis() < w[0].start.to_millis()) {
+        // Stable: cues starting together keep their order
+        doc.cues.sort_by_key(|c| c.start.to_millis());
+        for (id, cue) in doc.cues.iter_mut().enumerate() {
+            cue.id = id;
+        }
+        reordered = true;
+    }
+
+    let repair_ms = if cfg.min_duration_ms > 0 { cfg.min_duration_ms } else { FALLBACK_DURATION_MS };
+    for cue in doc.cues.iter_mut() {
+        let start = cue.start.to_millis();
+        let end = cue.end.to_millis();
+
+        let fixed_end = if end <= start {
+            start + repair_ms
+        } else if cfg.min_duration_ms > 0 && end - start < cfg.min_duration_ms {
+            start + cfg.min_duration_ms
+        } else if cfg.max_duration_ms > 0 && end - start > cfg.max_duration_ms {
+            start + cfg.max_duration_ms
+        } else {
+            end
+        };
+        if fixed_end != end {
+            cue.set_times(cue.start.clone(), SrtTime::from_millis(fixed_end));
+        }
+    }
+
+    if cfg.fix_overlaps {
+        for pos in 1..doc.cues.len() {
+            let next_start = doc.cues[pos].start.to_millis();
+            let cue = &mut doc.cues[pos - 1];
+            let start = cue.start.to_millis();
+            if cue.end.to_millis() + cfg.min_gap_ms <= next_start {
+                continue;
+            }
+            // Never clamp a cue to nothing; what can't be fixed stays in `remaining`
+            let clamped = next_start.saturating_sub(cfg.min_gap_ms);
+            if clamped > start {
+                cue.set_times(cue.start.clone(), SrtTime::from_millis(clamped));
+            }
+        }
+    }
+
+    if cfg.renumber {
+        for (pos, cue) in doc.cues.iter_mut().enumerate() {
+            cue.index_line = (pos + 1).to_string();
+            cue.layout.index_missing = false;
+        }
+    }
+
+    let changed_cues = if reordered {
+        doc.cues.len()
+    } else {
+        doc.cues
+            .iter()
+            .zip(&before)
+            .filter(|(c, (index, timing))| &c.index_line != index || &c.timing_line != timing)
+            .count()
+    };
+
+    TimingReport {
+        issues,
+        remaining: check_timing(doc, cfg),
+        changed_cues,
+        reordered,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::srt::{parse_srt_str, NewlineStyle};
+
+    const BROKEN: &str = "1\n00:00:05,000 --> 00:00:07,000\nC\n\n2\n00:00:01,000 --> 00:00:03,000\nA\n\n3\n00:00:02,500 --> 00:00:02,500\nB\n\n4\n00:00:08,000 --> 00:00:06,000 X1:10 X2:20\nD\n";
+
+    fn kinds(issues: &[TimingIssue]) -> Vec<TimingIssueKind> {
+        issues.iter().map(|i| i.kind).collect()
+    }
+
+    #[test]
+    fn test_check_reports_without_changing() {
+        let mut doc = parse_srt_str(BROKEN, NewlineStyle::Lf).unwrap();
+        let report = fix_timing(&mut doc, &TimingConfig::default(), true);
+
+        assert!(kinds(&report.issues).contains(&TimingIssueKind::OutOfOrder));
+        assert!(kinds(&report.issues).contains(&TimingIssueKind::ZeroLength));
+        assert!(kinds(&report.issues).contains(&TimingIssueKind::EndBeforeStart));
+        assert!(kinds(&report.issues).contains(&TimingIssueKind::Overlap));
+        assert_eq!(report.changed_cues, 0);
+        assert_eq!(doc.cues[0].timing_line, "00:00:05,000 --> 00:00:07,000");
+    }
+
+    #[test]
+    fn test_fix_sorts_clamps_and_renumbers() {
+        let mut doc = parse_srt_str(BROKEN, NewlineStyle::Lf).unwrap();
+        let report = fix_timing(&mut doc, &TimingConfig::default(), false);
+
+        assert!(report.reordered);
+        assert!(report.remaining.is_empty(), "{:?}", report.remaining);
+
+        let texts: Vec<&str> = doc.cues.iter().map(|c| c.text_lines[0].as_str()).collect();
+        assert_eq!(texts, vec!["A", "B", "C", "D"]);
+        let timings: Vec<&str> = doc.cues.iter().map(|c| c.timing_line.as_str()).collect();
+        assert_eq!(
+            timings,
+            vec![
+                "00:00:01,000 --> 00:00:02,417",
+                "00:00:02,500 --> 00:00:03,333",
+                "00:00:05,000 --> 00:00:07,000",
+                "00:00:08,000 --> 00:00:08,833 X1:10 X2:20",
+            ]
+        );
+        let numbers: Vec<&str> = doc.cues.iter().map(|c| c.index_line.as_str()).collect();
+        assert_eq!(numbers, vec!["1", "2", "3", "4"]);
+        assert!(doc.cues.iter().enumerate().all(|(pos, c)| c.id == pos));
+    }
+
+    #[test]
+    fn test_clean_file_has_no_issues() {
+        let doc = parse_srt_str(
+            "1\n00:00:01,000 --> 00:00:03,000\nA\n\n2\n00:00:03,100 --> 00:00:05,000\nB\n",
+            NewlineStyle::Lf,
+        )
+        .unwrap();
+        assert!(check_timing(&doc, &TimingConfig::default()).is_empty());
+    }
+}
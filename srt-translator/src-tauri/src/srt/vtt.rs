@@ -0,0 +1,387 @@
+//! WebVTT parsing + writing.
+//!
+//! Goals:
+//! - Keep the `WEBVTT` header and `NOTE`/`STYLE`/`REGION` blocks as-is
+//! - Keep cue identifiers and cue settings (`line:`, `position:`, `align:`, ...)
+//! - Expose cue text to the batcher through the shared `SrtDocument` view
+//! - Write valid .vtt, either from a parsed VTT file or converted from SRT cues
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::{CueLayout, NewlineStyle, SrtCue, SrtDocument, SrtError, SrtTime};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VttSetting {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VttCue {
+    pub identifier: Option<String>,
+    pub start: SrtTime,
+    pub end: SrtTime,
+
+    /// Cue settings after the end time, in input order.
+    pub settings: Vec<VttSetting>,
+
+    pub text_lines: Vec<String>,
+}
+
+impl VttCue {
+    pub fn setting(&self, name: &str) -> Option<&str> {
+        self.settings
+            .iter()
+            .find(|s| s.name == name)
+            .map(|s| s.value.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum VttBlock {
+    /// Raw lines of a `NOTE` comment block (including the `NOTE` line).
+    Note(Vec<String>),
+    /// Raw lines of a `STYLE` block (including the `STYLE` line).
+    Style(Vec<String>),
+    /// Raw lines of a `REGION` block (including the `REGION` line).
+    Region(Vec<String>),
+    /// Index into `VttDocument::cues`.
+    Cue(usize),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VttDocument {
+    pub newline: NewlineStyle,
+
+    /// The `WEBVTT` signature line plus any header lines before the first blank line.
+    pub header: Vec<String>,
+
+    /// All blocks in file order; cues reference `cues` by position.
+    pub blocks: Vec<VttBlock>,
+
+    /// Cues in file order; cue N becomes cue id N.
+    pub cues: Vec<VttCue>,
+}
+
+impl VttDocument {
+    /// Cue view of the text for the batcher/translator.
+    pub fn to_srt_document(&self) -> SrtDocument {
+        let cues = self
+            .cues
+            .iter()
+            .enumerate()
+            .map(|(id, cue)| SrtCue {
+                id,
+                index_line: (id + 1).to_string(),
+                timing_line: format!("{} --> {}", cue.start.format(), cue.end.format()),
+                start: cue.start.clone(),
+                end: cue.end.clone(),
+                text_lines: cue.text_lines.clone(),
+                layout: CueLayout::default(),
+            })
+            .collect();
+
+        SrtDocument::new(self.newline, cues)
+    }
+
+    /// Take the times of the cue view (cue N = VTT cue N) back, e.g. after the cues were shifted.
+    pub fn set_times(&mut self, doc: &SrtDocument) {
+        for (cue, timed) in self.cues.iter_mut().zip(&doc.cues) {
+            cue.start = timed.start.clone();
+            cue.end = timed.end.clone();
+        }
+    }
+}
+
+/// Parse decoded WebVTT text.
+pub fn parse_vtt_str(text: &str, newline: NewlineStyle) -> Result<VttDocument, SrtError> {
+    let normalized = text.replace("\r\n", "\n").replace('\r', "\n");
+    let lines: Vec<&str> = normalized.split('\n').collect();
+
+    let signature = lines.first().copied().unwrap_or("");
+    if !(signature == "WEBVTT" || signature.starts_with("WEBVTT ") || signature.starts_with("WEBVTT\t")) {
+        return Err(SrtError::Format {
+            line: 1,
+            message: "A WebVTT file must start with 'WEBVTT'.".into(),
+        });
+    }
+
+    let mut i = 0usize;
+    let mut header: Vec<String> = Vec::new();
+    while i < lines.len() && !lines[i].trim().is_empty() {
+        header.push(lines[i].to_string());
+        i += 1;
+    }
+
+    let mut blocks: Vec<VttBlock> = Vec::new();
+    let mut cues: Vec<VttCue> = Vec::new();
+
+    loop {
+        while i < lines.len() && lines[i].trim().is_empty() {
+            i += 1;
+        }
+        if i >= lines.len() {
+            break;
+        }
+
+        let block_start = i;
+        let mut block: Vec<&str> = Vec::new();
+        while i < lines.len() && !lines[i].trim().is_empty() {
+            block.push(lines[i]);
+            i += 1;
+        }
+
+        let first = block[0];
+        let raw = || block.iter().map(|l| l.to_string()).collect::<Vec<_>>();
+        if is_block_keyword(first, "NOTE") {
+            blocks.push(VttBlock::Note(raw()));
+        } else if is_block_keyword(first, "STYLE") {
+            blocks.push(VttBlock::Style(raw()));
+        } else if is_block_keyword(first, "REGION") {
+            blocks.push(VttBlock::Region(raw()));
+        } else {
+            let (identifier, timing_offset) = if first.contains("-->") {
+                (None, 0)
+            } else if block.len() > 1 && block[1].contains("-->") {
+                (Some(first.to_string()), 1)
+            } else {
+                return Err(SrtError::Format {
+                    line: block_start + 1,
+                    message: "Expected a cue timing line like '00:00:01.000 --> 00:00:03.000'.".into(),
+                });
+            };
+
+            let (start, end, settings) =
+                parse_vtt_timing_line(block[timing_offset]).map_err(|message| SrtError::Format {
+                    line: block_start + timing_offset + 1,
+                    message,
+                })?;
+
+            blocks.push(VttBlock::Cue(cues.len()));
+            cues.push(VttCue {
+                identifier,
+                start,
+                end,
+                settings,
+                text_lines: block[timing_offset + 1..].iter().map(|l| l.to_string()).collect(),
+            });
+        }
+    }
+
+    if cues.is_empty() {
+        return Err(SrtError::Format {
+            line: 1,
+            message: "No subtitle cues found. Make sure this is a valid .vtt file.".into(),
+        });
+    }
+
+    Ok(VttDocument {
+        newline,
+        header,
+        blocks,
+        cues,
+    })
+}
+
+fn is_block_keyword(line: &str, keyword: &str) -> bool {
+    line == keyword || line.starts_with(&format!("{keyword} ")) || line.starts_with(&format!("{keyword}\t"))
+}
+
+fn parse_vtt_timing_line(line: &str) -> Result<(SrtTime, SrtTime, Vec<VttSetting>), String> {
+    let (left, right) = line
+        .split_once("-->")
+        .ok_or_else(|| "Timing line is missing the '-->' separator.".to_string())?;
+
+    let mut right_parts = right.split_whitespace();
+    let end_token = right_parts
+        .next()
+        .ok_or_else(|| "Timing line is missing end time after '-->'.".to_string())?;
+
+    let start = parse_vtt_time(left.trim()).map_err(|e| format!("Invalid start time: {e}"))?;
+    let end = parse_vtt_time(end_token).map_err(|e| format!("Invalid end time: {e}"))?;
+
+    let settings = right_parts
+        .map(|token| {
+            let (name, value) = token.split_once(':').unwrap_or((token, ""));
+            VttSetting {
+                name: name.to_string(),
+                value: value.to_string(),
+            }
+        })
+        .collect();
+
+    Ok((start, end, settings))
+}
+
+/// Parse "HH:MM:SS.mmm" or "MM:SS.mmm".
+fn parse_vtt_time(s: &str) -> Result<SrtTime, String> {
+    let parts: Vec<&str> = s.split(':').collect();
+    let (hours, minutes, sec_part) = match parts.as_slice() {
+        [h, m, sec] => (h.parse::<u32>().map_err(|_| format!("Invalid hours in '{s}'"))?, *m, *sec),
+        [m, sec] => (0, *m, *sec),
+        _ => return Err(format!("Expected time like HH:MM:SS.mmm but got '{s}'")),
+    };
+
+    let minutes: u32 = minutes.parse().map_err(|_| format!("Invalid minutes in '{s}'"))?;
+    let (sec_str, ms_str) = sec_part
+        .split_once('.')
+        .ok_or_else(|| format!("Expected seconds and milliseconds like SS.mmm in '{s}'"))?;
+    let seconds: u32 = sec_str.parse().map_err(|_| format!("Invalid seconds in '{s}'"))?;
+    let millis: u32 = ms_str.parse().map_err(|_| format!("Invalid milliseconds in '{s}'"))?;
+
+    if minutes > 59 || seconds > 59 || millis > 999 || ms_str.len() != 3 {
+        return Err(format!("Time components out of range in '{s}'"));
+    }
+
+    Ok(SrtTime {
+        hours,
+        minutes,
+        seconds,
+        millis,
+    })
+}
+
+fn push_cue(
+    out: &mut Vec<String>,
+    identifier: Option<&str>,
+    start: &SrtTime,
+    end: &SrtTime,
+    settings: &[VttSetting],
+    text: &str,
+) {
+    if let Some(id) = identifier {
+        out.push(id.to_string());
+    }
+
+    let mut timing = format!("{} --> {}", start.format_vtt(), end.format_vtt());
+    for s in settings {
+        timing.push(' ');
+        timing.push_str(&s.name);
+        if !s.value.is_empty() {
+            timing.push(':');
+            timing.push_str(&s.value);
+        }
+    }
+    out.push(timing);
+
+    for line in text.split('\n') {
+        out.push(line.to_string());
+    }
+    out.push(String::new());
+}
+
+fn finish(lines: Vec<String>, newline: NewlineStyle) -> String {
+    let nl = newline.as_str();
+    let mut out = lines.join(nl);
+    out.push_str(nl);
+    out
+}
+
+/// Write a parsed VTT file back with translated cue text.
+/// Header, NOTE/STYLE/REGION blocks, identifiers and settings are kept.
+pub fn write_vtt(doc: &VttDocument, translated: &HashMap<usize, String>) -> Result<String, String> {
+    if translated.len() != doc.cues.len() {
+        return Err(format!(
+            "Translation mismatch: expected {} cues but got {} translations.",
+            doc.cues.len(),
+            translated.len()
+        ));
+    }
+
+    let mut out: Vec<String> = doc.header.clone();
+    out.push(String::new());
+
+    for block in &doc.blocks {
+        match block {
+            VttBlock::Note(raw) | VttBlock::Style(raw) | VttBlock::Region(raw) => {
+                out.extend(raw.iter().cloned());
+                out.push(String::new());
+            }
+            VttBlock::Cue(id) => {
+                let cue = &doc.cues[*id];
+                let text = translated
+                    .get(id)
+                    .ok_or_else(|| format!("Missing translation for cue id {id}"))?;
+                push_cue(&mut out, cue.identifier.as_deref(), &cue.start, &cue.end, &cue.settings, text);
+            }
+        }
+    }
+
+    Ok(finish(out, doc.newline))
+}
+
+/// Convert cues from any source (usually SRT) to WebVTT.
+/// The SRT cue number is kept as the cue identifier.
+pub fn write_cues_as_vtt(doc: &SrtDocument, translated: &HashMap<usize, String>) -> Result<String, String> {
+    super::validate_translation_count(doc, translated)?;
+
+    let mut out: Vec<String> = vec!["WEBVTT".to_string(), String::new()];
+    for cue in &doc.cues {
+        let text = translated
+            .get(&cue.id)
+            .ok_or_else(|| format!("Missing translation for cue id {}", cue.id))?;
+        push_cue(&mut out, Some(&cue.index_line), &cue.start, &cue.end, &[], text);
+    }
+
+    Ok(finish(out, doc.newline))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "WEBVTT - Episode 1\nKind: captions\n\nNOTE\nTranslated by the team\n\nSTYLE\n::cue { color: yellow; }\n\nREGION\nid:top width:40%\n\nintro\n00:01.000 --> 00:03.500 line:0 position:20% align:start\n<v Tom>Hello there\nSecond line\n\n00:00:04.000 --> 00:00:06.000\nBye\n";
+
+    #[test]
+    fn test_parse_blocks_and_settings() {
+        let doc = parse_vtt_str(SAMPLE, NewlineStyle::Lf).unwrap();
+
+        assert_eq!(doc.header, vec!["WEBVTT - Episode 1", "Kind: captions"]);
+        assert_eq!(doc.blocks.len(), 5);
+        assert!(matches!(doc.blocks[0], VttBlock::Note(_)));
+        assert!(matches!(doc.blocks[1], VttBlock::Style(_)));
+        assert!(matches!(doc.blocks[2], VttBlock::Region(_)));
+
+        let cue = &doc.cues[0];
+        assert_eq!(cue.identifier.as_deref(), Some("intro"));
+        assert_eq!(cue.start.seconds, 1);
+        assert_eq!(cue.end.millis, 500);
+        assert_eq!(cue.setting("line"), Some("0"));
+        assert_eq!(cue.setting("position"), Some("20%"));
+        assert_eq!(cue.setting("align"), Some("start"));
+        assert_eq!(cue.text_lines, vec!["<v Tom>Hello there", "Second line"]);
+        assert!(doc.cues[1].identifier.is_none());
+    }
+
+    #[test]
+    fn test_write_vtt_keeps_blocks_and_settings() {
+        let doc = parse_vtt_str(SAMPLE, NewlineStyle::Lf).unwrap();
+        let mut translated = HashMap::new();
+        translated.insert(0, "<v Tom>Xin chào\nDòng hai".to_string());
+        translated.insert(1, "Tạm biệt".to_string());
+
+        let out = write_vtt(&doc, &translated).unwrap();
+
+        assert!(out.starts_with("WEBVTT - Episode 1\nKind: captions\n\nNOTE\nTranslated by the team\n\n"));
+        assert!(out.contains("STYLE\n::cue { color: yellow; }\n\nREGION\nid:top width:40%\n\n"));
+        assert!(out.contains("intro\n00:00:01.000 --> 00:00:03.500 line:0 position:20% align:start\n<v Tom>Xin chào\nDòng hai\n\n"));
+        assert!(out.contains("00:00:04.000 --> 00:00:06.000\nTạm biệt\n"));
+
+        let reparsed = parse_vtt_str(&out, NewlineStyle::Lf).unwrap();
+        assert_eq!(reparsed.cues.len(), 2);
+    }
+
+    #[test]
+    fn test_srt_to_vtt_conversion() {
+        let doc = super::super::parse_srt_bytes(b"1\n00:00:01,000 --> 00:00:03,000\nHello\n\n2\n00:00:04,000 --> 00:00:05,250\nWorld\n").unwrap();
+        let translated: HashMap<usize, String> = doc.cues.iter().map(|c| (c.id, c.text_lines.join("\n"))).collect();
+
+        let out = write_cues_as_vtt(&doc, &translated).unwrap();
+
+        assert_eq!(
+            out,
+            "WEBVTT\n\n1\n00:00:01.000 --> 00:00:03.000\nHello\n\n2\n00:00:04.000 --> 00:00:05.250\nWorld\n\n"
+        );
+    }
+}
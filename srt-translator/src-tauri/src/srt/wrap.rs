@@ -0,0 +1,312 @@
+//! Line breaking for translated cue text.
+//!
+//! Goals:
+//! - Rebalance text into at most `max_lines` lines of `max_width` columns
+//! - Prefer breaks after punctuation and before conjunctions, then balanced lines
+//! - Count full-width (CJK) characters as two columns and allow breaks between them
+//! - Never split a tag (`<font color="red">`, `{\an8}`); tags take no width
+//! - Leave text that already fits alone (the model's or the source's own breaks win)
+
+use serde::{Deserialize, Serialize};
+
+use super::qa::visible_text;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WrapConfig {
+    pub max_lines: usize,
+
+    /// Columns per line; full-width characters take two.
+    pub max_width: usize,
+}
+
+impl Default for WrapConfig {
+    fn default() -> Self {
+        Self {
+            max_lines: 2,
+            max_width: 42,
+        }
+    }
+}
+
+/// Cost bonus for breaking after sentence/clause punctuation.
+const PUNCTUATION_BONUS: i64 = 300;
+/// Cost bonus for starting the next line with a conjunction.
+const CONJUNCTION_BONUS: i64 = 150;
+/// Cost per squared column past `max_width` (only when the text can't fit at all).
+const OVERFLOW_PENALTY: i64 = 1000;
+
+/// Words a line may start with to read naturally (English, Vietnamese).
+const CONJUNCTIONS: &[&str] = &[
+    "and", "but", "or", "so", "because", "which", "that", "when", "while", "if", "và", "nhưng", "hoặc", "vì", "nên", "mà",
+    "thì", "khi", "nếu",
+];
+
+/// An unbreakable piece of text: a word, a single CJK character, with any tags glued to it.
+#[derive(Debug, Default)]
+struct Token {
+    text: String,
+    width: usize,
+    /// Separated from the previous token by a space (false inside CJK runs).
+    space_before: bool,
+}
+
+/// Re-wrap `text` ('\n' separated lines) to fit `cfg`; returns it unchanged when it already fits.
+pub fn wrap_text(text: &str, cfg: &WrapConfig) -> String {
+    let max_lines = cfg.max_lines.max(1);
+    let fits = text.split('\n').count() <= max_lines && text.split('\n').all(|l| display_width(l) <= cfg.max_width);
+    if fits {
+        return text.to_string();
+    }
+
+    let tokens = tokenize(text);
+    if tokens.is_empty() {
+        return text.to_string();
+    }
+
+    // Fewest lines that fit; if nothing fits, spread over max_lines and let QA flag it
+    let mut breaks = Vec::new();
+    for lines in 1..=max_lines.min(tokens.len()) {
+        let fits;
+        (breaks, fits) = best_breaks(&tokens, lines, cfg.max_width);
+        if fits {
+            break;
+        }
+    }
+
+    let mut out = String::new();
+    let mut line_start = 0;
+    for end in breaks.into_iter().chain(std::iter::once(tokens.len())) {
+        if line_start > 0 {
+            out.push('\n');
+        }
+        out.push_str(&join_tokens(&tokens[line_start..end]));
+        line_start = end;
+    }
+    out
+}
+
+/// Width in columns, ignoring tags.
+pub fn display_width(line: &str) -> usize {
+    let mut width = 0;
+    let mut closing: Option<char> = None;
+    for c in line.chars() {
+        match closing {
+            Some(end) if c == end => closing = None,
+            Some(_) => {}
+            None if c == '<' || c == '{' => closing = Some(if c == '<' { '>' } else { '}' }),
+            None => width += char_width(c),
+        }
+    }
+    width
+}
+
+fn char_width(c: char) -> usize {
+    let wide = matches!(c as u32,
+        0x1100..=0x115F
+        | 0x2E80..=0x303E
+        | 0x3041..=0x33FF
+        | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF
+        | 0xA000..=0xA4CF
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6
+        | 0x20000..=0x3FFFD);
+    if wide {
+        2
+    } else {
+        1
+    }
+}
+
+/// Scripts written without spaces, where a line may break between any two characters.
+fn breaks_anywhere(c: char) -> bool {
+    matches!(c as u32, 0x3040..=0x30FF | 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF)
+}
+
+/// CJK punctuation that must not start a line.
+fn is_closing_punctuation(c: char) -> bool {
+    "。，、！？：；」』）】〉》…ー．".contains(c)
+}
+
+fn tokenize(text: &str) -> Vec<Token> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut current = Token::default();
+    let mut last_visible: Option<char> = None;
+    let mut pending_space = false;
+
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+
+        // Tags are copied whole and glued to the current token
+        if c == '<' || c == '{' {
+            let close = if c == '<' { '>' } else { '}' };
+            if let Some(len) = chars[i..].iter().position(|&x| x == close) {
+                if current.text.is_empty() {
+                    current.space_before = pending_space;
+                    pending_space = false;
+                }
+                current.text.extend(&chars[i..=i + len]);
+                i += len + 1;
+                continue;
+            }
+        }
+
+        if c.is_whitespace() {
+            if !current.text.is_empty() {
+                tokens.push(std::mem::take(&mut current));
+            }
+            pending_space = !tokens.is_empty();
+            last_visible = None;
+            i += 1;
+            continue;
+        }
+
+        let cjk_boundary = last_visible.is_some_and(|p| breaks_anywhere(p) || is_closing_punctuation(p))
+            && breaks_anywhere(c)
+            && !is_closing_punctuation(c);
+        if cjk_boundary && current.width > 0 {
+            tokens.push(std::mem::take(&mut current));
+        }
+        if current.text.is_empty() {
+            current.space_before = pending_space;
+            pending_space = false;
+        }
+        current.text.push(c);
+        current.width += char_width(c);
+        last_visible = Some(c);
+        i += 1;
+    }
+
+    if !current.text.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+fn join_tokens(tokens: &[Token]) -> String {
+    let mut out = String::new();
+    for (k, token) in tokens.iter().enumerate() {
+        if k > 0 && token.space_before {
+            out.push(' ');
+        }
+        out.push_str(&token.text);
+    }
+    out
+}
+
+fn line_width(tokens: &[Token]) -> usize {
+    tokens
+        .iter()
+        .enumerate()
+        .map(|(k, t)| t.width + usize::from(k > 0 && t.space_before))
+        .sum()
+}
+
+/// Bonus for breaking between `tokens[at - 1]` and `tokens[at]`.
+fn break_bonus(tokens: &[Token], at: usize) -> i64 {
+    let before = visible_text(&tokens[at - 1].text);
+    if before.ends_with([',', '.', ';', ':', '!', '?', '—', '。', '，', '！', '？', '、', '；', '：']) {
+        return PUNCTUATION_BONUS;
+    }
+    let after = visible_text(&tokens[at].text).to_lowercase();
+    if tokens[at].space_before && CONJUNCTIONS.contains(&after.as_str()) {
+        return CONJUNCTION_BONUS;
+    }
+    0
+}
+
+fn line_cost(tokens: &[Token], max_width: usize) -> i64 {
+    let width = line_width(tokens) as i64;
+    let max_width = max_width as i64;
+    if width > max_width {
+        OVERFLOW_PENALTY * (width - max_width).pow(2)
+    } else {
+        // Squared slack: equal line widths are cheapest
+        (max_width - width).pow(2)
+    }
+}
+
+/// Best split of `tokens` into exactly `lines` lines: the break positions and whether every line fits.
+fn best_breaks(tokens: &[Token], lines: usize, max_width: usize) -> (Vec<usize>, bool) {
+    let n = tokens.len();
+    // cost[k][j]: best cost of putting tokens[..j] on k lines; from[k][j]: start of the k-th line
+    let mut cost = vec![vec![i64::MAX; n + 1]; lines + 1];
+    let mut from = vec![vec![0usize; n + 1]; lines + 1];
+    cost[0][0] = 0;
+
+    for k in 1..=lines {
+        for j in k..=n {
+            for i in (k - 1)..j {
+                if cost[k - 1][i] == i64::MAX {
+                    continue;
+                }
+                let bonus = if i > 0 { break_bonus(tokens, i) } else { 0 };
+                let c = cost[k - 1][i] + line_cost(&tokens[i..j], max_width) - bonus;
+                if c < cost[k][j] {
+                    cost[k][j] = c;
+                    from[k][j] = i;
+                }
+            }
+        }
+    }
+
+    let mut breaks = Vec::with_capacity(lines - 1);
+    let mut j = n;
+    for k in (2..=lines).rev() {
+        j = from[k][j];
+        breaks.push(j);
+    }
+    breaks.reverse();
+
+    let starts = std::iter::once(0).chain(breaks.iter().copied());
+    let ends = breaks.iter().copied().chain(std::iter::once(n));
+    let fits = starts.zip(ends).all(|(start, end)| line_width(&tokens[start..end]) <= max_width);
+    (breaks, fits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cfg(max_width: usize) -> WrapConfig {
+        WrapConfig { max_lines: 2, max_width }
+    }
+
+    #[test]
+    fn test_fitting_text_is_untouched() {
+        assert_eq!(wrap_text("Short line\nand another", &cfg(42)), "Short line\nand another");
+    }
+
+    #[test]
+    fn test_breaks_after_punctuation() {
+        let text = "I told you already, we are not going back there tonight.";
+        assert_eq!(wrap_text(text, &cfg(42)), "I told you already,\nwe are not going back there tonight.");
+    }
+
+    #[test]
+    fn test_prefers_conjunction_and_balance() {
+        let text = "We waited for hours at the station but the last train never came";
+        assert_eq!(wrap_text(text, &cfg(42)), "We waited for hours at the station\nbut the last train never came");
+    }
+
+    #[test]
+    fn test_tags_are_never_split() {
+        let text = "<font color=\"red\">Warning</font> the bridge ahead is closed to all traffic tonight";
+        let wrapped = wrap_text(text, &cfg(42));
+        assert_eq!(wrapped.lines().count(), 2);
+        assert!(wrapped.starts_with("<font color=\"red\">Warning</font>"));
+        assert!(wrapped.lines().all(|l| display_width(l) <= 42));
+    }
+
+    #[test]
+    fn test_cjk_counts_double_width() {
+        let text = "我们在车站等了好几个小时，但是最后一班火车始终没有来。";
+        let wrapped = wrap_text(text, &cfg(32));
+        assert_eq!(wrapped, "我们在车站等了好几个小时，\n但是最后一班火车始终没有来。");
+    }
+}
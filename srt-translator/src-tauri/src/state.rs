@@ -1,9 +1,17 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::sync::Mutex;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
 use uuid::Uuid;
 
-use crate::srt::SrtDocument;
+use crate::editor::EditLog;
+use crate::history::JobHistory;
+use crate::queue::JobQueue;
+use crate::srt::{parse_subtitle_bytes, write_srt, ParseDiagnostic, ParsedSubtitle, SrtDocument, SubtitleFormat, SubtitleSource};
+use crate::translate::checkpoint::{source_hash, Checkpoint};
+use crate::translate::control::JobControl;
+use crate::translate::memory::TranslationMemory;
+use crate::translate::worker::FailedRange;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileItem {
@@ -11,6 +19,11 @@ pub struct FileItem {
     pub path: String,
     pub name: String,
     pub cue_count: usize,
+    pub format: SubtitleFormat,
+
+    /// Repairs made while importing ("imported with N fixes").
+    #[serde(default)]
+    pub diagnostics: Vec<ParseDiagnostic>,
     pub status: FileStatus,
 }
 
@@ -31,13 +44,21 @@ pub struct JobInfo {
     pub eta_seconds: u64,
     pub output_path: Option<String>,
     pub error: Option<String>,
+
+    /// Cues left untranslated by failed batches (PartiallyDone jobs).
+    #[serde(default)]
+    pub failed: Vec<FailedRange>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum JobStatus {
     Queued,
     Running,
+    /// Running batches finish; no new ones start until resumed.
+    Paused,
     Done,
+    /// Finished, but some batches failed; see `JobInfo::failed`.
+    PartiallyDone,
     Error,
     Cancelled,
 }
@@ -45,24 +66,145 @@ pub enum JobStatus {
 pub struct FileData {
     pub item: FileItem,
     pub document: SrtDocument,
+    pub source: SubtitleSource,
+
+    /// Hash of the file's bytes at import, to match job checkpoints against.
+    pub source_hash: String,
+}
+
+impl FileData {
+    /// Read and parse the subtitle file at `path` (.srt / .ass / .ssa / .vtt).
+    /// `lenient` repairs damaged SRT (listing each fix in `item.diagnostics`) instead of rejecting it.
+    pub fn load(id: String, path: &str, lenient: bool) -> Result<Self, String> {
+        let bytes = std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        let ParsedSubtitle { document, source, diagnostics } =
+            parse_subtitle_bytes(Path::new(path), &bytes, lenient).map_err(|e| e.to_string())?;
+
+        let name = Path::new(path)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("unknown.srt")
+            .to_string();
+        let item = FileItem {
+            id,
+            path: path.to_string(),
+            name,
+            cue_count: document.cues.len(),
+            format: source.format(),
+            diagnostics,
+            status: FileStatus::Ready,
+        };
+        Ok(Self { item, document, source, source_hash: source_hash(&bytes) })
+    }
+
+    /// Each cue's own text, by cue id.
+    pub fn source_texts(&self) -> HashMap<usize, String> {
+        self.document.cues.iter().map(|c| (c.id, c.text_lines.join("\n"))).collect()
+    }
+
+    /// The cues were renumbered in memory (e.g. sorted by start time): hash what the document
+    /// now holds, so checkpoints and history taken against the old order no longer match.
+    pub fn rehash(&mut self) -> Result<(), String> {
+        self.source_hash = source_hash(write_srt(&self.document, &self.source_texts())?.as_bytes());
+        Ok(())
+    }
 }
 
 pub struct TranslationJob {
     pub info: JobInfo,
     pub options: Option<crate::translate::worker::TranslationOptions>,
     pub translated: Option<HashMap<usize, String>>,
+
+    /// Cues `translated` belongs to, when the job merged/split the file's cues.
+    pub document: Option<SrtDocument>,
+
+    /// For each cue of `document`, the file's cue ids it was built from.
+    pub cue_origins: Option<Vec<Vec<usize>>>,
+
+    /// Cancel/pause switch shared with the running translation.
+    pub control: JobControl,
+
+    /// Hand edits of `translated`, once the first cue was edited.
+    pub edits: Option<EditLog>,
 }
 
 pub struct AppState {
     pub files: Mutex<HashMap<String, FileData>>,
     pub jobs: Mutex<HashMap<String, TranslationJob>>,
+
+    /// Shared with running jobs, which add their translations as batches finish.
+    pub memory: Arc<Mutex<TranslationMemory>>,
+
+    /// Jobs waiting to run, and the request limit shared by all jobs.
+    pub queue: Mutex<JobQueue>,
+
+    /// Finished, failed and cancelled jobs of every session.
+    pub history: Mutex<JobHistory>,
 }
 
 impl AppState {
     pub fn new() -> Self {
-        Self {
+        let state = Self {
             files: Mutex::new(HashMap::new()),
             jobs: Mutex::new(HashMap::new()),
+            // An unreadable memory file shouldn't stop the app; fall back to an empty in-RAM memory
+            memory: Arc::new(Mutex::new(TranslationMemory::open_default().unwrap_or_else(|e| {
+                eprintln!("Translation memory unavailable: {}", e);
+                TranslationMemory::default()
+            }))),
+            queue: Mutex::new(JobQueue::default()),
+            history: Mutex::new(JobHistory::open_default().unwrap_or_else(|e| {
+                eprintln!("Job history unavailable: {}", e);
+                JobHistory::default()
+            })),
+        };
+        state.restore_jobs();
+        state
+    }
+
+    /// Bring back jobs an earlier session left unfinished (closed, crashed, or failed),
+    /// with their files re-read from disk. Starting one resumes it from its checkpoint.
+    fn restore_jobs(&self) {
+        let mut files = self.files.lock().unwrap();
+        let mut jobs = self.jobs.lock().unwrap();
+
+        for (checkpoint, header) in Checkpoint::list() {
+            let (status, progress, error) = match FileData::load(header.file_id.clone(), &header.file_path, true) {
+                Ok(file) => {
+                    let resumable = file.source_hash == header.source_hash;
+                    let done: usize = checkpoint.done().map(|d| d.values().map(|e| e.cues).sum()).unwrap_or(0);
+                    let progress = (done as f32 / file.item.cue_count.max(1) as f32 * 100.0).min(100.0);
+                    files.insert(header.file_id.clone(), file);
+                    if resumable {
+                        (JobStatus::Queued, progress, None)
+                    } else {
+                        (JobStatus::Error, 0.0, Some("The source file changed since this job was saved; it can't be resumed.".to_string()))
+                    }
+                }
+                Err(e) => (JobStatus::Error, 0.0, Some(e)),
+            };
+
+            jobs.insert(
+                header.job_id.clone(),
+                TranslationJob {
+                    info: JobInfo {
+                        id: header.job_id,
+                        file_id: header.file_id,
+                        status,
+                        progress,
+                        eta_seconds: 0,
+                        output_path: None,
+                        error,
+                        failed: Vec::new(),
+                    },
+                    options: Some(header.options),
+                    translated: None,
+                    document: None,
+                    cue_origins: None,
+                    control: JobControl::new(),
+                    edits: None,
+                },
+            );
         }
     }
 }
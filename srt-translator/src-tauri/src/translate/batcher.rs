@@ -5,11 +5,20 @@
 //! - Add context cues before/after the batch for better translation quality
 //! - Keep stable cue IDs and preserve ordering
 //! - Protect common subtitle tags via masking placeholders
-//! - Keep requests under a configurable size budget (approx chars)
+//! - Keep requests under a configurable size budget (model tokens, or approx chars; see `tokens`)
+//! - Shrink batches after bad responses and grow them back after good ones (`AdaptiveSizer`)
 
 use serde::{Deserialize, Serialize};
 
 use crate::srt::SrtCue;
+use crate::translate::tokens::{CharCount, ModelTokens, TokenEstimator};
+
+/// Default `max_tokens_per_request`: prompt plus expected output, well inside current context windows.
+pub const DEFAULT_MAX_TOKENS_PER_REQUEST: usize = 8_000;
+
+fn default_max_tokens() -> Option<usize> {
+    Some(DEFAULT_MAX_TOKENS_PER_REQUEST)
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BatchConfig {
@@ -17,6 +26,9 @@ pub struct BatchConfig {
     pub context_before: usize,    // e.g., 2
     pub context_after: usize,     // e.g., 2
     pub max_chars_per_request: usize, // e.g., 12000 (rough token safety)
+    /// Token budget per request (prompt + expected output); None falls back to the char budget.
+    #[serde(default = "default_max_tokens")]
+    pub max_tokens_per_request: Option<usize>,
 }
 
 impl Default for BatchConfig {
@@ -26,6 +38,7 @@ impl Default for BatchConfig {
             context_before: 2,
             context_after: 2,
             max_chars_per_request: 12_000,
+            max_tokens_per_request: default_max_tokens(),
         }
     }
 }
@@ -108,58 +121,193 @@ fn cue_text_joined(cue: &SrtCue) -> String {
     cue.text_lines.join("\n")
 }
 
-fn estimate_chars(cues: &[PromptCue]) -> usize {
-    cues.iter().map(|c| c.text.len() + c.timing.len() + 16).sum()
+fn estimate(cues: &[PromptCue], estimator: &dyn TokenEstimator) -> usize {
+    cues.iter().map(|c| estimator.cue_cost(c)).sum()
+}
+
+fn prompt_cue(cue: &SrtCue, role: CueRole) -> PromptCue {
+    let (masked, _) = mask_tags(&cue_text_joined(cue));
+    PromptCue {
+        id: cue.id,
+        timing: cue.timing_line.clone(),
+        text: masked,
+        role,
+    }
 }
 
 /// Create context-aware batches.
 /// Output batches are ordered and cover all cues exactly once as "Translate".
+/// Up to `context_before`/`context_after` neighbouring cues are added as "Context",
+/// nearest first, as long as the batch stays within `max_tokens_per_request` (GPT-style
+/// estimate; the worker uses the job's model), or `max_chars_per_request` without a token limit.
 pub fn create_batches(all_cues: &[SrtCue], cfg: &BatchConfig) -> Vec<TranslationBatch> {
+    match cfg.max_tokens_per_request {
+        Some(max_tokens) => {
+            let estimator = ModelTokens::for_model("");
+            create_batches_with(all_cues, cfg, &estimator, max_tokens.saturating_sub(estimator.scaffold()))
+        }
+        None => create_batches_with(all_cues, cfg, &CharCount, cfg.max_chars_per_request),
+    }
+}
+
+/// Like `create_batches`, with each batch's cost from `estimator` kept within `budget`.
+pub fn create_batches_with(
+    all_cues: &[SrtCue],
+    cfg: &BatchConfig,
+    estimator: &dyn TokenEstimator,
+    budget: usize,
+) -> Vec<TranslationBatch> {
+    create_batches_skipping(all_cues, cfg, estimator, budget, &|_| false)
+}
+
+/// Like `create_batches_with`, but cues for which `done(id)` holds (already translated) are
+/// never sent for translation; they stay in place as context for their neighbours, so each
+/// batch still sees the lines around it on the timeline.
+pub fn create_batches_skipping(
+    all_cues: &[SrtCue],
+    cfg: &BatchConfig,
+    estimator: &dyn TokenEstimator,
+    budget: usize,
+    done: &dyn Fn(usize) -> bool,
+) -> Vec<TranslationBatch> {
     let n = all_cues.len();
+    let next_open = |from: usize| (from..n).find(|&i| !done(all_cues[i].id)).unwrap_or(n);
     let mut batches = Vec::new();
-    let mut start = 0usize;
+    let mut start = next_open(0);
     let mut batch_no = 0usize;
 
     while start < n {
-        let mut end = (start + cfg.batch_size).min(n);
+        // A batch translates a run of consecutive open cues
+        let run_end = (start..n).find(|&i| done(all_cues[i].id)).unwrap_or(n);
+        let mut end = (start + cfg.batch_size.max(1)).min(run_end);
 
-        // shrink to respect max_chars_per_request
+        // shrink to respect the budget
         loop {
-            let mut prompt_cues: Vec<PromptCue> = Vec::new();
-            let mut translate_ids: Vec<usize> = Vec::new();
-
-            // Only include cues to translate (no context)
-            for cue in &all_cues[start..end] {
-                let (masked, _) = mask_tags(&cue_text_joined(cue));
-                translate_ids.push(cue.id);
-                prompt_cues.push(PromptCue {
-                    id: cue.id,
-                    timing: cue.timing_line.clone(),
-                    text: masked,
-                    role: CueRole::Translate,
-                });
+            let translate: Vec<PromptCue> = all_cues[start..end]
+                .iter()
+                .map(|cue| prompt_cue(cue, CueRole::Translate))
+                .collect();
+            let mut size = estimate(&translate, estimator);
+
+            if size > budget && end > start + 1 {
+                // too large, shrink end
+                end -= 1;
+                continue;
             }
 
-            if estimate_chars(&prompt_cues) <= cfg.max_chars_per_request || end == start + 1 {
-                // accept
-                batches.push(TranslationBatch {
-                    batch_no,
-                    translate_ids,
-                    cues: prompt_cues,
-                });
-                batch_no += 1;
-                start = end;
-                break;
+            // Context takes whatever budget is left, nearest first, alternating sides;
+            // a side stops at the first cue that doesn't fit so context stays contiguous
+            let mut before: Vec<PromptCue> = Vec::new();
+            let mut after: Vec<PromptCue> = Vec::new();
+            let (mut before_open, mut after_open) = (true, true);
+            for k in 0..cfg.context_before.max(cfg.context_after) {
+                if before_open && k < cfg.context_before && k < start {
+                    let cue = prompt_cue(&all_cues[start - 1 - k], CueRole::Context);
+                    let cost = estimator.cue_cost(&cue);
+                    before_open = size + cost <= budget;
+                    if before_open {
+                        size += cost;
+                        before.insert(0, cue);
+                    }
+                }
+                if after_open && k < cfg.context_after && end + k < n {
+                    let cue = prompt_cue(&all_cues[end + k], CueRole::Context);
+                    let cost = estimator.cue_cost(&cue);
+                    after_open = size + cost <= budget;
+                    if after_open {
+                        size += cost;
+                        after.push(cue);
+                    }
+                }
             }
 
-            // too large, shrink end
-            end = (end - 1).max(start + 1);
+            let translate_ids = translate.iter().map(|c| c.id).collect();
+            let mut cues = before;
+            cues.extend(translate);
+            cues.extend(after);
+
+            batches.push(TranslationBatch {
+                batch_no,
+                translate_ids,
+                cues,
+            });
+            batch_no += 1;
+            start = next_open(end);
+            break;
         }
     }
 
     batches
 }
 
+/// Split `batch` into chunks of at most `chunk_size` translate cues.
+/// Each chunk keeps up to `context_before`/`context_after` of its neighbours in the batch as context.
+pub fn split_batch(batch: &TranslationBatch, chunk_size: usize, cfg: &BatchConfig) -> Vec<TranslationBatch> {
+    let chunk_size = chunk_size.max(1);
+    if batch.translate_ids.len() <= chunk_size {
+        return vec![batch.clone()];
+    }
+
+    batch
+        .translate_ids
+        .chunks(chunk_size)
+        .map(|ids| {
+            let first = batch.cues.iter().position(|c| c.id == ids[0]).unwrap_or(0);
+            let last = batch.cues.iter().position(|c| c.id == ids[ids.len() - 1]).unwrap_or(first);
+            let from = first.saturating_sub(cfg.context_before);
+            let to = (last + 1 + cfg.context_after).min(batch.cues.len());
+
+            let cues = batch.cues[from..to]
+                .iter()
+                .map(|c| PromptCue {
+                    role: if ids.contains(&c.id) { CueRole::Translate } else { CueRole::Context },
+                    ..c.clone()
+                })
+                .collect();
+            TranslationBatch {
+                batch_no: batch.batch_no,
+                translate_ids: ids.to_vec(),
+                cues,
+            }
+        })
+        .collect()
+}
+
+/// Shared batch scale: halved after a parse failure or truncated response,
+/// grown back a step after each success (never above the configured size).
+#[derive(Debug)]
+pub struct AdaptiveSizer {
+    scale: f32,
+}
+
+const MIN_SCALE: f32 = 1.0 / 16.0;
+const GROW_FACTOR: f32 = 1.25;
+
+impl AdaptiveSizer {
+    pub fn new() -> Self {
+        Self { scale: 1.0 }
+    }
+
+    /// Translate cues per request for a batch of `len`.
+    pub fn chunk_size(&self, len: usize) -> usize {
+        ((len as f32 * self.scale).ceil() as usize).max(1)
+    }
+
+    pub fn on_failure(&mut self) {
+        self.scale = (self.scale / 2.0).max(MIN_SCALE);
+    }
+
+    pub fn on_success(&mut self) {
+        self.scale = (self.scale * GROW_FACTOR).min(1.0);
+    }
+}
+
+impl Default for AdaptiveSizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -173,6 +321,7 @@ mod tests {
             start: SrtTime { hours: 0, minutes: 0, seconds: 0, millis: 0 },
             end: SrtTime { hours: 0, minutes: 0, seconds: 1, millis: 0 },
             text_lines: vec![text.to_string()],
+            layout: Default::default(),
         }
     }
 
@@ -222,7 +371,7 @@ mod tests {
             batch_size: 2,
             context_before: 1,
             context_after: 1,
-            max_chars_per_request: 10000,
+            ..BatchConfig::default()
         };
 
         let batches = create_batches(&cues, &cfg);
@@ -246,7 +395,7 @@ mod tests {
             batch_size: 1,
             context_before: 1,
             context_after: 1,
-            max_chars_per_request: 10000,
+            ..BatchConfig::default()
         };
 
         let batches = create_batches(&cues, &cfg);
@@ -256,4 +405,89 @@ mod tests {
         // Should have 3 cues total (1 context before + 1 translate + 1 context after)
         assert_eq!(batches[1].cues.len(), 3);
     }
+
+    #[test]
+    fn test_default_budget_is_in_tokens() {
+        // 400 ASCII chars: 445 by the char budget, 260 tokens (104 in, 156 out)
+        let cues: Vec<SrtCue> = (0..60).map(|i| make_test_cue(i, &"word ".repeat(80))).collect();
+        let cfg = BatchConfig { batch_size: 100, context_before: 0, context_after: 0, ..BatchConfig::default() };
+        assert_eq!(create_batches(&cues, &cfg)[0].translate_ids.len(), (8_000 - 250) / 260);
+
+        let by_chars = BatchConfig { max_tokens_per_request: None, ..cfg };
+        assert_eq!(create_batches(&cues, &by_chars)[0].translate_ids.len(), 12_000 / 445);
+
+        // Options saved without the field get the token budget too
+        let saved: BatchConfig = serde_json::from_str(
+            r#"{"batch_size": 25, "context_before": 2, "context_after": 2, "max_chars_per_request": 12000}"#,
+        )
+        .unwrap();
+        assert_eq!(saved.max_tokens_per_request, Some(DEFAULT_MAX_TOKENS_PER_REQUEST));
+    }
+
+    #[test]
+    fn test_context_fits_budget() {
+        let cues: Vec<SrtCue> = (0..5).map(|i| make_test_cue(i, "Some words")).collect();
+        // One cue costs 10 + 29 + 16 = 55 chars: room for the cue plus one context cue
+        let cfg = BatchConfig {
+            batch_size: 1,
+            context_before: 2,
+            context_after: 2,
+            max_chars_per_request: 120,
+            max_tokens_per_request: None,
+        };
+
+        let batches = create_batches(&cues, &cfg);
+        assert_eq!(batches.len(), 5);
+        let ids: Vec<usize> = batches[2].cues.iter().map(|c| c.id).collect();
+        assert_eq!(ids, vec![1, 2]);
+        assert!(matches!(batches[2].cues[0].role, CueRole::Context));
+    }
+
+    #[test]
+    fn test_done_cues_stay_as_context() {
+        let cues: Vec<SrtCue> = (0..6).map(|i| make_test_cue(i, "Text")).collect();
+        let cfg = BatchConfig {
+            batch_size: 10,
+            context_before: 1,
+            context_after: 1,
+            max_chars_per_request: 10000,
+            max_tokens_per_request: None,
+        };
+        // 2 and 3 came from the translation memory
+        let batches = create_batches_skipping(&cues, &cfg, &CharCount, cfg.max_chars_per_request, &|id| id == 2 || id == 3);
+
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].translate_ids, vec![0, 1]);
+        assert_eq!(batches[0].cues.iter().map(|c| c.id).collect::<Vec<_>>(), vec![0, 1, 2]);
+        assert_eq!(batches[1].translate_ids, vec![4, 5]);
+        assert_eq!(batches[1].cues.iter().map(|c| c.id).collect::<Vec<_>>(), vec![3, 4, 5]);
+        assert!(matches!(batches[1].cues[0].role, CueRole::Context));
+    }
+
+    #[test]
+    fn test_split_batch_keeps_neighbours_as_context() {
+        let cues: Vec<SrtCue> = (0..4).map(|i| make_test_cue(i, "Text")).collect();
+        let cfg = BatchConfig {
+            batch_size: 4,
+            context_before: 1,
+            context_after: 1,
+            max_chars_per_request: 10000,
+            max_tokens_per_request: None,
+        };
+        let batch = create_batches(&cues, &cfg).remove(0);
+
+        let parts = split_batch(&batch, 2, &cfg);
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[1].translate_ids, vec![2, 3]);
+        assert_eq!(parts[1].cues.iter().map(|c| c.id).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert!(matches!(parts[1].cues[0].role, CueRole::Context));
+
+        let mut sizer = AdaptiveSizer::new();
+        sizer.on_failure();
+        assert_eq!(sizer.chunk_size(25), 13);
+        sizer.on_success();
+        sizer.on_success();
+        sizer.on_success();
+        assert_eq!(sizer.chunk_size(25), 25);
+    }
 }
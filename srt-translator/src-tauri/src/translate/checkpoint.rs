@@ -0,0 +1,190 @@
+//! On-disk checkpoints, so a crash or a failed batch doesn't lose finished batches.
+//!
+//! Goals:
+//! - One append-only JSONL file per job: a header line (options, source file, source hash),
+//!   then one line per translated item, written as each batch finishes
+//! - A torn last line (crash mid-write) is skipped on load
+//! - Resuming re-sends only the items that have no line yet
+//! - The source hash refuses a resume onto a file that was edited since
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::proxy_config::AppConfig;
+use crate::translate::worker::TranslationOptions;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckpointHeader {
+    pub job_id: String,
+    pub file_id: String,
+    pub file_path: String,
+    pub file_name: String,
+
+    /// `source_hash` of the file when the job started.
+    pub source_hash: String,
+    pub options: TranslationOptions,
+    pub created_at: u64,
+}
+
+/// One translated batch item (a cue, or a sentence unit covering `cues` cues).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CheckpointEntry {
+    pub id: usize,
+    pub text: String,
+    #[serde(default = "default_cues")]
+    pub cues: usize,
+}
+
+fn default_cues() -> usize {
+    1
+}
+
+/// Hash of a source file's bytes (FNV-1a, hex).
+pub fn source_hash(bytes: &[u8]) -> String {
+    format!("{:016x}", super::fnv1a(bytes))
+}
+
+fn checkpoint_dir() -> Result<PathBuf, String> {
+    Ok(AppConfig::get_config_dir()?.join("checkpoints"))
+}
+
+/// Where the checkpoint of `job_id` lives.
+pub fn checkpoint_path(job_id: &str) -> Result<PathBuf, String> {
+    Ok(checkpoint_dir()?.join(format!("{}.jsonl", job_id)))
+}
+
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    path: PathBuf,
+    /// Batch tasks append concurrently; one line group at a time.
+    lock: Arc<Mutex<()>>,
+}
+
+impl Checkpoint {
+    /// Start a checkpoint at `path`, replacing any old one.
+    pub fn create(path: &Path, header: &CheckpointHeader) -> Result<Self, String> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create checkpoint directory: {}", e))?;
+        }
+        let line = serde_json::to_string(header).map_err(|e| e.to_string())?;
+        fs::write(path, line + "\n").map_err(|e| format!("Failed to write checkpoint: {}", e))?;
+        Ok(Self { path: path.to_path_buf(), lock: Arc::new(Mutex::new(())) })
+    }
+
+    /// An existing checkpoint and its header.
+    pub fn open(path: &Path) -> Result<(Self, CheckpointHeader), String> {
+        let content = fs::read_to_string(path).map_err(|e| format!("Failed to read checkpoint: {}", e))?;
+        let header: CheckpointHeader = content
+            .lines()
+            .next()
+            .and_then(|line| serde_json::from_str(line).ok())
+            .ok_or_else(|| format!("Checkpoint {} has no valid header", path.display()))?;
+        Ok((Self { path: path.to_path_buf(), lock: Arc::new(Mutex::new(())) }, header))
+    }
+
+    /// Every checkpoint in the config dir; unreadable ones are skipped.
+    pub fn list() -> Vec<(Self, CheckpointHeader)> {
+        let Ok(read_dir) = checkpoint_dir().and_then(|dir| fs::read_dir(dir).map_err(|e| e.to_string())) else {
+            return Vec::new();
+        };
+        read_dir
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "jsonl"))
+            .filter_map(|path| Self::open(&path).ok())
+            .collect()
+    }
+
+    /// Append finished items.
+    pub fn record(&self, entries: &[CheckpointEntry]) -> Result<(), String> {
+        let mut lines = String::new();
+        for entry in entries {
+            lines.push_str(&serde_json::to_string(entry).map_err(|e| e.to_string())?);
+            lines.push('\n');
+        }
+        let _guard = self.lock.lock().unwrap();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| format!("Failed to open checkpoint: {}", e))?;
+        // Close off a torn line left by a crash, so it doesn't swallow this one
+        let mut last = [0u8; 1];
+        if file.seek(SeekFrom::End(-1)).is_ok() && file.read_exact(&mut last).is_ok() && last[0] != b'\n' {
+            lines.insert(0, '\n');
+        }
+        file.write_all(lines.as_bytes())
+            .map_err(|e| format!("Failed to write checkpoint: {}", e))
+    }
+
+    /// Items recorded so far, by id (a later line for the same id wins).
+    pub fn done(&self) -> Result<HashMap<usize, CheckpointEntry>, String> {
+        let content = fs::read_to_string(&self.path).map_err(|e| format!("Failed to read checkpoint: {}", e))?;
+        Ok(content
+            .lines()
+            .skip(1)
+            .filter_map(|line| serde_json::from_str::<CheckpointEntry>(line).ok())
+            .map(|entry| (entry.id, entry))
+            .collect())
+    }
+
+    pub fn remove(&self) -> Result<(), String> {
+        fs::remove_file(&self.path).map_err(|e| format!("Failed to delete checkpoint: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::translate::temp_path;
+
+    fn header() -> CheckpointHeader {
+        CheckpointHeader {
+            job_id: "job".into(),
+            file_id: "file".into(),
+            file_path: "/tmp/a.srt".into(),
+            file_name: "a.srt".into(),
+            source_hash: source_hash(b"1\n00:00:01,000 --> 00:00:02,000\nHi\n"),
+            options: TranslationOptions::for_tests(),
+            created_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_record_and_reopen() {
+        let path = temp_path("checkpoint-reopen");
+        let checkpoint = Checkpoint::create(&path, &header()).unwrap();
+        checkpoint.record(&[CheckpointEntry { id: 0, text: "Chào".into(), cues: 1 }]).unwrap();
+        checkpoint.record(&[CheckpointEntry { id: 2, text: "Tạm biệt".into(), cues: 2 }]).unwrap();
+
+        let (reopened, header) = Checkpoint::open(&path).unwrap();
+        assert_eq!(header.job_id, "job");
+        let done = reopened.done().unwrap();
+        assert_eq!(done.len(), 2);
+        assert_eq!(done[&2].cues, 2);
+        reopened.remove().unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_torn_line_is_skipped() {
+        let path = temp_path("checkpoint-torn");
+        let checkpoint = Checkpoint::create(&path, &header()).unwrap();
+        checkpoint.record(&[CheckpointEntry { id: 1, text: "Một".into(), cues: 1 }]).unwrap();
+        // Crash in the middle of the next write
+        OpenOptions::new().append(true).open(&path).unwrap().write_all(b"{\"id\":2,\"te").unwrap();
+
+        assert_eq!(checkpoint.done().unwrap().keys().copied().collect::<Vec<_>>(), vec![1]);
+
+        checkpoint.record(&[CheckpointEntry { id: 3, text: "Ba".into(), cues: 1 }]).unwrap();
+        let mut ids: Vec<usize> = checkpoint.done().unwrap().into_keys().collect();
+        ids.sort();
+        assert_eq!(ids, vec![1, 3]);
+        assert_ne!(source_hash(b"a"), source_hash(b"b"));
+        checkpoint.remove().unwrap();
+    }
+}
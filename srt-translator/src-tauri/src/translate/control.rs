@@ -0,0 +1,144 @@
+//! Cancel and pause switches for a running job.
+//!
+//! Goals:
+//! - One handle shared by the job commands and every spawned batch task
+//! - Cancel ends in-flight requests and backoff sleeps at once; nothing new is sent
+//! - Pause only holds back batches not yet dispatched; running ones finish normally
+
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::{sync::watch, time::Duration};
+
+use crate::translate::worker::TranslateError;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum RunState {
+    Running,
+    Paused,
+    Cancelled,
+}
+
+#[derive(Debug, Clone)]
+pub struct JobControl {
+    state: Arc<watch::Sender<RunState>>,
+}
+
+impl Default for JobControl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl JobControl {
+    pub fn new() -> Self {
+        Self { state: Arc::new(watch::channel(RunState::Running).0) }
+    }
+
+    pub fn state(&self) -> RunState {
+        *self.state.borrow()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.state() == RunState::Cancelled
+    }
+
+    /// Move `from` -> `to`; false if the job wasn't in `from`.
+    fn transition(&self, from: &[RunState], to: RunState) -> bool {
+        self.state.send_if_modified(|state| {
+            let allowed = from.contains(state);
+            if allowed {
+                *state = to;
+            }
+            allowed
+        })
+    }
+
+    pub fn cancel(&self) -> bool {
+        self.transition(&[RunState::Running, RunState::Paused], RunState::Cancelled)
+    }
+
+    pub fn pause(&self) -> bool {
+        self.transition(&[RunState::Running], RunState::Paused)
+    }
+
+    pub fn resume(&self) -> bool {
+        self.transition(&[RunState::Paused], RunState::Running)
+    }
+
+    /// Back to Running for a new run, whatever the last one ended in
+    /// (e.g. paused while its final batch was in flight).
+    pub fn reset(&self) {
+        self.state.send_replace(RunState::Running);
+    }
+
+    /// Resolves once the job is cancelled; for `select!` against a request.
+    pub async fn cancelled(&self) {
+        let mut rx = self.state.subscribe();
+        // The sender lives as long as `self`, so this can't fail
+        let _ = rx.wait_for(|state| *state == RunState::Cancelled).await;
+    }
+
+    /// Wait while paused; Err once cancelled.
+    pub async fn proceed(&self) -> Result<(), TranslateError> {
+        let mut rx = self.state.subscribe();
+        let state = rx.wait_for(|state| *state != RunState::Paused).await.map(|s| *s);
+        match state {
+            Ok(RunState::Cancelled) => Err(TranslateError::Cancelled),
+            _ => Ok(()),
+        }
+    }
+
+    /// Sleep for `duration`, cut short by a cancel.
+    pub async fn sleep(&self, duration: Duration) -> Result<(), TranslateError> {
+        tokio::select! {
+            _ = tokio::time::sleep(duration) => Ok(()),
+            _ = self.cancelled() => Err(TranslateError::Cancelled),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transitions() {
+        let control = JobControl::new();
+        assert!(!control.resume());
+        assert!(control.pause());
+        assert!(!control.pause());
+        assert!(control.resume());
+        assert!(control.cancel());
+        assert!(!control.pause() && !control.resume() && !control.cancel());
+        assert_eq!(control.state(), RunState::Cancelled);
+    }
+
+    #[tokio::test]
+    async fn test_paused_run_finishes_then_restarts() {
+        let control = JobControl::new();
+        // Paused while the last batch was in flight; the run ends anyway
+        assert!(control.pause());
+        control.reset();
+
+        assert_eq!(control.state(), RunState::Running);
+        assert!(control.proceed().await.is_ok());
+        assert!(control.pause() && control.resume());
+    }
+
+    #[tokio::test]
+    async fn test_proceed_waits_for_resume_or_cancel() {
+        let control = JobControl::new();
+        assert!(control.proceed().await.is_ok());
+
+        control.pause();
+        let waiting = tokio::spawn({
+            let control = control.clone();
+            async move { control.proceed().await }
+        });
+        tokio::task::yield_now().await;
+        assert!(!waiting.is_finished());
+        control.cancel();
+        assert!(matches!(waiting.await.unwrap(), Err(TranslateError::Cancelled)));
+        assert!(control.sleep(Duration::from_secs(60)).await.is_err());
+    }
+}
@@ -0,0 +1,436 @@
+//! TMX 1.4 and CSV/TSV exchange with CAT tools (Trados, OmegaT, ...).
+//!
+//! Goals:
+//! - Import approved source/target pairs into the translation memory
+//! - Export a finished job as pairs, with language codes and job metadata
+//! - No XML/CSV dependencies: TMX is simple enough to scan, CSV follows RFC 4180
+//! - Read files in whatever encoding the tool wrote (UTF-16 TMX, legacy code page CSV)
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::srt::decode_best_effort;
+use crate::translate::glossary::Glossary;
+use crate::translate::memory::{normalize_source, MemoryKey};
+use crate::translate::worker::Language;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ExchangeFormat {
+    Tmx,
+    Csv,
+    Tsv,
+}
+
+impl ExchangeFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "tmx" => Some(Self::Tmx),
+            "csv" => Some(Self::Csv),
+            "tsv" | "tab" => Some(Self::Tsv),
+            _ => None,
+        }
+    }
+}
+
+/// Text of an imported file, decoded like subtitle files are (BOM, UTF-8, then a guess).
+pub fn decode_exchange_file(bytes: &[u8]) -> Result<String, String> {
+    decode_best_effort(bytes).map(|decoded| decoded.text).map_err(|e| e.to_string())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TranslationPair {
+    pub source: String,
+    pub target: String,
+}
+
+/// How imported pairs are keyed in the translation memory.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryImportOptions {
+    pub source_lang: Language,
+    pub target_lang: Language,
+
+    /// Model the pairs should be reused for (memory keys include the model).
+    pub model: String,
+
+    /// Glossary of the jobs that should reuse the pairs, if any.
+    #[serde(default)]
+    pub glossary: Option<Glossary>,
+}
+
+impl MemoryImportOptions {
+    pub fn key(&self, source: &str) -> MemoryKey {
+        MemoryKey {
+            source_lang: self.source_lang.label().to_string(),
+            target_lang: self.target_lang.label().to_string(),
+            model: self.model.clone(),
+            glossary_version: self.glossary.as_ref().map(Glossary::version).unwrap_or(0),
+            speakers: String::new(),
+            source: normalize_source(source),
+        }
+    }
+}
+
+/// One exported cue.
+#[derive(Debug, Clone)]
+pub struct ExportRow {
+    /// Cue number as shown in the file.
+    pub cue: String,
+    pub source: String,
+    pub target: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExportMeta {
+    pub job_id: String,
+    pub file_name: String,
+    pub source_lang: Language,
+    pub target_lang: Language,
+    pub model: String,
+}
+
+// ============================================================================
+// TMX
+// ============================================================================
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn xml_unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let Some(semi) = rest.find(';') else {
+            break;
+        };
+        let entity = &rest[1..semi];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[semi + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Value of `name="..."` (or '...') in an opening tag.
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let at = tag.find(&format!("{}=", name))?;
+    let value = &tag[at + name.len() + 1..];
+    let quote = value.chars().next().filter(|&q| q == '"' || q == '\'')?;
+    let end = value[1..].find(quote)?;
+    Some(xml_unescape(&value[1..1 + end]))
+}
+
+/// Plain text of a `<seg>`: native-code elements (`<bpt>`, `<ept>`, `<ph>`, `<it>`)
+/// are dropped with their content, other inline tags (`<hi>`) keep their text.
+fn seg_text(seg: &str) -> String {
+    let mut text = seg.to_string();
+    for code in ["bpt", "ept", "ph", "it", "ut"] {
+        while let Some(start) = text.find(&format!("<{}", code)) {
+            let Some(open_end) = text[start..].find('>').map(|end| start + end + 1) else {
+                break;
+            };
+            // Self-closing, or the element up to its own closing tag
+            let close = format!("</{}>", code);
+            let end = if text[..open_end].ends_with("/>") {
+                open_end
+            } else {
+                text[open_end..].find(&close).map_or(open_end, |end| open_end + end + close.len())
+            };
+            text.replace_range(start..end, "");
+        }
+    }
+
+    let mut out = String::with_capacity(text.len());
+    let mut in_tag = false;
+    for c in text.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => out.push(c),
+            _ => {}
+        }
+    }
+    xml_unescape(&out)
+}
+
+/// Pairs from a TMX document, for the given languages (region subtags are ignored, "en-US" matches English).
+pub fn parse_tmx(content: &str, source: &Language, target: &Language) -> Result<Vec<TranslationPair>, String> {
+    if !content.contains("<tmx") {
+        return Err("Not a TMX file (no <tmx> element).".into());
+    }
+
+    let mut pairs = Vec::new();
+    let mut rest = content;
+    while let Some(start) = rest.find("<tu") {
+        // "<tuv" starts with "<tu" too
+        let after = &rest[start + 3..];
+        if !after.starts_with([' ', '>', '\n', '\r', '\t']) {
+            rest = after;
+            continue;
+        }
+        let Some(end) = after.find("</tu>") else {
+            break;
+        };
+        let unit = &after[..end];
+        rest = &after[end + 5..];
+
+        let (mut source_text, mut target_text) = (None, None);
+        let mut tuvs = unit;
+        while let Some(tuv_start) = tuvs.find("<tuv") {
+            let tuv = &tuvs[tuv_start..];
+            let Some(tag_end) = tuv.find('>') else {
+                break;
+            };
+            let lang = attribute(&tuv[..tag_end], "xml:lang").or_else(|| attribute(&tuv[..tag_end], "lang"));
+            let tuv_end = tuv.find("</tuv>").unwrap_or(tuv.len());
+            let body = &tuv[tag_end + 1..tuv_end];
+
+            let seg = body
+                .find("<seg")
+                .and_then(|s| body[s..].find('>').map(|g| s + g + 1))
+                .and_then(|s| body[s..].find("</seg>").map(|e| seg_text(&body[s..s + e])));
+            match (lang.as_deref().and_then(Language::from_code), seg) {
+                (Some(l), Some(text)) if l == *source && source_text.is_none() => source_text = Some(text),
+                (Some(l), Some(text)) if l == *target && target_text.is_none() => target_text = Some(text),
+                _ => {}
+            }
+            tuvs = &tuv[tuv_end.min(tuv.len())..];
+            if tuvs.starts_with("</tuv>") {
+                tuvs = &tuvs[6..];
+            }
+        }
+
+        if let (Some(source), Some(target)) = (source_text, target_text) {
+            if !source.trim().is_empty() && !target.trim().is_empty() {
+                pairs.push(TranslationPair { source, target });
+            }
+        }
+    }
+    Ok(pairs)
+}
+
+pub fn write_tmx(rows: &[ExportRow], meta: &ExportMeta) -> String {
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str("<tmx version=\"1.4\">\n");
+    out.push_str(&format!(
+        "  <header creationtool=\"SRT Translator\" creationtoolversion=\"{}\" datatype=\"plaintext\" segtype=\"block\" adminlang=\"en\" srclang=\"{}\" o-tmf=\"srt-translator\">\n",
+        env!("CARGO_PKG_VERSION"),
+        meta.source_lang.code()
+    ));
+    out.push_str(&format!("    <prop type=\"x-job\">{}</prop>\n", xml_escape(&meta.job_id)));
+    out.push_str(&format!("    <prop type=\"x-file\">{}</prop>\n", xml_escape(&meta.file_name)));
+    out.push_str(&format!("    <prop type=\"x-model\">{}</prop>\n", xml_escape(&meta.model)));
+    out.push_str("  </header>\n  <body>\n");
+    for row in rows {
+        out.push_str(&format!("    <tu tuid=\"{}\">\n", xml_escape(&row.cue)));
+        out.push_str(&format!("      <prop type=\"x-cue\">{}</prop>\n", xml_escape(&row.cue)));
+        for (lang, text) in [(&meta.source_lang, &row.source), (&meta.target_lang, &row.target)] {
+            out.push_str(&format!(
+                "      <tuv xml:lang=\"{}\"><seg>{}</seg></tuv>\n",
+                lang.code(),
+                xml_escape(text)
+            ));
+        }
+        out.push_str("    </tu>\n");
+    }
+    out.push_str("  </body>\n</tmx>\n");
+    out
+}
+
+// ============================================================================
+// CSV / TSV
+// ============================================================================
+
+/// Rows of a delimited file; quoted fields may contain delimiters, quotes ("") and newlines.
+fn parse_rows(content: &str, delimiter: char) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row: Vec<String> = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = content.trim_start_matches('\u{feff}').chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    field.push('"');
+                    chars.next();
+                }
+                '"' => in_quotes = false,
+                _ => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' if field.is_empty() => in_quotes = true,
+            '\r' => {}
+            '\n' => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            _ if c == delimiter => row.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows.retain(|r| r.iter().any(|f| !f.trim().is_empty()));
+    rows
+}
+
+/// Pairs from the first two columns; a header row ("source,target" or language codes) is skipped.
+pub fn parse_delimited(content: &str, delimiter: char) -> Result<Vec<TranslationPair>, String> {
+    let rows = parse_rows(content, delimiter);
+    let is_header = |row: &Vec<String>| {
+        row.len() >= 2
+            && row[..2].iter().all(|cell| {
+                let cell = cell.trim().to_ascii_lowercase();
+                ["source", "target", "src", "tgt"].contains(&cell.as_str()) || Language::from_code(&cell).is_some()
+            })
+    };
+
+    let mut pairs = Vec::new();
+    for (n, row) in rows.iter().enumerate() {
+        if n == 0 && is_header(row) {
+            continue;
+        }
+        if row.len() < 2 {
+            return Err(format!("Row {} has {} column(s); expected source and target.", n + 1, row.len()));
+        }
+        if !row[0].trim().is_empty() && !row[1].trim().is_empty() {
+            pairs.push(TranslationPair {
+                source: row[0].clone(),
+                target: row[1].clone(),
+            });
+        }
+    }
+    Ok(pairs)
+}
+
+pub fn delimited_field(text: &str, delimiter: char) -> String {
+    if text.contains([delimiter, '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// Source and target first (so the file imports back as is), then metadata columns.
+pub fn write_delimited(rows: &[ExportRow], meta: &ExportMeta, delimiter: char) -> String {
+    let header = [
+        meta.source_lang.code(),
+        meta.target_lang.code(),
+        "cue",
+        "file",
+        "job_id",
+        "model",
+    ];
+    let mut out = header.join(&delimiter.to_string());
+    out.push('\n');
+    for row in rows {
+        let fields = [&row.source, &row.target, &row.cue, &meta.file_name, &meta.job_id, &meta.model];
+        let line: Vec<String> = fields.iter().map(|f| delimited_field(f, delimiter)).collect();
+        out.push_str(&line.join(&delimiter.to_string()));
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meta() -> ExportMeta {
+        ExportMeta {
+            job_id: "job-1".into(),
+            file_name: "movie.srt".into(),
+            source_lang: Language::English,
+            target_lang: Language::Vietnamese,
+            model: "gpt-4o-mini".into(),
+        }
+    }
+
+    fn rows() -> Vec<ExportRow> {
+        vec![ExportRow {
+            cue: "1".into(),
+            source: "Tom & Jerry,\n\"run\"".into(),
+            target: "Tom & Jerry,\n\"chạy\"".into(),
+        }]
+    }
+
+    #[test]
+    fn test_tmx_round_trip() {
+        let tmx = write_tmx(&rows(), &meta());
+        assert!(tmx.contains("<tuv xml:lang=\"vi\"><seg>Tom &amp; Jerry,\n&quot;chạy&quot;</seg></tuv>"));
+
+        let pairs = parse_tmx(&tmx, &Language::English, &Language::Vietnamese).unwrap();
+        assert_eq!(pairs, vec![TranslationPair { source: rows()[0].source.clone(), target: rows()[0].target.clone() }]);
+    }
+
+    #[test]
+    fn test_utf16_tmx_with_bom() {
+        let tmx = "<?xml version=\"1.0\" encoding=\"UTF-16\"?><tmx version=\"1.4\"><body>\
+            <tu><tuv xml:lang=\"en\"><seg>Goodbye</seg></tuv><tuv xml:lang=\"vi\"><seg>Tạm biệt</seg></tuv></tu></body></tmx>";
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend(tmx.encode_utf16().flat_map(|unit| unit.to_le_bytes()));
+
+        let content = decode_exchange_file(&bytes).unwrap();
+        let pairs = parse_tmx(&content, &Language::English, &Language::Vietnamese).unwrap();
+        assert_eq!(pairs, vec![TranslationPair { source: "Goodbye".into(), target: "Tạm biệt".into() }]);
+    }
+
+    #[test]
+    fn test_tmx_from_cat_tool() {
+        let tmx = r#"<?xml version="1.0"?><tmx version="1.4"><header srclang="en-US"/><body>
+            <tu><tuv xml:lang="en-US"><seg>Where are you <bpt i="1">&lt;i&gt;</bpt>going<ept i="1">&lt;/i&gt;</ept>?</seg></tuv>
+            <tuv xml:lang="vi-VN"><seg>Anh đi đâu&#x20;vậy?</seg></tuv></tu>
+            <tu><tuv lang="EN"><seg>Only English</seg></tuv></tu>
+            <tu><tuv xml:lang="en"><seg>A<ph x="1"/>B<ph>c</ph>C</seg></tuv><tuv xml:lang="vi"><seg>ABC</seg></tuv></tu></body></tmx>"#;
+        let pairs = parse_tmx(tmx, &Language::English, &Language::Vietnamese).unwrap();
+        assert_eq!(pairs, vec![
+            TranslationPair { source: "Where are you going?".into(), target: "Anh đi đâu vậy?".into() },
+            TranslationPair { source: "ABC".into(), target: "ABC".into() },
+        ]);
+    }
+
+    #[test]
+    fn test_csv_round_trip_with_header() {
+        let csv = write_delimited(&rows(), &meta(), ',');
+        assert!(csv.starts_with("en,vi,cue,file,job_id,model\n"));
+        let pairs = parse_delimited(&csv, ',').unwrap();
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(pairs[0].target, "Tom & Jerry,\n\"chạy\"");
+
+        let tsv = parse_delimited("Hello\tXin chào\nBye\tTạm biệt\n", '\t').unwrap();
+        assert_eq!(tsv.len(), 2);
+    }
+}
@@ -0,0 +1,215 @@
+//! Glossaries: required renderings for names and show-specific terms.
+//!
+//! Goals:
+//! - Source term -> required target, with case-sensitive and whole-word options
+//! - A do-not-translate list (names, brands) that must come back unchanged
+//! - Only terms that occur in a batch go into its prompt
+//! - Check every translation afterwards and report (or retry) cues that broke a term
+//! - Save named glossaries in the config dir so each show keeps its own
+
+use serde::{Deserialize, Serialize};
+
+use crate::translate::library;
+
+pub const GLOSSARY_HEADER: &str = "GLOSSARY (always translate these exactly as given):";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GlossaryTerm {
+    pub source: String,
+    pub target: String,
+    #[serde(default)]
+    pub case_sensitive: bool,
+    #[serde(default = "default_whole_word")]
+    pub whole_word: bool,
+}
+
+fn default_whole_word() -> bool {
+    true
+}
+
+/// What to do with a cue whose translation broke a term.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum TermEnforcement {
+    /// Keep the translation and report the cue.
+    #[default]
+    Flag,
+    /// Ask the model once more, then report what's still wrong.
+    Retry,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Glossary {
+    pub name: String,
+    #[serde(default)]
+    pub terms: Vec<GlossaryTerm>,
+
+    /// Words that must appear in the translation exactly as in the source.
+    #[serde(default)]
+    pub do_not_translate: Vec<String>,
+    #[serde(default)]
+    pub enforcement: TermEnforcement,
+}
+
+/// A locked term missing from one cue's translation.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TermViolation {
+    pub cue_id: usize,
+    pub source: String,
+    pub expected: String,
+}
+
+impl Glossary {
+    /// Stable hash of the glossary contents (FNV-1a), for translation memory keys.
+    pub fn version(&self) -> u64 {
+        let json = serde_json::to_string(self).unwrap_or_default();
+        super::fnv1a(json.as_bytes())
+    }
+
+    /// (source, required target) pairs that occur in `text`.
+    fn rules_in<'a>(&'a self, text: &str) -> impl Iterator<Item = (&'a str, &'a str, bool, bool)> + 'a {
+        let text = text.to_string();
+        let terms = self
+            .terms
+            .iter()
+            .map(|t| (t.source.as_str(), t.target.as_str(), t.case_sensitive, t.whole_word));
+        let keep = self.do_not_translate.iter().map(|w| (w.as_str(), w.as_str(), true, true));
+        terms
+            .chain(keep)
+            .filter(move |(source, _, case_sensitive, whole_word)| contains_term(&text, source, *case_sensitive, *whole_word))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty() && self.do_not_translate.is_empty()
+    }
+
+    /// Prompt lines for the terms that occur in `text`.
+    pub fn prompt_lines(&self, text: &str) -> Vec<String> {
+        self.rules_in(text)
+            .map(|(source, target, _, _)| {
+                if source == target {
+                    format!("- {} (keep as is)", source)
+                } else {
+                    format!("- {} → {}", source, target)
+                }
+            })
+            .collect()
+    }
+
+    /// Prompt section listing the terms that occur in `texts`; empty when none do.
+    pub fn prompt_section<'a>(&self, texts: impl IntoIterator<Item = &'a str>) -> String {
+        let mut lines: Vec<String> = Vec::new();
+        for line in texts.into_iter().flat_map(|text| self.prompt_lines(text)) {
+            if !lines.contains(&line) {
+                lines.push(line);
+            }
+        }
+        if lines.is_empty() {
+            return String::new();
+        }
+        format!("{}\n{}\n\n", GLOSSARY_HEADER, lines.join("\n"))
+    }
+
+    /// Terms in `source` that `translation` doesn't render as required.
+    pub fn check(&self, cue_id: usize, source: &str, translation: &str) -> Vec<TermViolation> {
+        self.rules_in(source)
+            .filter(|(_, target, case_sensitive, whole_word)| !contains_term(translation, target, *case_sensitive, *whole_word))
+            .map(|(source, target, _, _)| TermViolation {
+                cue_id,
+                source: source.to_string(),
+                expected: target.to_string(),
+            })
+            .collect()
+    }
+}
+
+/// Whether `needle` occurs in `haystack`; `whole_word` is ignored for unspaced scripts.
+pub fn contains_term(haystack: &str, needle: &str, case_sensitive: bool, whole_word: bool) -> bool {
+    if needle.trim().is_empty() {
+        return false;
+    }
+    let fold = |s: &str| -> Vec<char> {
+        if case_sensitive {
+            s.chars().collect()
+        } else {
+            s.chars().flat_map(char::to_lowercase).collect()
+        }
+    };
+    let (hay, needle) = (fold(haystack), fold(needle));
+    let whole_word = whole_word && !needle.iter().any(|&c| c as u32 >= 0x2E80);
+
+    (0..hay.len().saturating_sub(needle.len()) + 1)
+        .filter(|&i| hay.len() >= needle.len() && hay[i..i + needle.len()] == needle[..])
+        .any(|i| {
+            if !whole_word {
+                return true;
+            }
+            let before_ok = i == 0 || !hay[i - 1].is_alphanumeric();
+            let after_ok = hay.get(i + needle.len()).is_none_or(|c| !c.is_alphanumeric());
+            before_ok && after_ok
+        })
+}
+
+const LIBRARY_KIND: &str = "glossaries";
+
+pub fn list_glossaries() -> Result<Vec<Glossary>, String> {
+    let mut glossaries: Vec<Glossary> = library::list(LIBRARY_KIND)?;
+    glossaries.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(glossaries)
+}
+
+pub fn save_glossary(glossary: &Glossary) -> Result<(), String> {
+    library::save(LIBRARY_KIND, &glossary.name, glossary)
+}
+
+pub fn delete_glossary(name: &str) -> Result<(), String> {
+    library::delete(LIBRARY_KIND, name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glossary() -> Glossary {
+        Glossary {
+            name: "Show".into(),
+            terms: vec![GlossaryTerm {
+                source: "Winterfell".into(),
+                target: "Đông Thành".into(),
+                case_sensitive: false,
+                whole_word: true,
+            }],
+            do_not_translate: vec!["Stark".into()],
+            enforcement: TermEnforcement::Flag,
+        }
+    }
+
+    #[test]
+    fn test_whole_word_and_case() {
+        assert!(contains_term("Back to winterfell!", "Winterfell", false, true));
+        assert!(!contains_term("Winterfells", "Winterfell", false, true));
+        assert!(!contains_term("stark", "Stark", true, true));
+        assert!(contains_term("我们去北境吧", "北境", false, true));
+    }
+
+    #[test]
+    fn test_prompt_lists_only_present_terms() {
+        let g = glossary();
+        assert_eq!(g.prompt_section(["Hello there"]), "");
+        assert_eq!(
+            g.prompt_section(["Lord Stark of Winterfell", "Stark again"]),
+            "GLOSSARY (always translate these exactly as given):\n- Winterfell → Đông Thành\n- Stark (keep as is)\n\n"
+        );
+    }
+
+    #[test]
+    fn test_check_reports_broken_terms() {
+        let g = glossary();
+        let violations = g.check(4, "Lord Stark of Winterfell", "Lãnh chúa Stark của Winterfell");
+        assert_eq!(
+            violations,
+            vec![TermViolation { cue_id: 4, source: "Winterfell".into(), expected: "Đông Thành".into() }]
+        );
+        assert!(g.check(4, "Lord Stark of Winterfell", "Lãnh chúa Stark của Đông Thành").is_empty());
+        assert_ne!(g.version(), Glossary::default().version());
+    }
+}
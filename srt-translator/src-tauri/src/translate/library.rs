@@ -0,0 +1,59 @@
+//! Named JSON documents in the config dir (glossaries, speaker tables), one file each.
+//!
+//! Goals:
+//! - `<config>/srt-translator/<kind>/<name>.json`, with names made filename-safe
+//! - Unreadable files are skipped when listing rather than failing the whole list
+
+use serde::{de::DeserializeOwned, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::proxy_config::AppConfig;
+
+fn library_dir(kind: &str) -> Result<PathBuf, String> {
+    Ok(AppConfig::get_config_dir()?.join(kind))
+}
+
+/// File for `name`: anything but letters, digits, '-' and '_' becomes '_'.
+fn library_path(kind: &str, name: &str) -> Result<PathBuf, String> {
+    if name.trim().is_empty() {
+        return Err("Name is empty.".into());
+    }
+    let file: String = name
+        .trim()
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    Ok(library_dir(kind)?.join(format!("{}.json", file)))
+}
+
+pub fn list<T: DeserializeOwned>(kind: &str) -> Result<Vec<T>, String> {
+    let Ok(read_dir) = fs::read_dir(library_dir(kind)?) else {
+        return Ok(Vec::new());
+    };
+    let mut paths: Vec<PathBuf> = read_dir
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+    Ok(paths
+        .into_iter()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .filter_map(|content| serde_json::from_str(&content).ok())
+        .collect())
+}
+
+pub fn save<T: Serialize>(kind: &str, name: &str, value: &T) -> Result<(), String> {
+    let path = library_path(kind, name)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create {} directory: {}", kind, e))?;
+    }
+    let content = serde_json::to_string_pretty(value).map_err(|e| format!("Failed to serialize: {}", e))?;
+    fs::write(&path, content).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+pub fn delete(kind: &str, name: &str) -> Result<(), String> {
+    let path = library_path(kind, name)?;
+    fs::remove_file(&path).map_err(|e| format!("Failed to delete {}: {}", path.display(), e))
+}
@@ -0,0 +1,455 @@
+//! Translation memory: translations we already paid for, reused across jobs.
+//!
+//! Goals:
+//! - Key by normalized source text + language pair + model + glossary version
+//! - Persist as an append-only JSON-lines file in the config dir (later lines win)
+//! - Work offline: a job whose cues are all cached never calls the API
+//! - Suggest near matches (edit distance) as reference examples for new lines; a length index
+//!   and a bigram pre-score keep that to a few edit distances per line, whatever the memory size
+//! - Inspect, prune and clear from the UI (see `commands::memory`)
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::proxy_config::AppConfig;
+
+const MEMORY_FILE: &str = "translation-memory.jsonl";
+/// Entries looked at per fuzzy lookup, nearest length first, then newest first.
+const MAX_FUZZY_SCANNED: usize = 2_000;
+/// Of those, how many (best bigram overlap) get a full edit distance.
+const MAX_FUZZY_SCORED: usize = 50;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MemoryKey {
+    pub source_lang: String,
+    pub target_lang: String,
+    pub model: String,
+
+    /// Changes whenever the glossary does, so old translations don't bypass new terms.
+    #[serde(default)]
+    pub glossary_version: u64,
+
+    /// Speaker → listener when a speaker table is in use; the same line can be
+    /// translated differently depending on who says it to whom.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub speakers: String,
+
+    /// Normalized source text (see `normalize_source`).
+    pub source: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryEntry {
+    #[serde(flatten)]
+    pub key: MemoryKey,
+    pub translation: String,
+
+    /// Unix seconds.
+    pub created_at: u64,
+
+    /// Position in the log, later = newer; the file keeps it as line order.
+    #[serde(skip)]
+    seq: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryStats {
+    pub entries: usize,
+    pub file_path: Option<String>,
+    pub file_bytes: u64,
+}
+
+/// A prior translation similar to a cue's source text.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FuzzyMatch {
+    pub source: String,
+    pub translation: String,
+
+    /// Similarity 0..=100 (100 = same normalized text).
+    pub percent: u8,
+}
+
+/// Which entries `prune` removes; unset fields match everything.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PruneFilter {
+    pub older_than_days: Option<u64>,
+    pub model: Option<String>,
+    pub target_lang: Option<String>,
+}
+
+/// Trim each line and collapse runs of whitespace, so spacing differences still hit.
+pub fn normalize_source(text: &str) -> String {
+    text.replace("\r\n", "\n")
+        .split('\n')
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// 1 - (character edit distance / longer length).
+pub fn similarity(a: &str, b: &str) -> f32 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    1.0 - previous[b.len()] as f32 / longest as f32
+}
+
+/// Character bigrams, sorted, for a cheap overlap score before the edit distance.
+fn bigrams(text: &str) -> Vec<(char, char)> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut pairs: Vec<(char, char)> = chars.windows(2).map(|w| (w[0], w[1])).collect();
+    pairs.sort_unstable();
+    pairs
+}
+
+/// Dice coefficient of two sorted bigram lists.
+fn bigram_overlap(a: &[(char, char)], b: &[(char, char)]) -> f32 {
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    let (mut i, mut j, mut shared) = (0, 0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                shared += 1;
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    2.0 * shared as f32 / (a.len() + b.len()) as f32
+}
+
+pub fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+#[derive(Debug, Default)]
+pub struct TranslationMemory {
+    /// None keeps the memory in RAM only (e.g. when the config dir is unavailable).
+    path: Option<PathBuf>,
+    entries: HashMap<MemoryKey, MemoryEntry>,
+    /// Keys by source length in chars, oldest first within a length, for fuzzy lookups.
+    by_length: BTreeMap<usize, Vec<MemoryKey>>,
+    next_seq: u64,
+}
+
+impl TranslationMemory {
+    /// Load (or start) the memory file at `path`. Unreadable lines are skipped.
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let mut memory = Self { path: Some(path.to_path_buf()), ..Self::default() };
+        if path.exists() {
+            let content = fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read translation memory: {}", e))?;
+            for line in content.lines() {
+                if let Ok(mut entry) = serde_json::from_str::<MemoryEntry>(line) {
+                    entry.seq = memory.next_seq;
+                    memory.next_seq += 1;
+                    memory.entries.insert(entry.key.clone(), entry);
+                }
+            }
+        }
+        memory.reindex();
+        Ok(memory)
+    }
+
+    /// Live entries in log order, oldest first.
+    fn in_log_order(&self) -> Vec<&MemoryEntry> {
+        let mut entries: Vec<&MemoryEntry> = self.entries.values().collect();
+        entries.sort_by_key(|e| e.seq);
+        entries
+    }
+
+    fn reindex(&mut self) {
+        let mut by_length: BTreeMap<usize, Vec<MemoryKey>> = BTreeMap::new();
+        for entry in self.in_log_order() {
+            by_length.entry(entry.key.source.chars().count()).or_default().push(entry.key.clone());
+        }
+        self.by_length = by_length;
+    }
+
+    /// The memory in the app's config dir.
+    pub fn open_default() -> Result<Self, String> {
+        Self::open(&AppConfig::get_config_dir()?.join(MEMORY_FILE))
+    }
+
+    pub fn lookup(&self, key: &MemoryKey) -> Option<&str> {
+        self.entries.get(key).map(|e| e.translation.as_str())
+    }
+
+    /// Up to `limit` entries for the same language pair (any model or glossary) whose source
+    /// is at least `threshold` (0..1) similar to `key.source`, best first.
+    pub fn fuzzy_matches(&self, key: &MemoryKey, threshold: f32, limit: usize) -> Vec<FuzzyMatch> {
+        let len = key.source.chars().count();
+        // Length alone bounds the score, so only lengths within the threshold can match
+        let shortest = (len as f32 * threshold).ceil() as usize;
+        let longest = if threshold > 0.0 { (len as f32 / threshold).floor() as usize } else { usize::MAX };
+        let mut lengths: Vec<(&usize, &Vec<MemoryKey>)> = self.by_length.range(shortest..=longest).collect();
+        lengths.sort_by_key(|(other, _)| other.abs_diff(len));
+
+        let wanted = bigrams(&key.source);
+        let mut candidates: Vec<(f32, &MemoryKey)> = lengths
+            .into_iter()
+            // Newest first within a length
+            .flat_map(|(_, keys)| keys.iter().rev())
+            .filter(|k| k.source_lang == key.source_lang && k.target_lang == key.target_lang)
+            .take(MAX_FUZZY_SCANNED)
+            .map(|k| (bigram_overlap(&wanted, &bigrams(&k.source)), k))
+            .collect();
+        candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+        candidates.truncate(MAX_FUZZY_SCORED);
+
+        let mut found: Vec<(f32, &MemoryEntry)> = candidates
+            .into_iter()
+            .filter_map(|(_, k)| self.entries.get(k))
+            .map(|e| (similarity(&key.source, &e.key.source), e))
+            .filter(|(score, _)| *score >= threshold)
+            .collect();
+        found.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| b.1.created_at.cmp(&a.1.created_at)));
+
+        let mut matches: Vec<FuzzyMatch> = Vec::new();
+        for (score, entry) in found {
+            // The same source under several models/glossaries is one suggestion
+            if matches.iter().any(|m| m.source == entry.key.source) {
+                continue;
+            }
+            matches.push(FuzzyMatch {
+                source: entry.key.source.clone(),
+                translation: entry.translation.clone(),
+                percent: (score * 100.0).floor() as u8,
+            });
+            if matches.len() == limit {
+                break;
+            }
+        }
+        matches
+    }
+
+    /// Add translations and append them to the file.
+    pub fn insert_many(&mut self, items: impl IntoIterator<Item = (MemoryKey, String)>) -> Result<(), String> {
+        let created_at = now_secs();
+        let mut lines = String::new();
+        for (key, translation) in items {
+            let entry = MemoryEntry { key, translation, created_at, seq: self.next_seq };
+            self.next_seq += 1;
+            lines.push_str(&serde_json::to_string(&entry).map_err(|e| e.to_string())?);
+            lines.push('\n');
+            let key = entry.key.clone();
+            let bucket = self.by_length.entry(key.source.chars().count()).or_default();
+            if self.entries.insert(entry.key.clone(), entry).is_some() {
+                // Replaced: it's now the newest of its length
+                bucket.retain(|k| *k != key);
+            }
+            bucket.push(key);
+        }
+
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if lines.is_empty() {
+            return Ok(());
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create config directory: {}", e))?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Failed to open translation memory: {}", e))?;
+        file.write_all(lines.as_bytes())
+            .map_err(|e| format!("Failed to write translation memory: {}", e))
+    }
+
+    pub fn stats(&self) -> MemoryStats {
+        MemoryStats {
+            entries: self.entries.len(),
+            file_path: self.path.as_ref().map(|p| p.to_string_lossy().to_string()),
+            file_bytes: self.path.as_ref().and_then(|p| fs::metadata(p).ok()).map(|m| m.len()).unwrap_or(0),
+        }
+    }
+
+    /// Entries whose source or translation contains `query` (all when None), newest first.
+    pub fn search(&self, query: Option<&str>, limit: usize) -> Vec<MemoryEntry> {
+        let query = query.map(str::to_lowercase);
+        let mut found: Vec<MemoryEntry> = self
+            .entries
+            .values()
+            .filter(|e| {
+                query.as_deref().is_none_or(|q| {
+                    e.key.source.to_lowercase().contains(q) || e.translation.to_lowercase().contains(q)
+                })
+            })
+            .cloned()
+            .collect();
+        found.sort_by(|a, b| b.created_at.cmp(&a.created_at).then_with(|| a.key.source.cmp(&b.key.source)));
+        found.truncate(limit);
+        found
+    }
+
+    /// Remove matching entries and compact the file; returns how many were removed.
+    pub fn prune(&mut self, filter: &PruneFilter) -> Result<usize, String> {
+        let cutoff = filter.older_than_days.map(|days| now_secs().saturating_sub(days * 86_400));
+        let before = self.entries.len();
+        self.entries.retain(|_, e| {
+            let matches = cutoff.is_none_or(|c| e.created_at < c)
+                && filter.model.as_ref().is_none_or(|m| &e.key.model == m)
+                && filter.target_lang.as_ref().is_none_or(|l| &e.key.target_lang == l);
+            !matches
+        });
+        self.reindex();
+        self.rewrite()?;
+        Ok(before - self.entries.len())
+    }
+
+    pub fn clear(&mut self) -> Result<(), String> {
+        self.entries.clear();
+        self.by_length.clear();
+        self.rewrite()
+    }
+
+    /// Write the file from scratch with one line per live entry.
+    fn rewrite(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut lines = String::new();
+        for entry in self.in_log_order() {
+            lines.push_str(&serde_json::to_string(entry).map_err(|e| e.to_string())?);
+            lines.push('\n');
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create config directory: {}", e))?;
+        }
+        fs::write(path, lines).map_err(|e| format!("Failed to write translation memory: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::translate::temp_path;
+
+    fn key(source: &str) -> MemoryKey {
+        MemoryKey {
+            source_lang: "English".into(),
+            target_lang: "Vietnamese".into(),
+            model: "gpt-4o-mini".into(),
+            glossary_version: 0,
+            speakers: String::new(),
+            source: normalize_source(source),
+        }
+    }
+
+    #[test]
+    fn test_normalize_source() {
+        assert_eq!(normalize_source("  Hello   there \r\n\r\n world "), "Hello there\nworld");
+    }
+
+    #[test]
+    fn test_round_trip_through_file() {
+        let path = temp_path("tm-roundtrip");
+        let mut memory = TranslationMemory::open(&path).unwrap();
+        memory.insert_many([(key("Hello"), "Xin chào".to_string())]).unwrap();
+        memory.insert_many([(key("Hello"), "Chào bạn".to_string())]).unwrap();
+
+        let reopened = TranslationMemory::open(&path).unwrap();
+        assert_eq!(reopened.lookup(&key(" Hello ")), Some("Chào bạn"));
+        assert_eq!(reopened.stats().entries, 1);
+
+        let mut other_model = key("Hello");
+        other_model.model = "claude-3-5-haiku".into();
+        assert_eq!(reopened.lookup(&other_model), None);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_prune_compacts_file() {
+        let path = temp_path("tm-prune");
+        let mut memory = TranslationMemory::open(&path).unwrap();
+        let mut claude = key("Bye");
+        claude.model = "claude-3-5-haiku".into();
+        memory.insert_many([(key("Hello"), "Xin chào".to_string()), (claude, "Tạm biệt".to_string())]).unwrap();
+
+        let filter = PruneFilter { model: Some("claude-3-5-haiku".into()), ..Default::default() };
+        assert_eq!(memory.prune(&filter).unwrap(), 1);
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 1);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_length_index_stays_in_log_order() {
+        let path = temp_path("tm-order");
+        let mut memory = TranslationMemory::open(&path).unwrap();
+        let sources: Vec<String> = (0..8).map(|i| format!("Line {}", i)).collect();
+        for source in &sources {
+            memory.insert_many([(key(source), "x".to_string())]).unwrap();
+        }
+        let mut claude = key("Bye");
+        claude.model = "claude-3-5-haiku".into();
+        memory.insert_many([(claude, "Tạm biệt".to_string()), (key("Line 0"), "y".to_string())]).unwrap();
+        memory.prune(&PruneFilter { model: Some("claude-3-5-haiku".into()), ..Default::default() }).unwrap();
+
+        let mut expected: Vec<MemoryKey> = sources[1..].iter().map(|s| key(s)).collect();
+        expected.push(key("Line 0"));
+        assert_eq!(memory.by_length[&6], expected);
+        let reopened = TranslationMemory::open(&path).unwrap();
+        assert_eq!(reopened.by_length[&6], expected);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_fuzzy_matches_near_duplicates() {
+        let mut memory = TranslationMemory::default();
+        memory
+            .insert_many([
+                (key("Where are you going?"), "Anh đi đâu vậy?".to_string()),
+                (key("Where were you?"), "Anh đã ở đâu?".to_string()),
+            ])
+            .unwrap();
+
+        let matches = memory.fuzzy_matches(&key("Where are you going, Tom?"), 0.75, 3);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].translation, "Anh đi đâu vậy?");
+        assert_eq!(matches[0].percent, 80);
+        assert!(memory.fuzzy_matches(&key("Good morning"), 0.75, 3).is_empty());
+    }
+
+    #[test]
+    fn test_fuzzy_scan_is_capped() {
+        let mut memory = TranslationMemory::default();
+        // More same-length entries than one lookup scans
+        let filler = (0..MAX_FUZZY_SCANNED * 2).map(|i| (key(&format!("Filler line no {:05}", i)), "x".to_string()));
+        memory.insert_many(filler).unwrap();
+        memory.insert_many([(key("Where are you going?"), "Anh đi đâu vậy?".to_string())]).unwrap();
+
+        let matches = memory.fuzzy_matches(&key("Where are you going?!"), 0.9, 1);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].translation, "Anh đi đâu vậy?");
+        memory.clear().unwrap();
+        assert!(memory.fuzzy_matches(&key("Where are you going?!"), 0.9, 1).is_empty());
+    }
+}
@@ -1,2 +1,25 @@
 pub mod batcher;
+pub mod checkpoint;
+pub mod control;
+pub mod exchange;
+pub mod glossary;
+pub mod library;
+pub mod memory;
+pub mod segment;
+pub mod speakers;
+pub mod tokens;
 pub mod worker;
+
+/// A fresh path under the system temp dir for one test, unique per `name` and test run.
+#[cfg(test)]
+pub fn temp_path(name: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("srt-test-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let _ = std::fs::remove_dir_all(&path);
+    path
+}
+
+/// FNV-1a hash of `bytes`: stable across runs and builds, for keys and change detection.
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325u64, |hash, &b| (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3))
+}
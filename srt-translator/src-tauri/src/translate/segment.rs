@@ -0,0 +1,167 @@
+//! Sentence units: cues that together form one sentence, translated as one item.
+//!
+//! Goals:
+//! - Group consecutive cues until one ends a sentence ("I told him that" / "he should leave.")
+//! - Never join across a long pause or more than `MAX_UNIT_CUES` cues
+//! - Give the batcher one cue per unit, so prompts and parsing stay unchanged
+//! - Spread each unit's translation back over its cues by duration
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::srt::{restructure::split_text_weighted, SrtCue};
+
+/// Most cues joined into one unit.
+const MAX_UNIT_CUES: usize = 4;
+/// Longest pause (ms) a sentence may continue across.
+const MAX_UNIT_GAP_MS: u64 = 1500;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SentenceUnit {
+    /// Ids of the cues in this unit, in order.
+    pub cue_ids: Vec<usize>,
+}
+
+/// Whether `text` ends a sentence (ignoring trailing tags and quotes).
+fn ends_sentence(text: &str) -> bool {
+    let visible = crate::srt::qa::visible_text(text);
+    let trimmed = visible.trim_end().trim_end_matches(['"', '\'', '”', '’', ')', '」', '』']);
+    trimmed.is_empty() || trimmed.ends_with(['.', '!', '?', '…', '。', '！', '？', ':', ';', '♪'])
+}
+
+/// Group `cues` into sentence units covering every cue exactly once, in order.
+pub fn segment_sentences(cues: &[SrtCue]) -> Vec<SentenceUnit> {
+    let mut units: Vec<SentenceUnit> = Vec::new();
+    let mut current: Vec<usize> = Vec::new();
+
+    for (pos, cue) in cues.iter().enumerate() {
+        current.push(cue.id);
+
+        let last_line = cue.text_lines.last().map(String::as_str).unwrap_or("");
+        let next_starts_late = cues.get(pos + 1).is_none_or(|next| {
+            next.start.to_millis().saturating_sub(cue.end.to_millis()) > MAX_UNIT_GAP_MS
+        });
+        // A dash line starts a new speaker, not a continuation
+        let next_is_dialogue = cues
+            .get(pos + 1)
+            .and_then(|next| next.text_lines.first())
+            .is_some_and(|line| line.trim_start().starts_with('-'));
+
+        if ends_sentence(last_line) || next_starts_late || next_is_dialogue || current.len() >= MAX_UNIT_CUES {
+            units.push(SentenceUnit { cue_ids: std::mem::take(&mut current) });
+        }
+    }
+    units
+}
+
+/// One cue per unit (id = unit index) holding the unit's text, for the batcher.
+pub fn unit_cues(cues: &[SrtCue], units: &[SentenceUnit]) -> Vec<SrtCue> {
+    units
+        .iter()
+        .enumerate()
+        .map(|(unit_id, unit)| {
+            let first = &cues[unit.cue_ids[0]];
+            let last = &cues[*unit.cue_ids.last().expect("units are never empty")];
+
+            let mut cue = first.clone();
+            cue.id = unit_id;
+            if unit.cue_ids.len() > 1 {
+                let text: Vec<String> = unit.cue_ids.iter().flat_map(|&id| cues[id].text_lines.iter().map(|l| l.trim().to_string())).collect();
+                cue.text_lines = vec![text.join(" ")];
+                cue.set_times(first.start.clone(), last.end.clone());
+            }
+            cue
+        })
+        .collect()
+}
+
+/// Spread each unit's translation over its cues, in proportion to the cues' durations.
+pub fn redistribute(
+    cues: &[SrtCue],
+    units: &[SentenceUnit],
+    unit_translations: &HashMap<usize, String>,
+) -> HashMap<usize, String> {
+    let mut translated = HashMap::with_capacity(cues.len());
+    for (unit_id, unit) in units.iter().enumerate() {
+        let Some(text) = unit_translations.get(&unit_id) else {
+            continue;
+        };
+        if unit.cue_ids.len() == 1 {
+            translated.insert(unit.cue_ids[0], text.clone());
+            continue;
+        }
+
+        let weights: Vec<u64> = unit
+            .cue_ids
+            .iter()
+            .map(|&id| cues[id].end.to_millis().saturating_sub(cues[id].start.to_millis()).max(1))
+            .collect();
+        let mut pieces = split_text_weighted(text, &weights);
+        // Too few words to go around: the whole sentence stays on the first cue and the rest
+        // are left blank, rather than split mid-word or show untranslated source text
+        if pieces.len() < unit.cue_ids.len() {
+            pieces = vec![text.clone()];
+        }
+        pieces.resize(unit.cue_ids.len(), String::new());
+        for (&id, piece) in unit.cue_ids.iter().zip(pieces) {
+            translated.insert(id, piece);
+        }
+    }
+    translated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::srt::parse_srt_bytes;
+
+    const SAMPLE: &[u8] = b"1\n00:00:01,000 --> 00:00:02,000\nI told him that\n\n2\n00:00:02,100 --> 00:00:05,100\nhe should leave.\n\n3\n00:00:06,000 --> 00:00:07,000\n- Why?\n\n4\n00:00:07,100 --> 00:00:08,000\n- Because\n\n5\n00:00:20,000 --> 00:00:21,000\nlater\n";
+
+    #[test]
+    fn test_segments_sentences_across_cues() {
+        let doc = parse_srt_bytes(SAMPLE).unwrap();
+        let units = segment_sentences(&doc.cues);
+        let ids: Vec<Vec<usize>> = units.into_iter().map(|u| u.cue_ids).collect();
+        // Dialogue dashes and the long pause before cue 5 end units too
+        assert_eq!(ids, vec![vec![0, 1], vec![2], vec![3], vec![4]]);
+    }
+
+    #[test]
+    fn test_unit_cue_joins_text_and_times() {
+        let doc = parse_srt_bytes(SAMPLE).unwrap();
+        let units = segment_sentences(&doc.cues);
+        let cues = unit_cues(&doc.cues, &units);
+        assert_eq!(cues.len(), 4);
+        assert_eq!(cues[0].text_lines, vec!["I told him that he should leave."]);
+        assert_eq!(cues[0].timing_line, "00:00:01,000 --> 00:00:05,100");
+        assert_eq!(cues[1].id, 1);
+    }
+
+    #[test]
+    fn test_redistribute_by_duration() {
+        let doc = parse_srt_bytes(SAMPLE).unwrap();
+        let units = segment_sentences(&doc.cues);
+        let unit_translations = HashMap::from([
+            (0, "Tôi đã bảo anh ta rằng anh ta nên rời khỏi đây ngay.".to_string()),
+            (1, "- Tại sao?".to_string()),
+        ]);
+
+        let translated = redistribute(&doc.cues, &units, &unit_translations);
+        // Cue 1 lasts 1s, cue 2 lasts 3s: about a quarter of the text goes first
+        assert_eq!(translated[&0], "Tôi đã bảo anh");
+        assert_eq!(translated[&1], "ta rằng anh ta nên rời khỏi đây ngay.");
+        assert_eq!(translated[&2], "- Tại sao?");
+        assert!(!translated.contains_key(&3));
+    }
+
+    #[test]
+    fn test_short_translation_stays_on_first_cue() {
+        let doc = parse_srt_bytes(SAMPLE).unwrap();
+        let units = segment_sentences(&doc.cues);
+        let unit_translations = HashMap::from([(0, "Ừ.".to_string())]);
+
+        let translated = redistribute(&doc.cues, &units, &unit_translations);
+        assert_eq!(translated[&0], "Ừ.");
+        assert_eq!(translated[&1], "");
+    }
+}
@@ -0,0 +1,360 @@
+//! Speakers: who says each line and to whom, so pronouns and honorifics stay consistent.
+//!
+//! Goals:
+//! - Detect speakers from ASS actor fields and SRT `NAME:` prefixes; dash lines start a new speaker
+//! - Guess the listener as the last other speaker in the same scene
+//! - A per-project speaker table: gender, register, self-reference, and fixed forms of
+//!   address between pairs (Vietnamese anh/em, Japanese -san/-sama)
+//! - Every batch's prompt gets the profiles and address forms of the speakers it contains
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::srt::{qa::visible_text, SrtCue};
+use crate::translate::library;
+
+/// Longest pause (ms) after which an unlabelled cue is still the previous speaker.
+const MAX_CONTINUE_GAP_MS: u64 = 2000;
+/// A pause (ms) this long ends the scene: the next speaker has no known listener.
+const SCENE_GAP_MS: u64 = 10_000;
+/// Longest `NAME:` prefix, in characters.
+const MAX_NAME_CHARS: usize = 24;
+
+/// Titles of the parts of the speaker section.
+pub const SPEAKER_HEADERS: [&str; 3] = [
+    "SPEAKERS (keep each character's voice, pronouns and forms of address consistent):",
+    "FORMS OF ADDRESS (always use these):",
+    "WHO SPEAKS EACH ITEM (speaker → listener):",
+];
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Gender {
+    Female,
+    Male,
+    #[default]
+    Unspecified,
+}
+
+/// Register a character speaks in.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Formality {
+    Formal,
+    #[default]
+    Neutral,
+    Casual,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SpeakerProfile {
+    pub name: String,
+    #[serde(default)]
+    pub gender: Gender,
+    #[serde(default)]
+    pub formality: Formality,
+
+    /// How the character refers to themselves by default ("tôi", "boku", "watakushi").
+    #[serde(default)]
+    pub self_reference: Option<String>,
+    #[serde(default)]
+    pub notes: String,
+}
+
+/// How `speaker` addresses `listener` (one direction; add the reverse separately).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AddressForm {
+    pub speaker: String,
+    pub listener: String,
+
+    /// What the speaker calls the listener ("anh", "Tanaka-san").
+    pub address: String,
+
+    /// What the speaker calls themselves when talking to this listener ("em").
+    #[serde(default)]
+    pub self_reference: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SpeakerTable {
+    pub name: String,
+    #[serde(default)]
+    pub speakers: Vec<SpeakerProfile>,
+    #[serde(default)]
+    pub addresses: Vec<AddressForm>,
+}
+
+/// Who says one cue, and to whom if known.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Turn {
+    pub speaker: String,
+    pub listener: Option<String>,
+}
+
+impl Turn {
+    /// Short label for memory keys and prompts: "Lan → Minh" or "Lan".
+    pub fn label(&self) -> String {
+        match &self.listener {
+            Some(listener) => format!("{} → {}", self.speaker, listener),
+            None => self.speaker.clone(),
+        }
+    }
+}
+
+impl SpeakerTable {
+    pub fn profile(&self, name: &str) -> Option<&SpeakerProfile> {
+        self.speakers.iter().find(|p| p.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn address(&self, speaker: &str, listener: &str) -> Option<&AddressForm> {
+        self.addresses
+            .iter()
+            .find(|a| a.speaker.eq_ignore_ascii_case(speaker) && a.listener.eq_ignore_ascii_case(listener))
+    }
+
+    /// Prompt section for a batch; `items` are (item number, turn) for the cues being translated.
+    /// Empty when no item has a known speaker.
+    pub fn prompt_section(&self, items: &[(usize, &Turn)]) -> String {
+        if items.is_empty() {
+            return String::new();
+        }
+
+        let mut names: Vec<&str> = Vec::new();
+        let mut pairs: Vec<(&str, &str)> = Vec::new();
+        for (_, turn) in items {
+            for name in std::iter::once(turn.speaker.as_str()).chain(turn.listener.as_deref()) {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+            if let Some(listener) = &turn.listener {
+                if !pairs.contains(&(turn.speaker.as_str(), listener.as_str())) {
+                    pairs.push((turn.speaker.as_str(), listener.as_str()));
+                }
+            }
+        }
+
+        let mut section = format!("{}\n", SPEAKER_HEADERS[0]);
+        for profile in names.iter().filter_map(|name| self.profile(name)) {
+            section.push_str(&profile_line(profile));
+            section.push('\n');
+        }
+
+        let forms: Vec<String> = pairs
+            .iter()
+            .filter_map(|(speaker, listener)| self.address(speaker, listener))
+            .map(address_line)
+            .collect();
+        if !forms.is_empty() {
+            section.push_str(&format!("{}\n{}\n", SPEAKER_HEADERS[1], forms.join("\n")));
+        }
+
+        let lines: Vec<String> = items.iter().map(|(n, turn)| format!("{}. {}", n, turn.label())).collect();
+        section.push_str(&format!("{}\n{}\n\n", SPEAKER_HEADERS[2], lines.join("\n")));
+        section
+    }
+
+    /// Every line one turn can add to the section (before lines shared with other items are merged).
+    pub fn turn_lines(&self, turn: &Turn) -> Vec<String> {
+        let mut lines: Vec<String> = std::iter::once(turn.speaker.as_str())
+            .chain(turn.listener.as_deref())
+            .filter_map(|name| self.profile(name))
+            .map(profile_line)
+            .collect();
+        if let Some(form) = turn.listener.as_deref().and_then(|listener| self.address(&turn.speaker, listener)) {
+            lines.push(address_line(form));
+        }
+        lines.push(format!("00. {}", turn.label()));
+        lines
+    }
+}
+
+fn profile_line(profile: &SpeakerProfile) -> String {
+    let mut line = format!("- {}: {}", profile.name, describe(profile));
+    if !profile.notes.trim().is_empty() {
+        line.push_str(&format!(". {}", profile.notes.trim()));
+    }
+    line
+}
+
+fn address_line(form: &AddressForm) -> String {
+    let mut line = format!("- {} → {}: calls them \"{}\"", form.speaker, form.listener, form.address);
+    if let Some(me) = &form.self_reference {
+        line.push_str(&format!(", refers to self as \"{}\"", me));
+    }
+    line
+}
+
+fn describe(profile: &SpeakerProfile) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    match profile.gender {
+        Gender::Female => parts.push("female".into()),
+        Gender::Male => parts.push("male".into()),
+        Gender::Unspecified => {}
+    }
+    parts.push(
+        match profile.formality {
+            Formality::Formal => "formal speech",
+            Formality::Neutral => "neutral speech",
+            Formality::Casual => "casual speech",
+        }
+        .into(),
+    );
+    if let Some(me) = &profile.self_reference {
+        parts.push(format!("refers to self as \"{}\"", me));
+    }
+    parts.join(", ")
+}
+
+/// `NAME` of a leading `NAME:` (or `- NAME:`) on `line`: an all-caps name, or any name in `table`.
+pub fn name_prefix(line: &str, table: &SpeakerTable) -> Option<String> {
+    let visible = visible_text(line);
+    let rest = visible.trim_start().trim_start_matches('-').trim_start();
+    let (name, after) = rest.split_once(':')?;
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_CHARS || !after.is_empty() && !after.starts_with(char::is_whitespace) {
+        return None;
+    }
+    if let Some(profile) = table.profile(name) {
+        return Some(profile.name.clone());
+    }
+
+    let letters = name.chars().filter(|c| c.is_alphabetic()).count();
+    let all_caps = letters >= 2
+        && name.chars().all(|c| c.is_uppercase() || c == ' ' || c == '.' || c == '\'' || c == '-');
+    all_caps.then(|| {
+        // "MRS. SMITH" -> "Mrs. Smith"
+        name.split(' ')
+            .map(|word| {
+                let mut chars = word.chars();
+                chars.next().map(|first| first.to_string() + &chars.as_str().to_lowercase()).unwrap_or_default()
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    })
+}
+
+/// Turn of each cue whose speaker is known, keyed by cue id.
+/// `actors` are ASS `Name` fields by cue id; empty names fall back to text prefixes.
+pub fn detect_turns(cues: &[SrtCue], actors: Option<&[String]>, table: &SpeakerTable) -> HashMap<usize, Turn> {
+    let mut turns = HashMap::new();
+    let mut current: Option<String> = None;
+    // The last speakers of the scene, most recent first
+    let mut recent: Vec<String> = Vec::new();
+
+    for (pos, cue) in cues.iter().enumerate() {
+        let gap = pos
+            .checked_sub(1)
+            .map(|prev| cue.start.to_millis().saturating_sub(cues[prev].end.to_millis()))
+            .unwrap_or(u64::MAX);
+        if gap > SCENE_GAP_MS {
+            recent.clear();
+        }
+
+        let first_line = cue.text_lines.first().map(String::as_str).unwrap_or("");
+        let actor = actors
+            .and_then(|actors| actors.get(cue.id))
+            .map(|a| a.trim())
+            .filter(|a| !a.is_empty())
+            .map(|a| table.profile(a).map(|p| p.name.clone()).unwrap_or_else(|| a.to_string()));
+        let speaker = actor.or_else(|| name_prefix(first_line, table)).or_else(|| {
+            // An unlabelled line continues the last speaker, unless a dash says someone else talks
+            let continues = gap <= MAX_CONTINUE_GAP_MS && !first_line.trim_start().starts_with('-');
+            current.clone().filter(|_| continues)
+        });
+
+        current = speaker.clone();
+        let Some(speaker) = speaker else {
+            continue;
+        };
+        let listener = recent.iter().find(|name| !name.eq_ignore_ascii_case(&speaker)).cloned();
+        recent.retain(|name| !name.eq_ignore_ascii_case(&speaker));
+        recent.insert(0, speaker.clone());
+        turns.insert(cue.id, Turn { speaker, listener });
+    }
+    turns
+}
+
+const LIBRARY_KIND: &str = "speakers";
+
+pub fn list_speaker_tables() -> Result<Vec<SpeakerTable>, String> {
+    let mut tables: Vec<SpeakerTable> = library::list(LIBRARY_KIND)?;
+    tables.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(tables)
+}
+
+pub fn save_speaker_table(table: &SpeakerTable) -> Result<(), String> {
+    library::save(LIBRARY_KIND, &table.name, table)
+}
+
+pub fn delete_speaker_table(name: &str) -> Result<(), String> {
+    library::delete(LIBRARY_KIND, name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::srt::parse_srt_bytes;
+
+    fn table() -> SpeakerTable {
+        SpeakerTable {
+            name: "Show".into(),
+            speakers: vec![
+                SpeakerProfile { name: "Lan".into(), gender: Gender::Female, formality: Formality::Casual, ..Default::default() },
+                SpeakerProfile { name: "Minh".into(), gender: Gender::Male, ..Default::default() },
+            ],
+            addresses: vec![AddressForm {
+                speaker: "Lan".into(),
+                listener: "Minh".into(),
+                address: "anh".into(),
+                self_reference: Some("em".into()),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_name_prefix() {
+        let table = table();
+        assert_eq!(name_prefix("TOM: Get down!", &table), Some("Tom".into()));
+        assert_eq!(name_prefix("- MRS. SMITH: Hello.", &table), Some("Mrs. Smith".into()));
+        assert_eq!(name_prefix("lan: hi", &table), Some("Lan".into()));
+        assert_eq!(name_prefix("We leave at 10:30.", &table), None);
+        assert_eq!(name_prefix("Note: this is fine", &table), None);
+    }
+
+    #[test]
+    fn test_detect_turns_tracks_listener() {
+        let doc = parse_srt_bytes(
+            b"1\n00:00:01,000 --> 00:00:02,000\nLAN: Are you coming?\n\n2\n00:00:02,500 --> 00:00:03,500\nI'll wait.\n\n3\n00:00:04,000 --> 00:00:05,000\nMINH: Give me a minute.\n\n4\n00:00:05,500 --> 00:00:06,000\n- Who's there?\n\n5\n00:00:30,000 --> 00:00:31,000\nLAN: Hello?\n",
+        )
+        .unwrap();
+        let turns = detect_turns(&doc.cues, None, &table());
+        assert_eq!(turns[&0], Turn { speaker: "Lan".into(), listener: None });
+        assert_eq!(turns[&1].speaker, "Lan");
+        assert_eq!(turns[&2], Turn { speaker: "Minh".into(), listener: Some("Lan".into()) });
+        // A dash without a name is someone unknown; a long pause starts a new scene
+        assert!(!turns.contains_key(&3));
+        assert_eq!(turns[&4].listener, None);
+
+        let actors = vec!["Minh".to_string(), String::new()];
+        let turns = detect_turns(&doc.cues[..2], Some(&actors), &table());
+        assert_eq!(turns[&0].speaker, "Minh");
+    }
+
+    #[test]
+    fn test_prompt_section_lists_pair_forms() {
+        let table = table();
+        let lan = Turn { speaker: "Lan".into(), listener: Some("Minh".into()) };
+        let minh = Turn { speaker: "Minh".into(), listener: Some("Lan".into()) };
+        assert_eq!(table.prompt_section(&[]), "");
+        assert_eq!(
+            table.prompt_section(&[(1, &lan), (2, &minh)]),
+            "SPEAKERS (keep each character's voice, pronouns and forms of address consistent):\n\
+             - Lan: female, casual speech\n\
+             - Minh: male, neutral speech\n\
+             FORMS OF ADDRESS (always use these):\n\
+             - Lan → Minh: calls them \"anh\", refers to self as \"em\"\n\
+             WHO SPEAKS EACH ITEM (speaker → listener):\n\
+             1. Lan → Minh\n\
+             2. Minh → Lan\n\n"
+        );
+    }
+}
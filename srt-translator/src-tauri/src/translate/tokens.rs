@@ -0,0 +1,291 @@
+//! Request size estimates for batching.
+//!
+//! Goals:
+//! - Budget batches in model tokens instead of UTF-8 bytes (CJK is ~3 bytes but ~1 token per char)
+//! - Per-model heuristics (GPT, Claude, Gemini); no tokenizer files needed
+//! - Count the prompt scaffold and the expected translation, not only the source text
+//! - Keep the old byte budget available for configs without a token limit
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+
+use crate::translate::batcher::{CueRole, PromptCue};
+use crate::translate::glossary::{Glossary, GLOSSARY_HEADER};
+use crate::translate::memory::FuzzyMatch;
+use crate::translate::speakers::{SpeakerTable, Turn, SPEAKER_HEADERS};
+use crate::translate::worker::{reference_line, REFERENCE_HEADER};
+
+/// Estimates what one cue adds to a request, in the unit of the batch budget.
+pub trait TokenEstimator {
+    fn cue_cost(&self, cue: &PromptCue) -> usize;
+}
+
+/// The original budget: bytes of text and timing plus a fixed overhead.
+pub struct CharCount;
+
+impl TokenEstimator for CharCount {
+    fn cue_cost(&self, cue: &PromptCue) -> usize {
+        cue.text.len() + cue.timing.len() + 16
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ModelFamily {
+    Gpt,
+    Claude,
+    Gemini,
+}
+
+impl ModelFamily {
+    /// Guess the family from a model name ("gpt-4o-mini", "claude-3-5-sonnet", "gemini-1.5-pro"); defaults to GPT.
+    pub fn for_model(model: &str) -> Self {
+        let model = model.to_ascii_lowercase();
+        if model.contains("claude") {
+            Self::Claude
+        } else if model.contains("gemini") || model.contains("gemma") {
+            Self::Gemini
+        } else {
+            Self::Gpt
+        }
+    }
+
+    /// (ASCII chars per token, other Latin/Cyrillic chars per token, tokens per CJK char).
+    fn rates(self) -> (f32, f32, f32) {
+        match self {
+            Self::Gpt => (4.0, 2.5, 1.0),
+            Self::Claude => (3.5, 2.0, 1.3),
+            Self::Gemini => (4.0, 2.5, 0.9),
+        }
+    }
+}
+
+/// Adds the prompt sections a cue brings along to `inner`'s cost: its reference translation,
+/// the glossary terms it contains and its speaker's lines, so batches are sized with them.
+/// Lines several cues share are counted for each of them, which errs on the safe side.
+pub struct WithSections<'a> {
+    inner: &'a dyn TokenEstimator,
+    references: &'a HashMap<usize, FuzzyMatch>,
+    glossary: Option<&'a Glossary>,
+    speakers: Option<(&'a SpeakerTable, &'a HashMap<usize, Turn>)>,
+}
+
+impl<'a> WithSections<'a> {
+    pub fn new(inner: &'a dyn TokenEstimator, references: &'a HashMap<usize, FuzzyMatch>) -> Self {
+        Self { inner, references, glossary: None, speakers: None }
+    }
+
+    pub fn with_glossary(self, glossary: Option<&'a Glossary>) -> Self {
+        Self { glossary, ..self }
+    }
+
+    pub fn with_speakers(self, speakers: Option<(&'a SpeakerTable, &'a HashMap<usize, Turn>)>) -> Self {
+        Self { speakers, ..self }
+    }
+
+    fn text_cost(&self, text: String) -> usize {
+        self.inner.cue_cost(&PromptCue { id: 0, timing: String::new(), text, role: CueRole::Context })
+    }
+
+    /// The section titles, paid once per request for each section that may appear.
+    pub fn header(&self) -> usize {
+        let mut titles: Vec<&str> = Vec::new();
+        if !self.references.is_empty() {
+            titles.push(REFERENCE_HEADER);
+        }
+        if self.glossary.is_some_and(|g| !g.is_empty()) {
+            titles.push(GLOSSARY_HEADER);
+        }
+        if self.speakers.is_some_and(|(_, turns)| !turns.is_empty()) {
+            titles.extend(SPEAKER_HEADERS);
+        }
+        titles.iter().map(|title| self.text_cost(title.to_string())).sum()
+    }
+}
+
+impl TokenEstimator for WithSections<'_> {
+    fn cue_cost(&self, cue: &PromptCue) -> usize {
+        if matches!(cue.role, CueRole::Context) {
+            return self.inner.cue_cost(cue);
+        }
+        let mut lines: Vec<String> = Vec::new();
+        lines.extend(self.references.get(&cue.id).map(reference_line));
+        if let Some(glossary) = self.glossary {
+            lines.extend(glossary.prompt_lines(&cue.text));
+        }
+        if let Some((table, turns)) = self.speakers {
+            lines.extend(turns.get(&cue.id).map(|turn| table.turn_lines(turn)).unwrap_or_default());
+        }
+        self.inner.cue_cost(cue) + lines.into_iter().map(|line| self.text_cost(line)).sum::<usize>()
+    }
+}
+
+/// Tokens of the instructions around the cue list (rules, delimiters, system prompt).
+const SCAFFOLD_TOKENS: usize = 250;
+/// Tokens for the "12. " prefix and line break of each item.
+const ITEM_OVERHEAD_TOKENS: usize = 4;
+/// Translation output per source token; generous so a batch isn't cut off mid-list.
+const OUTPUT_RATIO: f32 = 1.5;
+
+/// Heuristic token counts for one model family.
+pub struct ModelTokens {
+    pub family: ModelFamily,
+}
+
+impl ModelTokens {
+    pub fn for_model(model: &str) -> Self {
+        Self { family: ModelFamily::for_model(model) }
+    }
+
+    pub fn tokens(&self, text: &str) -> usize {
+        let (ascii_rate, latin_rate, cjk_rate) = self.family.rates();
+        let (mut ascii, mut latin, mut cjk) = (0usize, 0usize, 0usize);
+        for c in text.chars() {
+            if c.is_ascii() {
+                ascii += 1;
+            } else if (c as u32) < 0x2E80 {
+                latin += 1;
+            } else {
+                cjk += 1;
+            }
+        }
+        let tokens = ascii as f32 / ascii_rate + latin as f32 / latin_rate + cjk as f32 * cjk_rate;
+        tokens.ceil() as usize
+    }
+
+    /// Fixed tokens per request, to take off the budget before batching.
+    pub fn scaffold(&self) -> usize {
+        SCAFFOLD_TOKENS
+    }
+}
+
+impl TokenEstimator for ModelTokens {
+    fn cue_cost(&self, cue: &PromptCue) -> usize {
+        let input = self.tokens(&cue.text) + ITEM_OVERHEAD_TOKENS;
+        match cue.role {
+            // Context is read, never returned
+            CueRole::Context => input,
+            CueRole::Translate => input + (input as f32 * OUTPUT_RATIO).ceil() as usize,
+        }
+    }
+}
+
+/// Tokens a job used, as reported by the API (or estimated when it doesn't say).
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// Some requests had no `usage` in the response and were counted with `ModelTokens`.
+    #[serde(default)]
+    pub estimated: bool,
+}
+
+impl TokenUsage {
+    pub fn add(&mut self, other: TokenUsage) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.estimated |= other.estimated;
+    }
+
+    /// Rough cost in USD at list prices; None for models without a known price (local, proxies).
+    pub fn cost_usd(&self, model: &str) -> Option<f64> {
+        let (input, output) = price_per_million(model)?;
+        Some((self.prompt_tokens as f64 * input + self.completion_tokens as f64 * output) / 1_000_000.0)
+    }
+}
+
+/// (input, output) USD per million tokens; more specific names first.
+const PRICES: [(&str, f64, f64); 9] = [
+    ("gpt-4o-mini", 0.15, 0.60),
+    ("gpt-4o", 2.50, 10.00),
+    ("gpt-4.1-mini", 0.40, 1.60),
+    ("gpt-4.1", 2.00, 8.00),
+    ("claude-3-5-haiku", 0.80, 4.00),
+    ("claude-3-5-sonnet", 3.00, 15.00),
+    ("gemini-1.5-flash", 0.075, 0.30),
+    ("gemini-1.5-pro", 1.25, 5.00),
+    ("gemini-2.0-flash", 0.10, 0.40),
+];
+
+fn price_per_million(model: &str) -> Option<(f64, f64)> {
+    let model = model.to_ascii_lowercase();
+    PRICES.iter().find(|(name, _, _)| model.contains(name)).map(|&(_, input, output)| (input, output))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cue(text: &str, role: CueRole) -> PromptCue {
+        PromptCue {
+            id: 0,
+            timing: "00:00:01,000 --> 00:00:02,000".into(),
+            text: text.into(),
+            role,
+        }
+    }
+
+    #[test]
+    fn test_model_family_from_name() {
+        assert_eq!(ModelFamily::for_model("claude-3-5-sonnet-latest"), ModelFamily::Claude);
+        assert_eq!(ModelFamily::for_model("models/gemini-1.5-flash"), ModelFamily::Gemini);
+        assert_eq!(ModelFamily::for_model("gpt-4o-mini"), ModelFamily::Gpt);
+    }
+
+    #[test]
+    fn test_cjk_is_cheaper_than_its_bytes() {
+        let text = "我们在车站等了好几个小时";
+        let gpt = ModelTokens { family: ModelFamily::Gpt };
+        assert_eq!(gpt.tokens(text), 12);
+        assert!(gpt.cue_cost(&cue(text, CueRole::Translate)) < CharCount.cue_cost(&cue(text, CueRole::Translate)));
+    }
+
+    #[test]
+    fn test_cost_estimate() {
+        let usage = TokenUsage { prompt_tokens: 1_000_000, completion_tokens: 500_000, estimated: false };
+        assert_eq!(usage.cost_usd("gpt-4o-mini-2024-07-18"), Some(0.45));
+        assert_eq!(usage.cost_usd("llama3:8b"), None);
+    }
+
+    #[test]
+    fn test_references_count_against_budget() {
+        let gpt = ModelTokens { family: ModelFamily::Gpt };
+        let references = HashMap::from([(
+            1,
+            FuzzyMatch { source: "Where are you going?".into(), translation: "Anh đi đâu vậy?".into(), percent: 90 },
+        )]);
+        let with = WithSections::new(&gpt, &references);
+        let mut item = cue("Where are you going, Tom?", CueRole::Translate);
+        assert_eq!(with.cue_cost(&item), gpt.cue_cost(&item));
+        item.id = 1;
+        assert!(with.cue_cost(&item) > gpt.cue_cost(&item) + 10);
+        assert!(with.header() > 0);
+        assert_eq!(WithSections::new(&gpt, &HashMap::new()).header(), 0);
+    }
+
+    #[test]
+    fn test_glossary_terms_count_against_budget() {
+        let gpt = ModelTokens { family: ModelFamily::Gpt };
+        let references = HashMap::new();
+        let glossary: Glossary = serde_json::from_value(serde_json::json!({
+            "name": "Show",
+            "terms": [{ "source": "Tom", "target": "Tôm" }],
+            "do_not_translate": ["Jerry"],
+        }))
+        .unwrap();
+        let with = WithSections::new(&gpt, &references).with_glossary(Some(&glossary));
+        let item = cue("Tom and Jerry", CueRole::Translate);
+        assert!(with.cue_cost(&item) > gpt.cue_cost(&item) + 8);
+        assert_eq!(with.cue_cost(&cue("Nobody", CueRole::Translate)), gpt.cue_cost(&cue("Nobody", CueRole::Translate)));
+        assert!(with.header() > 0);
+    }
+
+    #[test]
+    fn test_context_costs_no_output() {
+        let gpt = ModelTokens { family: ModelFamily::Gpt };
+        let context = gpt.cue_cost(&cue("Hello there, how are you?", CueRole::Context));
+        let translate = gpt.cue_cost(&cue("Hello there, how are you?", CueRole::Translate));
+        assert_eq!(context, 7 + ITEM_OVERHEAD_TOKENS);
+        assert!(translate > context * 2);
+    }
+}
@@ -6,8 +6,11 @@
 
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::{HashMap, BTreeMap},
-    sync::{Arc, Mutex},
+    collections::{HashMap, BTreeMap, VecDeque},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
     time::Instant,
 };
 
@@ -15,8 +18,16 @@ use tokio::{sync::Semaphore, time::Duration};
 use tauri::{Manager, Emitter};
 use regex::Regex;
 
-use crate::srt::{SrtDocument, SrtCue};
-use crate::translate::batcher::{create_batches, mask_tags, unmask_tags, BatchConfig, TranslationBatch, PromptCue};
+use crate::queue::RequestSlots;
+use crate::srt::{bilingual::BilingualOptions, encoding::OutputEncoding, qa::QaProfile, wrap::WrapConfig, SrtDocument, SrtCue, SubtitleFormat};
+use crate::translate::checkpoint::{Checkpoint, CheckpointEntry};
+use crate::translate::control::JobControl;
+use crate::translate::batcher::{create_batches_skipping, mask_tags, split_batch, unmask_tags, AdaptiveSizer, BatchConfig, CueRole, TranslationBatch, PromptCue};
+use crate::translate::glossary::{Glossary, TermEnforcement, TermViolation};
+use crate::translate::memory::{normalize_source, FuzzyMatch, MemoryKey, TranslationMemory};
+use crate::translate::tokens::{CharCount, ModelTokens, TokenUsage, WithSections};
+use crate::translate::segment::{redistribute, segment_sentences, unit_cues};
+use crate::translate::speakers::{SpeakerTable, Turn};
 
 // ============================================================================
 // NEWLINE ENCODING/DECODING FOR PARSE SAFETY
@@ -50,10 +61,24 @@ fn decode_newlines(text: &str) -> String {
 // NUMBERED LIST PROMPT BUILDER (COUNT-SAFE, O(n))
 // ============================================================================
 
+/// Prefix of context lines in the prompt.
+const CONTEXT_MARKER: &str = "> ";
+
+pub const REFERENCE_HEADER: &str =
+    "REFERENCE TRANSLATIONS (approved translations of similar lines; reuse their wording where it fits):";
+
+/// One line of the reference section.
+pub fn reference_line(reference: &FuzzyMatch) -> String {
+    format!("- {} → {}", encode_newlines(&reference.source), encode_newlines(&reference.translation))
+}
+
 fn build_translation_prompt(
     batch: &TranslationBatch,
     source_lang: &str,
-    target_lang: &str
+    target_lang: &str,
+    glossary: Option<&Glossary>,
+    references: &[&FuzzyMatch],
+    speakers: Option<(&SpeakerTable, &HashMap<usize, Turn>)>,
 ) -> Result<(String, usize), TranslateError> {
     let count = batch.translate_ids.len(); // Single source of truth
     
@@ -77,6 +102,47 @@ fn build_translation_prompt(
     
     let content_list = lines.join("\n");
     
+    // Context cues go outside BEGIN/END, unnumbered, so they can't be mistaken for items
+    let first_id = batch.translate_ids.first().copied().unwrap_or(0);
+    let context_section = |title: &str, before: bool| -> String {
+        let context: Vec<String> = batch.cues.iter()
+            .filter(|c| matches!(c.role, CueRole::Context) && (c.id < first_id) == before)
+            .map(|c| format!("{}{}", CONTEXT_MARKER, encode_newlines(&c.text)))
+            .collect();
+        if context.is_empty() {
+            String::new()
+        } else {
+            format!("{} (context only — do NOT translate or return):\n{}\n\n", title, context.join("\n"))
+        }
+    };
+    let context_before = context_section("PREVIOUS LINES", true);
+    let context_after = context_section("FOLLOWING LINES", false);
+    
+    // Only the terms that occur in the cues being translated
+    let glossary_section = glossary
+        .map(|g| g.prompt_section(batch.cues.iter().filter(|c| matches!(c.role, CueRole::Translate)).map(|c| c.text.as_str())))
+        .unwrap_or_default();
+    
+    // Who says each item, with the profiles and address forms of those speakers
+    let speaker_section = speakers
+        .map(|(table, turns)| {
+            let items: Vec<(usize, &Turn)> = batch.translate_ids.iter().enumerate()
+                .filter_map(|(idx, id)| turns.get(id).map(|turn| (idx + 1, turn)))
+                .collect();
+            table.prompt_section(&items)
+        })
+        .unwrap_or_default();
+    
+    // Similar lines translated before (fuzzy translation memory matches)
+    let reference_section = if references.is_empty() {
+        String::new()
+    } else {
+        let lines: Vec<String> = references.iter()
+            .map(|m| reference_line(m))
+            .collect();
+        format!("{}\n{}\n\n", REFERENCE_HEADER, lines.join("\n"))
+    };
+    
     let prompt = format!(
         "Translate the following {} subtitles to {}.\n\n\
          RULES:\n\
@@ -85,10 +151,11 @@ fn build_translation_prompt(
          - Line breaks are encoded as <NL> token — keep them\n\
          - Do not insert real line breaks inside items; use <NL> token only\n\
          - No markdown, no code blocks, no extra blank lines\n\
-         - Do not merge or split items\n\n\
-         BEGIN\n{}\nEND\n\n\
+         - Do not merge or split items\n\
+         - Lines marked \"{}\" are context; use them for meaning only\n\n\
+         {}{}{}{}BEGIN\n{}\nEND\n\n{}\
          Output format: numbered list between BEGIN/END delimiters only.",
-        source_lang, target_lang, count, content_list
+        source_lang, target_lang, count, CONTEXT_MARKER.trim(), glossary_section, speaker_section, reference_section, context_before, content_list, context_after
     );
     
     Ok((prompt, count))
@@ -98,7 +165,17 @@ fn build_translation_prompt(
 // NUMBERED LIST PARSER (LINE-BASED, REGEX, STRICT VALIDATION)
 // ============================================================================
 
-fn parse_numbered_response(response: &str, expected_count: usize) -> Result<Vec<String>, TranslateError> {
+/// Masked texts of the batch's context cues, as they appear in the prompt.
+fn context_texts(batch: &TranslationBatch) -> Vec<String> {
+    batch.cues.iter()
+        .filter(|c| matches!(c.role, CueRole::Context))
+        .map(|c| encode_newlines(&c.text))
+        .collect()
+}
+
+/// `context` is the batch's context lines; echoed copies are dropped instead of
+/// being appended to the previous item.
+fn parse_numbered_response(response: &str, expected_count: usize, context: &[String]) -> Result<Vec<String>, TranslateError> {
     // Extract content between BEGIN/END using line-based scanning
     let lines: Vec<&str> = response.lines().collect();
     let mut in_block = false;
@@ -149,7 +226,9 @@ fn parse_numbered_response(response: &str, expected_count: usize) -> Result<Vec<
         } else if let Some(num) = current_num {
             // Continuation line - only append non-empty, non-noise lines
             let trimmed = line.trim();
-            if !trimmed.is_empty() && !trimmed.starts_with("Note:") {
+            let leaked_context = trimmed.starts_with(CONTEXT_MARKER.trim())
+                || context.iter().any(|c| c.trim() == trimmed);
+            if !trimmed.is_empty() && !trimmed.starts_with("Note:") && !leaked_context {
                 if let Some(existing) = items.get_mut(&num) {
                     if !existing.is_empty() {
                         existing.push('\n');
@@ -200,7 +279,7 @@ fn parse_numbered_response(response: &str, expected_count: usize) -> Result<Vec<
     Ok(translations)
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum Language {
     Auto,
     English,
@@ -223,6 +302,34 @@ impl Language {
             Language::Vietnamese => "Vietnamese",
         }
     }
+
+    /// BCP 47 code, as used by TMX and CAT tools.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Language::Auto => "und",
+            Language::English => "en",
+            Language::ChineseSimplified => "zh-CN",
+            Language::ChineseTraditional => "zh-TW",
+            Language::Japanese => "ja",
+            Language::Korean => "ko",
+            Language::Vietnamese => "vi",
+        }
+    }
+
+    /// Language for a code like "en-US", "zh-Hant" or "VI"; None when unsupported.
+    pub fn from_code(code: &str) -> Option<Self> {
+        let code = code.trim().to_ascii_lowercase().replace('_', "-");
+        let primary = code.split('-').next().unwrap_or("");
+        match primary {
+            "en" => Some(Language::English),
+            "zh" if ["-tw", "-hk", "-mo", "-hant"].iter().any(|r| code.contains(r)) => Some(Language::ChineseTraditional),
+            "zh" => Some(Language::ChineseSimplified),
+            "ja" => Some(Language::Japanese),
+            "ko" => Some(Language::Korean),
+            "vi" => Some(Language::Vietnamese),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -242,6 +349,85 @@ pub struct TranslationOptions {
     pub provider: ProviderConfig,
     pub max_retries: u32,
     pub min_delay_ms: u64, // for rate limiting/backoff
+    /// Output container; None keeps the imported file's format (e.g. Srt -> write .srt from a .vtt).
+    #[serde(default)]
+    pub output_format: Option<SubtitleFormat>,
+    /// Output encoding; None writes back in the source file's encoding.
+    #[serde(default)]
+    pub output_encoding: Option<OutputEncoding>,
+    /// Reading-speed/line-length limits checked after translation; None uses Netflix-style limits.
+    #[serde(default)]
+    pub qa_profile: Option<QaProfile>,
+    /// Re-wrap translated text that doesn't fit these limits; None keeps the model's line breaks.
+    #[serde(default)]
+    pub wrap: Option<WrapConfig>,
+    /// Write original + translation in each cue; None writes the translation only.
+    #[serde(default)]
+    pub bilingual: Option<BilingualOptions>,
+    /// Merge/split cues that `qa_profile` flags as too fast or too long (.srt sources only,
+    /// once every batch has succeeded).
+    #[serde(default)]
+    pub fit_reading_speed: bool,
+    /// Translate sentences that run across several cues as one item, then spread them back.
+    #[serde(default)]
+    pub sentence_units: bool,
+    /// Reuse translations from the translation memory and add new ones to it.
+    #[serde(default = "default_use_memory")]
+    pub use_memory: bool,
+    /// Terms that must be translated a fixed way; checked after every batch.
+    #[serde(default)]
+    pub glossary: Option<Glossary>,
+    /// Show the model prior translations of similar lines (0..1 similarity, e.g. 0.75); None disables.
+    #[serde(default)]
+    pub fuzzy_threshold: Option<f32>,
+    /// Speaker profiles and forms of address; Some turns on speaker detection (an empty table just labels speakers).
+    #[serde(default)]
+    pub speakers: Option<SpeakerTable>,
+    /// What a batch that still fails after `max_retries` does to the rest of the job.
+    #[serde(default)]
+    pub failure_policy: FailurePolicy,
+    /// Written in place of cues a failed batch left untranslated.
+    #[serde(default)]
+    pub untranslated_text: UntranslatedText,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum FailurePolicy {
+    /// Finish the other batches; the job ends partially done with the failed cues listed.
+    #[default]
+    Continue,
+    /// Start no more batches and fail the job (finished batches are still checkpointed).
+    Abort,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum UntranslatedText {
+    /// The cue's original text.
+    #[default]
+    Source,
+    /// A fixed marker such as "[untranslated]".
+    Marker(String),
+}
+
+fn default_use_memory() -> bool {
+    true
+}
+
+#[cfg(test)]
+impl TranslationOptions {
+    /// English → Vietnamese with gpt-4o-mini on localhost, one thread, no retries; the rest default.
+    pub fn for_tests() -> Self {
+        serde_json::from_value(serde_json::json!({
+            "source_lang": Language::English,
+            "target_lang": Language::Vietnamese,
+            "batch": BatchConfig::default(),
+            "threads": 1,
+            "provider": ProviderConfig { base_url: "http://localhost".into(), api_key: None, model: "gpt-4o-mini".into() },
+            "max_retries": 0,
+            "min_delay_ms": 0,
+        }))
+        .unwrap()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -254,6 +440,9 @@ pub struct ProgressEvent {
     pub eta_seconds: u64,
     pub stage: String,
     pub active_threads: usize,
+    /// Cues taken from the translation memory / sent to the API.
+    pub memory_hits: usize,
+    pub memory_misses: usize,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -261,7 +450,7 @@ pub struct BatchStatus {
     pub job_id: String,
     pub batch_no: usize,
     pub total_batches: usize,
-    pub status: String, // "pending", "running", "done", "error"
+    pub status: String, // "pending", "running", "done", "error", "cancelled"
     pub cue_start: usize,
     pub cue_end: usize,
     pub error_msg: Option<String>,
@@ -275,6 +464,8 @@ pub enum TranslateError {
     BadResponse(String),
     #[error("Parse error: {0}")]
     ParseError(String),
+    #[error("Response was cut off before the end of the list")]
+    Truncated,
     #[error("Translation cancelled")]
     Cancelled,
 }
@@ -285,6 +476,8 @@ pub struct OpenAiCompatClient {
     base_url: String,
     api_key: Option<String>,
     model: String,
+    /// Running total over every request made through this client and its clones.
+    usage: Arc<Mutex<TokenUsage>>,
 }
 
 impl OpenAiCompatClient {
@@ -294,9 +487,16 @@ impl OpenAiCompatClient {
             base_url: provider.base_url.trim_end_matches('/').to_string(),
             api_key: provider.api_key.clone(),
             model: provider.model.clone(),
+            usage: Arc::new(Mutex::new(TokenUsage::default())),
         }
     }
 
+    /// Count tokens into `usage` instead of a private total.
+    pub fn with_usage(mut self, usage: Arc<Mutex<TokenUsage>>) -> Self {
+        self.usage = usage;
+        self
+    }
+
     pub async fn translate_json(&self, system: &str, user_json: &str) -> Result<String, TranslateError> {
         // OpenAI-compatible Chat Completions payload.
         let url = format!("{}/chat/completions", self.base_url);
@@ -344,6 +544,26 @@ impl OpenAiCompatClient {
             .and_then(|x| x.as_str())
             .ok_or_else(|| TranslateError::BadResponse(format!("Missing choices[0].message.content. Raw: {text}")))?;
 
+        // Billed even if the list turns out to be cut off
+        let reported = |field: &str| v.pointer(&format!("/usage/{}", field)).and_then(|x| x.as_u64());
+        let usage = match (reported("prompt_tokens"), reported("completion_tokens")) {
+            (Some(prompt_tokens), Some(completion_tokens)) => TokenUsage { prompt_tokens, completion_tokens, estimated: false },
+            _ => {
+                let estimator = ModelTokens::for_model(&self.model);
+                TokenUsage {
+                    prompt_tokens: (estimator.tokens(system) + estimator.tokens(user_json)) as u64,
+                    completion_tokens: estimator.tokens(content) as u64,
+                    estimated: true,
+                }
+            }
+        };
+        self.usage.lock().unwrap().add(usage);
+
+        // The model ran out of output tokens: the list is incomplete
+        if v.pointer("/choices/0/finish_reason").and_then(|x| x.as_str()) == Some("length") {
+            return Err(TranslateError::Truncated);
+        }
+
         Ok(content.to_string())
     }
 }
@@ -393,28 +613,253 @@ fn extract_json_object(s: &str) -> Result<serde_json::Value, TranslateError> {
     serde_json::from_str(slice).map_err(|e| TranslateError::BadResponse(format!("Failed to parse JSON: {e}. Raw: {slice}")))
 }
 
+/// A cue whose translation was taken from, or helped by, the translation memory.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryMatch {
+    pub cue_id: usize,
+
+    /// 100 for an exact hit; lower for a fuzzy reference shown to the model.
+    pub percent: u8,
+    pub source: String,
+    pub translation: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DocumentTranslation {
+    pub translated: HashMap<usize, String>,
+
+    /// By cue id, for reviewers.
+    pub memory_matches: Vec<MemoryMatch>,
+
+    /// Cues of batches that failed, which `translated` has no entry for.
+    pub failed: Vec<FailedRange>,
+}
+
+/// A run of consecutive cues (file cue ids, inclusive) a failed batch left untranslated.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FailedRange {
+    pub first_cue: usize,
+    pub last_cue: usize,
+    pub error: String,
+}
+
+/// Group `cue_ids` into runs of consecutive ids.
+fn failed_ranges(mut cue_ids: Vec<usize>, error: &str) -> Vec<FailedRange> {
+    cue_ids.sort_unstable();
+    cue_ids.dedup();
+    let mut ranges: Vec<FailedRange> = Vec::new();
+    for id in cue_ids {
+        match ranges.last_mut() {
+            Some(range) if range.last_cue + 1 == id => range.last_cue = id,
+            _ => ranges.push(FailedRange { first_cue: id, last_cue: id, error: error.to_string() }),
+        }
+    }
+    ranges
+}
+
+/// Translation memory key for one source text under `opts`, said in `turn` if known.
+pub fn memory_key(opts: &TranslationOptions, turn: Option<&Turn>, source: &str) -> MemoryKey {
+    MemoryKey {
+        source_lang: opts.source_lang.label().to_string(),
+        target_lang: opts.target_lang.label().to_string(),
+        model: opts.provider.model.clone(),
+        glossary_version: opts.glossary.as_ref().map(Glossary::version).unwrap_or(0),
+        speakers: turn.map(Turn::label).unwrap_or_default(),
+        source: normalize_source(source),
+    }
+}
+
+/// Handles a running job shares with the rest of the app.
+#[derive(Clone)]
+pub struct JobHandles {
+    pub memory: Arc<Mutex<TranslationMemory>>,
+    pub control: JobControl,
+    /// Where finished batches are saved; items already in it aren't sent again.
+    pub checkpoint: Option<Checkpoint>,
+    /// Request slots shared by all jobs (on top of this job's own `threads`).
+    pub request_slots: RequestSlots,
+    /// Tokens used so far; still readable when the job fails.
+    pub usage: Arc<Mutex<TokenUsage>>,
+}
+
+/// Batches for the items `is_done` doesn't cover, sized by the job's budget.
+/// Reference translations go into the prompt of the batch holding their item, so they
+/// count against the same budget.
+fn pending_batches(
+    item_cues: &[SrtCue],
+    opts: &TranslationOptions,
+    fuzzy: &HashMap<usize, FuzzyMatch>,
+    turns: &HashMap<usize, Turn>,
+    is_done: &dyn Fn(usize) -> bool,
+) -> Vec<TranslationBatch> {
+    // The prompt's glossary, speaker and reference sections come out of the same budget
+    let sections = |inner| {
+        WithSections::new(inner, fuzzy)
+            .with_glossary(opts.glossary.as_ref())
+            .with_speakers(opts.speakers.as_ref().map(|table| (table, turns)))
+    };
+    match opts.batch.max_tokens_per_request {
+        Some(max_tokens) => {
+            let model_tokens = ModelTokens::for_model(&opts.provider.model);
+            let estimator = sections(&model_tokens);
+            let budget = max_tokens.saturating_sub(model_tokens.scaffold() + estimator.header());
+            create_batches_skipping(item_cues, &opts.batch, &estimator, budget, is_done)
+        }
+        None => {
+            let estimator = sections(&CharCount);
+            let budget = opts.batch.max_chars_per_request.saturating_sub(estimator.header());
+            create_batches_skipping(item_cues, &opts.batch, &estimator, budget, is_done)
+        }
+    }
+}
+
 pub async fn translate_document(
     app: tauri::AppHandle,
     job_id: String,
     file_name: String,
     doc: SrtDocument,
     opts: TranslationOptions,
-) -> Result<HashMap<usize, String>, TranslateError> {
+    handles: JobHandles,
+    turns: HashMap<usize, Turn>,
+) -> Result<DocumentTranslation, TranslateError> {
+    let JobHandles { memory, control, checkpoint, request_slots, usage } = handles;
     let total_cues = doc.cues.len();
-    let batches = create_batches(&doc.cues, &opts.batch);
 
-    let client = OpenAiCompatClient::new(&opts.provider);
+    // With sentence units, each batch item is a unit; `item_sizes` is how many cues it covers
+    let units = opts.sentence_units.then(|| segment_sentences(&doc.cues));
+    let (item_cues, item_sizes) = match &units {
+        Some(units) => (unit_cues(&doc.cues, units), units.iter().map(|u| u.cue_ids.len()).collect()),
+        None => (doc.cues.clone(), vec![1; total_cues]),
+    };
+    // A unit is said by whoever says its first cue
+    let turns: HashMap<usize, Turn> = match &units {
+        Some(units) => units.iter().enumerate()
+            .filter_map(|(unit_id, unit)| turns.get(&unit.cue_ids[0]).map(|turn| (unit_id, turn.clone())))
+            .collect(),
+        None => turns,
+    };
+    let turns = Arc::new(turns);
+
+    // Items an earlier run of this job finished
+    let resumed: HashMap<usize, String> = match &checkpoint {
+        Some(checkpoint) => checkpoint.done().unwrap_or_else(|e| {
+            let _ = app.emit("translation://warning", format!("{}; translating from the start.", e));
+            HashMap::new()
+        }),
+        None => HashMap::new(),
+    }
+    .into_iter()
+    .filter(|(id, _)| *id < item_cues.len())
+    .map(|(id, entry)| (id, entry.text))
+    .collect();
+    let resumed_cues: usize = resumed.keys().map(|&id| item_sizes[id]).sum();
+
+    // Translation memory hits are done before any request; only misses are translated
+    let mut cached: HashMap<usize, String> = HashMap::new();
+    if opts.use_memory {
+        let memory = memory.lock().unwrap();
+        for cue in item_cues.iter().filter(|c| !resumed.contains_key(&c.id)) {
+            if let Some(text) = memory.lookup(&memory_key(&opts, turns.get(&cue.id), &cue.text_lines.join("\n"))) {
+                cached.insert(cue.id, text.to_string());
+            }
+        }
+    }
+    let memory_hits: usize = cached.keys().map(|&id| item_sizes[id]).sum();
+    let memory_misses = total_cues - memory_hits - resumed_cues;
+    let already_done = memory_hits + resumed_cues;
+    let misses: Vec<SrtCue> = item_cues
+        .iter()
+        .filter(|c| !cached.contains_key(&c.id) && !resumed.contains_key(&c.id))
+        .cloned()
+        .collect();
+
+    // Best fuzzy match per missed item, shown to the model with its batch; found once, up front,
+    // off the async threads, locking the memory per item so other jobs aren't held up
+    let fuzzy: HashMap<usize, FuzzyMatch> = match (opts.use_memory, opts.fuzzy_threshold) {
+        (true, Some(threshold)) => {
+            let keys: Vec<(usize, MemoryKey)> = misses
+                .iter()
+                .map(|cue| (cue.id, memory_key(&opts, turns.get(&cue.id), &cue.text_lines.join("\n"))))
+                .collect();
+            let memory = memory.clone();
+            tokio::task::spawn_blocking(move || {
+                keys.into_iter()
+                    .filter_map(|(id, key)| {
+                        let best = memory.lock().unwrap().fuzzy_matches(&key, threshold.clamp(0.0, 1.0), 1);
+                        best.into_iter().next().map(|m| (id, m))
+                    })
+                    .collect()
+            })
+            .await
+            .unwrap_or_default()
+        }
+        _ => HashMap::new(),
+    };
+    let mut memory_matches: Vec<MemoryMatch> = cached
+        .iter()
+        .map(|(&id, text)| MemoryMatch {
+            cue_id: id,
+            percent: 100,
+            source: item_cues[id].text_lines.join("\n"),
+            translation: text.clone(),
+        })
+        .chain(fuzzy.iter().map(|(&id, m)| MemoryMatch {
+            cue_id: id,
+            percent: m.percent,
+            source: m.source.clone(),
+            translation: m.translation.clone(),
+        }))
+        .collect();
+
+    // Batched over the whole timeline, so context is the real neighbouring lines; memory hits
+    // and resumed items are only ever context
+    let is_done = |id: usize| cached.contains_key(&id) || resumed.contains_key(&id);
+    let batches = pending_batches(&item_cues, &opts, &fuzzy, &turns, &is_done);
+    let sizer = Arc::new(Mutex::new(AdaptiveSizer::new()));
+
+    let client = OpenAiCompatClient::new(&opts.provider).with_usage(usage);
     let sem = Arc::new(Semaphore::new(opts.threads.clamp(1, 10)));
 
-    let translated: Arc<Mutex<HashMap<usize, String>>> = Arc::new(Mutex::new(HashMap::new()));
-    let done_cues: Arc<Mutex<usize>> = Arc::new(Mutex::new(0));
+    let translated: Arc<Mutex<HashMap<usize, String>>> = Arc::new(Mutex::new(cached.into_iter().chain(resumed).collect()));
+    let done_cues: Arc<Mutex<usize>> = Arc::new(Mutex::new(already_done));
+    let term_violations: Arc<Mutex<Vec<TermViolation>>> = Arc::new(Mutex::new(Vec::new()));
+
+    if already_done > 0 {
+        let _ = app.emit("translation://progress", ProgressEvent {
+            job_id: job_id.clone(),
+            file_name: file_name.clone(),
+            done_cues: already_done,
+            total_cues,
+            percent: (already_done as f32 / total_cues as f32) * 100.0,
+            eta_seconds: 0,
+            stage: if resumed_cues > 0 { "resumed" } else { "memory" }.into(),
+            active_threads: 0,
+            memory_hits,
+            memory_misses,
+        });
+    }
 
     let start_time = Instant::now();
     let total_batches = batches.len();
     let mut handles = Vec::with_capacity(total_batches);
+    let any_failed = Arc::new(AtomicBool::new(false));
 
     for batch in batches {
-        let permit = sem.clone().acquire_owned().await.unwrap();
+        if opts.failure_policy == FailurePolicy::Abort && any_failed.load(Ordering::Relaxed) {
+            break;
+        }
+        // Paused: hold this batch until resumed (without taking a slot); those already running carry on
+        if control.proceed().await.is_err() {
+            break;
+        }
+        let permit = tokio::select! {
+            permit = sem.clone().acquire_owned() => permit.unwrap(),
+            _ = control.cancelled() => break,
+        };
+        let request_permit = tokio::select! {
+            permit = request_slots.acquire() => permit,
+            _ = control.cancelled() => break,
+        };
         let client = client.clone();
         let app = app.clone();
         let translated_map = translated.clone();
@@ -425,10 +870,32 @@ pub async fn translate_document(
         let tgt = opts.target_lang.clone();
         let max_retries = opts.max_retries;
         let min_delay = opts.min_delay_ms;
-        let doc_cues = doc.cues.clone();
+        let doc_cues = item_cues.clone();
+        let item_sizes = item_sizes.clone();
+        let sizer = sizer.clone();
+        let batch_cfg = opts.batch.clone();
+        let glossary = opts.glossary.clone();
+        let speakers = opts.speakers.clone();
+        let turns = turns.clone();
+        let violations_ref = term_violations.clone();
+        let control = control.clone();
+        let checkpoint = checkpoint.clone();
+        let any_failed = any_failed.clone();
+        let memory = opts.use_memory.then(|| memory.clone());
+        let memory_keys: Vec<(usize, MemoryKey)> = batch
+            .translate_ids
+            .iter()
+            .map(|&id| (id, memory_key(&opts, turns.get(&id), &item_cues[id].text_lines.join("\n"))))
+            .collect();
+        let references: Vec<(usize, FuzzyMatch)> = batch
+            .translate_ids
+            .iter()
+            .filter_map(|id| fuzzy.get(id).map(|m| (*id, m.clone())))
+            .collect();
 
-        handles.push(tokio::spawn(async move {
-            let _permit = permit;
+        let batch_ids = batch.translate_ids.clone();
+        handles.push((batch_ids, tokio::spawn(async move {
+            let _permit = (permit, request_permit);
 
             // Emit batch start
             let _ = app.emit("batch://status", BatchStatus {
@@ -441,66 +908,172 @@ pub async fn translate_document(
                 error_msg: None,
             });
 
-            // Build numbered list prompt (new format - replaces JSON)
-            let (user_prompt, expected_count) = build_translation_prompt(
-                &batch,
-                src.label(),
-                tgt.label()
-            )?;
+            // Adaptive sizing: work through the batch in chunks of the current size;
+            // a chunk that comes back unparseable or cut off is halved and retried
+            let chunk_size = sizer.lock().unwrap().chunk_size(batch.translate_ids.len());
+            // (part, already retried for glossary terms)
+            let mut pending: VecDeque<(TranslationBatch, bool)> = split_batch(&batch, chunk_size, &batch_cfg)
+                .into_iter()
+                .map(|part| (part, false))
+                .collect();
+            let mut local: Vec<(usize, String)> = Vec::new();
+            let mut broken_terms: Vec<TermViolation> = Vec::new();
+            // Items left untranslated once a part fails for good, and why
+            let mut failure: Option<(Vec<usize>, TranslateError)> = None;
 
-            // Retry loop
-            let mut attempt = 0u32;
-            let response_text = loop {
-                attempt += 1;
+            while let Some((part, retried)) = pending.pop_front() {
+                // Build numbered list prompt (new format - replaces JSON)
+                let (user_prompt, expected_count) = build_translation_prompt(
+                    &part,
+                    src.label(),
+                    tgt.label(),
+                    glossary.as_ref(),
+                    &references.iter().filter(|(id, _)| part.translate_ids.contains(id)).map(|(_, m)| m).collect::<Vec<_>>(),
+                    speakers.as_ref().map(|table| (table, turns.as_ref())),
+                )?;
 
-                // Basic pace control
-                tokio::time::sleep(Duration::from_millis(min_delay)).await;
+                // Retry loop (a truncated response won't fit any better on retry)
+                let mut attempt = 0u32;
+                let response = loop {
+                    attempt += 1;
+
+                    // Basic pace control
+                    if let Err(e) = control.sleep(Duration::from_millis(min_delay)).await {
+                        break Err(e);
+                    }
+
+                    // A cancel drops the request mid-flight
+                    let request = client.translate_json("You are a professional subtitle translator. Follow instructions precisely.", &user_prompt);
+                    let result = tokio::select! {
+                        result = request => result,
+                        _ = control.cancelled() => Err(TranslateError::Cancelled),
+                    };
+                    match result {
+                        Ok(s) => break Ok(s),
+                        Err(e @ (TranslateError::Truncated | TranslateError::Cancelled)) => break Err(e),
+                        Err(e) if attempt <= max_retries => {
+                            // Exponential backoff
+                            let backoff = (200u64 * 2u64.saturating_pow(attempt.min(6))).min(10_000);
+                            let _ = app.emit(
+                                "translation://warning",
+                                format!("Retrying batch {} (attempt {}): {}", batch.batch_no, attempt, e),
+                            );
+                            if let Err(e) = control.sleep(Duration::from_millis(backoff)).await {
+                                break Err(e);
+                            }
+                            continue;
+                        }
+                        Err(e) => break Err(e),
+                    }
+                };
 
-                match client.translate_json("You are a professional subtitle translator. Follow instructions precisely.", &user_prompt).await {
-                    Ok(s) => break s,
-                    Err(e) if attempt <= max_retries => {
-                        // Exponential backoff
-                        let backoff = (200u64 * 2u64.saturating_pow(attempt.min(6))).min(10_000);
+                // Parse numbered list response (new format)
+                let parsed = response.and_then(|text| {
+                    parse_numbered_response(&text, expected_count, &context_texts(&part))
+                });
+                let translations = match parsed {
+                    Ok(translations) => {
+                        sizer.lock().unwrap().on_success();
+                        translations
+                    }
+                    Err(e @ (TranslateError::ParseError(_) | TranslateError::Truncated)) if part.translate_ids.len() > 1 => {
+                        sizer.lock().unwrap().on_failure();
                         let _ = app.emit(
                             "translation://warning",
-                            format!("Retrying batch {} (attempt {}): {}", batch.batch_no, attempt, e),
+                            format!("Batch {} came back incomplete ({}); retrying in smaller pieces.", batch.batch_no, e),
                         );
-                        tokio::time::sleep(Duration::from_millis(backoff)).await;
+                        let half = part.translate_ids.len().div_ceil(2);
+                        for smaller in split_batch(&part, half, &batch_cfg).into_iter().rev() {
+                            pending.push_front((smaller, retried));
+                        }
                         continue;
                     }
                     Err(e) => {
-                        // Emit batch error
+                        // Emit batch error (or cancellation)
+                        let cancelled = matches!(e, TranslateError::Cancelled);
                         let _ = app.emit("batch://status", BatchStatus {
                             job_id: job_id_cl.clone(),
                             batch_no: batch.batch_no,
                             total_batches,
-                            status: "error".to_string(),
+                            status: if cancelled { "cancelled" } else { "error" }.to_string(),
                             cue_start: *batch.translate_ids.first().unwrap_or(&0),
                             cue_end: *batch.translate_ids.last().unwrap_or(&0),
-                            error_msg: Some(e.to_string()),
+                            error_msg: (!cancelled).then(|| e.to_string()),
                         });
-                        return Err(e);
+                        if cancelled {
+                            return Err(e);
+                        }
+                        // Keep the parts that did finish; the rest of the batch is reported as failed
+                        any_failed.store(true, Ordering::Relaxed);
+                        let failed_ids = part.translate_ids.iter()
+                            .chain(pending.iter().flat_map(|(p, _)| p.translate_ids.iter()))
+                            .copied()
+                            .collect();
+                        failure = Some((failed_ids, e));
+                        break;
                     }
+                };
+
+                // Map back to original IDs and unmask tags
+                let mut part_local: Vec<(usize, String)> = Vec::new();
+                for (idx, &id) in part.translate_ids.iter().enumerate() {
+                    let text = &translations[idx];
+                    
+                    // Unmask tags
+                    let cue: &SrtCue = &doc_cues[id];
+                    let (_, mappings) = mask_tags(&cue.text_lines.join("\n"));
+                    let unmasked = unmask_tags(text, &mappings);
+                    
+                    part_local.push((id, unmasked));
                 }
-            };
 
-            // Parse numbered list response (new format)
-            let translations = parse_numbered_response(&response_text, expected_count)?;
+                // Glossary post-check: retry the part once if asked to, then report what's left
+                if let Some(glossary) = &glossary {
+                    let violations: Vec<TermViolation> = part_local
+                        .iter()
+                        .flat_map(|(id, text)| glossary.check(*id, &doc_cues[*id].text_lines.join("\n"), text))
+                        .collect();
+                    if !violations.is_empty() && glossary.enforcement == TermEnforcement::Retry && !retried {
+                        let _ = app.emit(
+                            "translation://warning",
+                            format!("Batch {} missed {} glossary terms; retrying.", batch.batch_no, violations.len()),
+                        );
+                        pending.push_front((part, true));
+                        continue;
+                    }
+                    broken_terms.extend(violations);
+                }
+                local.extend(part_local);
+            }
+
+            // Save the batch before anything else can go wrong; without it only a resume is lost
+            if let Some(checkpoint) = &checkpoint {
+                let entries: Vec<CheckpointEntry> = local
+                    .iter()
+                    .map(|(id, text)| CheckpointEntry { id: *id, text: text.clone(), cues: item_sizes[*id] })
+                    .collect();
+                if let Err(e) = checkpoint.record(&entries) {
+                    let _ = app.emit("translation://warning", e);
+                }
+            }
 
-            // Map back to original IDs and unmask tags
-            let mut local: Vec<(usize, String)> = Vec::new();
-            for (idx, &id) in batch.translate_ids.iter().enumerate() {
-                let text = &translations[idx];
-                
-                // Unmask tags
-                let cue: &SrtCue = &doc_cues[id];
-                let (_, mappings) = mask_tags(&cue.text_lines.join("\n"));
-                let unmasked = unmask_tags(text, &mappings);
-                
-                local.push((id, unmasked));
+            // Remember for next time (not translations that broke a term);
+            // a failed write only costs a future request
+            if let Some(memory) = &memory {
+                let entries = memory_keys.into_iter().filter(|(id, _)| {
+                    !broken_terms.iter().any(|v| v.cue_id == *id)
+                }).filter_map(|(id, key)| {
+                    local.iter().find(|(done_id, _)| *done_id == id).map(|(_, text)| (key, text.clone()))
+                });
+                if let Err(e) = memory.lock().unwrap().insert_many(entries) {
+                    let _ = app.emit("translation://warning", e);
+                }
             }
 
+            violations_ref.lock().unwrap().extend(broken_terms);
+
             // Store translated texts
+            let local_cues: usize = local.iter().map(|(id, _)| item_sizes[*id]).sum();
             let mut map_guard = translated_map.lock().unwrap();
             for (id, text) in local {
                 map_guard.insert(id, text);
@@ -509,12 +1082,12 @@ pub async fn translate_document(
 
             // Update progress
             let mut done_guard = done_cues_ref.lock().unwrap();
-            *done_guard += batch.translate_ids.len();
+            *done_guard += local_cues;
             let done_now = *done_guard;
             drop(done_guard);
 
             let elapsed = start_time.elapsed().as_secs_f64().max(0.001);
-            let rate = done_now as f64 / elapsed;
+            let rate = (done_now - already_done) as f64 / elapsed;
             let remaining = (total_cues - done_now) as f64;
             let eta = if rate > 0.0 { (remaining / rate).ceil() as u64 } else { 0 };
 
@@ -529,9 +1102,14 @@ pub async fn translate_document(
                 eta_seconds: eta,
                 stage: "translating".into(),
                 active_threads: 0,
+                memory_hits,
+                memory_misses,
             };
 
             let _ = app.emit("translation://progress", evt);
+            if failure.is_some() {
+                return Ok(failure);
+            }
 
             // Emit batch done
             let _ = app.emit("batch://status", BatchStatus {
@@ -544,15 +1122,133 @@ pub async fn translate_document(
                 error_msg: None,
             });
 
-            Ok::<(), TranslateError>(())
-        }));
+            Ok::<Option<(Vec<usize>, TranslateError)>, TranslateError>(None)
+        })));
     }
 
-    // Wait for all batches
-    for h in handles {
-        h.await.unwrap()?;
+    // Wait for every batch, so none is still writing after the job has ended
+    let mut failures: Vec<(Vec<usize>, TranslateError)> = Vec::new();
+    let mut cancelled = false;
+    for (batch_ids, h) in handles {
+        match h.await.unwrap() {
+            Ok(None) => {}
+            Ok(Some(failure)) => failures.push(failure),
+            Err(TranslateError::Cancelled) => cancelled = true,
+            // Failed outside the request itself (prompt, checkpoint, memory): the batch's
+            // unfinished cues fail like any other batch
+            Err(e) => {
+                let done = translated.lock().unwrap();
+                let failed_ids = batch_ids.into_iter().filter(|id| !done.contains_key(id)).collect();
+                failures.push((failed_ids, e));
+            }
+        }
+    }
+    if cancelled || control.is_cancelled() {
+        return Err(TranslateError::Cancelled);
+    }
+    // Abort, or nothing got translated at all: the job failed
+    let nothing_done = translated.lock().unwrap().is_empty();
+    if (opts.failure_policy == FailurePolicy::Abort || nothing_done) && !failures.is_empty() {
+        return Err(failures.swap_remove(0).1);
     }
 
+    // Report by cue id (the first cue of a sentence unit)
+    let mut violations = term_violations.lock().unwrap().clone();
+    if let Some(units) = &units {
+        for v in &mut violations {
+            v.cue_id = units[v.cue_id].cue_ids[0];
+        }
+        for m in &mut memory_matches {
+            m.cue_id = units[m.cue_id].cue_ids[0];
+        }
+    }
+    memory_matches.sort_by_key(|m| m.cue_id);
+    if !violations.is_empty() {
+        violations.sort_by_key(|v| v.cue_id);
+        let _ = app.emit(
+            "translation://warning",
+            format!("{} glossary terms weren't translated as required; see the flagged cues.", violations.len()),
+        );
+        let _ = app.emit("glossary://violations", serde_json::json!({
+            "job_id": job_id,
+            "violations": violations,
+        }));
+    }
+
+    let mut failed: Vec<FailedRange> = failures
+        .iter()
+        .flat_map(|(item_ids, e)| {
+            let cue_ids = match &units {
+                Some(units) => item_ids.iter().flat_map(|&id| units[id].cue_ids.iter().copied()).collect(),
+                None => item_ids.clone(),
+            };
+            failed_ranges(cue_ids, &e.to_string())
+        })
+        .collect();
+    failed.sort_by_key(|r| r.first_cue);
+
     let final_map = translated.lock().unwrap().clone();
-    Ok(final_map)
+    let translated = match &units {
+        Some(units) => redistribute(&doc.cues, units, &final_map),
+        None => final_map,
+    };
+    Ok(DocumentTranslation { translated, memory_matches, failed })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::srt::SrtTime;
+    use crate::translate::checkpoint::CheckpointHeader;
+
+    fn cue(id: usize) -> SrtCue {
+        SrtCue {
+            id,
+            index_line: (id + 1).to_string(),
+            timing_line: "00:00:01,000 --> 00:00:02,000".into(),
+            start: SrtTime::from_millis(1000),
+            end: SrtTime::from_millis(2000),
+            text_lines: vec![format!("Line {}", id)],
+            layout: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_failed_ranges_merge_consecutive_cues() {
+        let ranges = failed_ranges(vec![7, 3, 4, 5, 9, 4], "timeout");
+        let spans: Vec<(usize, usize)> = ranges.iter().map(|r| (r.first_cue, r.last_cue)).collect();
+        assert_eq!(spans, vec![(3, 5), (7, 7), (9, 9)]);
+        assert!(ranges.iter().all(|r| r.error == "timeout"));
+        assert!(failed_ranges(Vec::new(), "timeout").is_empty());
+    }
+
+    #[test]
+    fn test_retry_sends_only_failed_cues() {
+        let cues: Vec<SrtCue> = (0..8).map(cue).collect();
+        let mut opts = TranslationOptions::for_tests();
+        opts.batch = BatchConfig { batch_size: 10, context_before: 1, context_after: 1, ..BatchConfig::default() };
+        let path = crate::translate::temp_path("worker-retry");
+        let header = CheckpointHeader {
+            job_id: "job".into(),
+            file_id: "file".into(),
+            file_path: "/tmp/a.srt".into(),
+            file_name: "a.srt".into(),
+            source_hash: String::new(),
+            options: opts.clone(),
+            created_at: 0,
+        };
+        // The first run finished every batch but the ones holding 2-3 and 6
+        let checkpoint = Checkpoint::create(&path, &header).unwrap();
+        let finished: Vec<CheckpointEntry> =
+            [0, 1, 4, 5, 7].iter().map(|&id| CheckpointEntry { id, text: format!("Dòng {}", id), cues: 1 }).collect();
+        checkpoint.record(&finished).unwrap();
+
+        let done = checkpoint.done().unwrap();
+        let batches = pending_batches(&cues, &opts, &HashMap::new(), &HashMap::new(), &|id| done.contains_key(&id));
+        let sent: Vec<Vec<usize>> = batches.iter().map(|b| b.translate_ids.clone()).collect();
+        assert_eq!(sent, vec![vec![2, 3], vec![6]]);
+        // Finished neighbours are still shown as context
+        assert_eq!(batches[0].cues.iter().map(|c| c.id).collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+        checkpoint.remove().unwrap();
+    }
 }
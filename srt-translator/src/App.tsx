@@ -8,13 +8,18 @@ type FileItem = {
     path: string;
     name: string;
     cue_count: number;
+    diagnostics: { line: number; message: string; fix: string }[];
     status: "Ready" | "Processing" | "Done" | "Error";
 };
 
+/** ", imported with N fixes" when the lenient parser repaired the file. */
+const fixesNote = (file: FileItem) =>
+    file.diagnostics.length > 0 ? `, imported with ${file.diagnostics.length} fixes` : "";
+
 type JobInfo = {
     id: string;
     file_id: string;
-    status: "Queued" | "Running" | "Done" | "Error" | "Cancelled";
+    status: "Queued" | "Running" | "Paused" | "Done" | "PartiallyDone" | "Error" | "Cancelled";
     progress: number;
     eta_seconds: number;
     output_path?: string;
@@ -85,7 +90,7 @@ function App() {
                 setFiles([...files(), ...imported]);
                 if (imported.length > 0) {
                     setSelectedFile(imported[0]);
-                    setStatusMessage(`✓ Loaded ${imported[0].name} (${imported[0].cue_count} cues)`);
+                    setStatusMessage(`✓ Loaded ${imported[0].name} (${imported[0].cue_count} cues)${fixesNote(imported[0])}`);
                 }
             }
         } catch (error) {
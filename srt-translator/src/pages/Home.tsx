@@ -12,13 +12,18 @@ type FileItem = {
     path: string;
     name: string;
     cue_count: number;
+    diagnostics: { line: number; message: string; fix: string }[];
     status: "Ready" | "Processing" | "Done" | "Error";
 };
 
+/** ", imported with N fixes" when the lenient parser repaired the file. */
+const fixesNote = (file: FileItem) =>
+    file.diagnostics.length > 0 ? `, imported with ${file.diagnostics.length} fixes` : "";
+
 type JobInfo = {
     id: string;
     file_id: string;
-    status: "Queued" | "Running" | "Done" | "Error" | "Cancelled";
+    status: "Queued" | "Running" | "Paused" | "Done" | "PartiallyDone" | "Error" | "Cancelled";
     progress: number;
     eta_seconds: number;
     output_path?: string;
@@ -86,7 +91,7 @@ export default function HomePage() {
             const files = await invoke<FileItem[]>("import_srt_files", { paths: [selected] });
             if (files.length > 0) {
                 setSelectedFile(files[0]);
-                setStatusMessage(`✓ Loaded ${files[0].name} (${files[0].cue_count} cues)`);
+                setStatusMessage(`✓ Loaded ${files[0].name} (${files[0].cue_count} cues)${fixesNote(files[0])}`);
             }
         } catch (error: any) {
             setStatusMessage(`✗ Error loading file: ${String(error)}`);